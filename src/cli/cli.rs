@@ -1,4 +1,13 @@
-use crate::config::Config;
+use std::time::Duration;
+
+use crate::{
+    config::Config,
+    gui::app::{LibMsg, Msg},
+    library::{
+        ArtCache, ArtSize, DuplicateMode, Library, Query, Song, SongId,
+    },
+    player::Player,
+};
 
 use super::instance::Instance;
 
@@ -9,6 +18,8 @@ pub struct Cli {
     port: String,
     /// Instance cli parser
     instance: Instance,
+    /// Config used by actions working with the saved library
+    config: Config,
 }
 
 impl Cli {
@@ -18,6 +29,7 @@ impl Cli {
             ip: config.get_server_ip().to_owned(),
             port: config.get_server_port().to_owned(),
             instance: Instance::new(),
+            config: config.clone(),
         }
     }
 
@@ -35,6 +47,16 @@ impl Cli {
                     self.instance.parse(instance_args);
                     self.instance.submit(&self.ip, &self.port);
                 }
+                "purge" => self.purge(),
+                "errors" => self.errors(),
+                "duplicates" => self.duplicates(&args[1..]),
+                "search" => self.search(&args[1..].join(" ")),
+                "list" => self.list(&args[1..]),
+                "albums" => {
+                    self.albums(args.get(1).is_some_and(|a| a == "tracks"))
+                }
+                "artists" => self.artists(),
+                "organize" => self.organize(&args[1..]),
                 _ => eprintln!("Invalid argument: {arg}"),
             }
         }
@@ -59,6 +81,359 @@ impl Cli {
         println!("    Display help\n");
         println!("\x1b[93m  i, instance \x1b[90m[instance-action]\x1b[0m");
         println!("    Sends message given by action to running instance\n");
+        println!("\x1b[93m  purge\x1b[0m");
+        println!("    Removes deleted songs from the library\n");
+        println!("\x1b[93m  errors\x1b[0m");
+        println!("    Lists song files that failed to load\n");
+        println!("\x1b[93m  duplicates \x1b[90m[audio] [keep <id>]\x1b[0m");
+        println!("    Lists groups of duplicate songs, with keep given");
+        println!("    keeps the song with given ID and hides its duplicates");
+        println!("    audio: compares decoded audio instead of tags\n");
+        println!("\x1b[93m  search \x1b[90m[query]\x1b[0m");
+        println!(
+            "    Searches songs by name, artist, album, genre and path\n"
+        );
+        println!(
+            "\x1b[93m  list \x1b[90m[--query <query> | --smart <name>]\x1b[0m"
+        );
+        println!("    Lists songs matching the query or the smart playlist");
+        println!(
+            "    e.g. --query 'artist:\"Daft Punk\" year:>=2000 !offline'\n"
+        );
+        println!("\x1b[93m  albums \x1b[90m[tracks]\x1b[0m");
+        println!(
+            "    Lists albums, with tracks lists their songs and art too\n"
+        );
+        println!("\x1b[93m  artists\x1b[0m");
+        println!("    Lists artists with their albums\n");
+        println!("\x1b[93m  organize \x1b[90m[--apply] [template]\x1b[0m");
+        println!("    Lists where song files would be moved by the template,");
+        println!("    with --apply moves them, e.g. template");
+        println!(
+            "    '{{albumartist}}/{{album}}/{{track:02}} {{title}}.{{ext}}'\n"
+        );
         self.instance.help();
     }
+
+    /// Removes deleted songs from the library. When instance is running,
+    /// it's done by the instance, because it would overwrite the library.
+    fn purge(&self) {
+        let msg = Msg::Lib(LibMsg::Purge);
+        if Instance::send(&msg, &self.ip, &self.port).is_ok() {
+            println!("Purge was sent to the running instance");
+            return;
+        }
+
+        let mut lib = Library::load(&self.config);
+        let old = lib.clone();
+        let removed = lib.purge(None);
+
+        if let Err(e) = lib.save(&self.config) {
+            eprintln!("Failed to save library: {e}");
+            return;
+        }
+        if let Err(e) =
+            Player::remove_saved_songs(&self.config, &old, &removed)
+        {
+            eprintln!("Failed to update player playlist: {e}");
+        }
+        println!("Removed {} deleted songs", removed.len());
+    }
+
+    /// Lists song files that failed to load in the last library update
+    fn errors(&self) {
+        let lib = Library::load(&self.config);
+        for error in lib.get_errors() {
+            println!(
+                "\x1b[93m{}\x1b[0m: {}",
+                error.path.to_string_lossy(),
+                error.error
+            );
+        }
+        println!("{} song files failed to load", lib.get_errors().len());
+    }
+
+    /// Lists groups of duplicate songs, when `keep` is given, keeps the
+    /// given song from its group
+    fn duplicates(&self, args: &[String]) {
+        let mode = if args.iter().any(|a| a == "audio") {
+            DuplicateMode::Audio
+        } else {
+            DuplicateMode::Tags
+        };
+        let lib = Library::load(&self.config);
+        let groups = lib.find_duplicates(mode);
+
+        if let Some(i) = args.iter().position(|a| a == "keep") {
+            match args.get(i + 1).and_then(|id| id.parse().ok()) {
+                Some(id) => self.keep_duplicate(lib, &groups, SongId::new(id)),
+                None => eprintln!("Missing ID of the song to keep"),
+            }
+            return;
+        }
+
+        for (i, group) in groups.iter().enumerate() {
+            println!("\x1b[92mGroup {}:\x1b[0m", i + 1);
+            for song in group.iter().filter_map(|&id| lib.get_song_ref(id)) {
+                println!(
+                    "\x1b[93m  {}\x1b[0m {} - {} ({}) \x1b[90m{}\x1b[0m",
+                    song.get_id().get(),
+                    song.get_name(),
+                    song.get_artist(),
+                    song.get_length_str(),
+                    song.get_path().to_string_lossy()
+                );
+            }
+        }
+        println!("{} groups of duplicate songs", groups.len());
+    }
+
+    /// Keeps song with the given ID and marks other songs in its group as
+    /// its duplicates. When instance is running, it's done by the instance.
+    fn keep_duplicate(
+        &self,
+        mut lib: Library,
+        groups: &[Vec<SongId>],
+        keep: SongId,
+    ) {
+        let others: Vec<SongId> =
+            match groups.iter().find(|g| g.contains(&keep)) {
+                Some(group) => {
+                    group.iter().copied().filter(|&id| id != keep).collect()
+                }
+                None => {
+                    eprintln!("Song {} has no duplicates", keep.get());
+                    return;
+                }
+            };
+
+        let msg = Msg::Lib(LibMsg::Merge(keep, others.clone()));
+        if Instance::send(&msg, &self.ip, &self.port).is_ok() {
+            println!("Merge was sent to the running instance");
+            return;
+        }
+
+        let merged = lib.merge(keep, &others);
+        if let Err(e) = lib.save(&self.config) {
+            eprintln!("Failed to save library: {e}");
+            return;
+        }
+        if let Err(e) =
+            Player::replace_saved_songs(&self.config, &lib, &merged, keep)
+        {
+            eprintln!("Failed to update player playlist: {e}");
+        }
+        println!("Merged {} duplicates", merged.len());
+    }
+
+    /// Searches songs in the library and lists them from the best match
+    fn search(&self, query: &str) {
+        let lib = Library::load(&self.config);
+        Cli::print_songs(&lib, &lib.search(query));
+    }
+
+    /// Lists songs matching the query or the smart playlist with the given
+    /// name, without arguments lists all the songs
+    fn list(&self, args: &[String]) {
+        let query = match args.first().map(|a| a.as_str()) {
+            None => String::new(),
+            Some("-q" | "--query") => args[1..].join(" "),
+            Some("-s" | "--smart") => {
+                let name = args[1..].join(" ");
+                match self.config.get_smart_playlist(&name) {
+                    Some(playlist) => playlist.get_query().to_owned(),
+                    None => {
+                        eprintln!("Smart playlist '{name}' doesn't exist");
+                        return;
+                    }
+                }
+            }
+            Some(arg) => {
+                eprintln!("Invalid argument: {arg}");
+                return;
+            }
+        };
+
+        let query = match Query::parse(&query) {
+            Ok(query) => query,
+            Err(e) => {
+                eprintln!("Invalid query: {e}");
+                return;
+            }
+        };
+        let lib = Library::load(&self.config);
+        Cli::print_songs(&lib, &lib.query(&query));
+    }
+
+    /// Lists albums of the library, with `tracks` lists their songs and art
+    /// too
+    fn albums(&self, tracks: bool) {
+        let lib = Library::load(&self.config);
+        for album in lib.albums() {
+            let year = album
+                .get_date()
+                .map(|d| d.to_owned())
+                .or(album.get_year().map(|y| y.to_string()));
+            println!(
+                "\x1b[93m{}\x1b[0m - {} \x1b[90m{}, {} songs, {}\x1b[0m",
+                album.get_name(),
+                album.get_artist(),
+                year.as_deref().unwrap_or("-"),
+                album.get_songs().len(),
+                Cli::duration_str(album.get_length())
+            );
+            if !tracks {
+                continue;
+            }
+            if let Some(art) = album.get_art() {
+                let path =
+                    ArtCache::new(&self.config).path(art, ArtSize::Large);
+                println!("  \x1b[90mart: {}\x1b[0m", path.display());
+            }
+            for song in album
+                .get_songs()
+                .iter()
+                .filter_map(|&id| lib.get_song_ref(id))
+            {
+                println!(
+                    "  \x1b[90m{:>7}\x1b[0m {} - {} \x1b[90m{}\x1b[0m",
+                    Cli::track_str(song),
+                    song.get_name(),
+                    song.get_artist(),
+                    song.get_length_str()
+                );
+            }
+        }
+        println!("{} albums", lib.albums().len());
+    }
+
+    /// Lists artists of the library with their albums
+    fn artists(&self) {
+        let lib = Library::load(&self.config);
+        for artist in lib.artists() {
+            println!(
+                "\x1b[93m{}\x1b[0m \x1b[90m{} songs\x1b[0m",
+                artist.get_name(),
+                artist.get_songs().len()
+            );
+            for album in artist
+                .get_albums()
+                .iter()
+                .filter_map(|&i| lib.albums().get(i))
+            {
+                if album.is_compilation() {
+                    println!(
+                        "  {} \x1b[90m(compilation)\x1b[0m",
+                        album.get_name()
+                    );
+                } else {
+                    println!("  {}", album.get_name());
+                }
+            }
+        }
+        println!("{} artists", lib.artists().len());
+    }
+
+    /// Lists where song files would be moved by the template, when
+    /// `--apply` is given, moves them. Without template, template from
+    /// config is used. When instance is running, the listed moves are sent
+    /// to it.
+    fn organize(&self, args: &[String]) {
+        let apply = args.iter().any(|a| a == "--apply");
+        let template: Vec<&str> = args
+            .iter()
+            .filter(|a| *a != "--apply")
+            .map(|a| a.as_str())
+            .collect();
+        let template = match template.join(" ") {
+            t if t.trim().is_empty() => {
+                self.config.get_organize_template().to_owned()
+            }
+            t => t,
+        };
+        let mut lib = Library::load(&self.config);
+        let plan = match lib.plan_organize(&self.config, &template) {
+            Ok(plan) => plan,
+            Err(e) => {
+                eprintln!("Invalid template: {e}");
+                return;
+            }
+        };
+        for m in plan.moves.iter() {
+            println!(
+                "\x1b[90m{}\x1b[0m\n  -> {}",
+                m.from.to_string_lossy(),
+                m.to.to_string_lossy()
+            );
+            if let Some(conflict) = m.conflict_str() {
+                println!("  \x1b[91mnot moved, {conflict}\x1b[0m");
+            }
+        }
+        let valid = plan.moves.iter().filter(|m| m.conflict.is_none()).count();
+        if !apply {
+            println!(
+                "{valid} songs would be moved, {} have conflicts",
+                plan.moves.len() - valid
+            );
+            return;
+        }
+
+        // Running instance refuses the plan when its library differs
+        let msg = Msg::Lib(LibMsg::OrganizePlan(plan.clone()));
+        if Instance::send(&msg, &self.ip, &self.port).is_ok() {
+            println!("Organizing was sent to the running instance");
+            return;
+        }
+
+        match lib.organize(&self.config, &plan) {
+            Ok(moved) => println!("Moved {moved} songs"),
+            Err(e) => {
+                eprintln!("Failed to organize library: {e}");
+                return;
+            }
+        }
+        if let Err(e) = lib.save(&self.config) {
+            eprintln!("Failed to save library: {e}");
+        }
+    }
+
+    /// Formats track number of the song such as `3/12`, disc number is
+    /// added for albums with more discs, such as `2.3/12`
+    fn track_str(song: &Song) -> String {
+        let mut res = match song.get_track() {
+            Some(track) => track.to_string(),
+            None => "-".to_owned(),
+        };
+        if let Some(total) = song.get_track_total() {
+            res = format!("{res}/{total}");
+        }
+        match (song.get_disc(), song.get_disc_total()) {
+            (Some(disc), Some(total)) if total > 1 => format!("{disc}.{res}"),
+            (Some(disc), None) if disc > 1 => format!("{disc}.{res}"),
+            _ => res,
+        }
+    }
+
+    /// Formats duration as `h:mm:ss` or `m:ss`
+    fn duration_str(duration: Duration) -> String {
+        let secs = duration.as_secs();
+        if secs >= 3600 {
+            format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+        } else {
+            format!("{}:{:02}", secs / 60, secs % 60)
+        }
+    }
+
+    /// Prints songs with given IDs
+    fn print_songs(lib: &Library, ids: &[SongId]) {
+        for song in ids.iter().filter_map(|&id| lib.get_song_ref(id)) {
+            println!(
+                "\x1b[93m{}\x1b[0m - {} \x1b[90m{}\x1b[0m",
+                song.get_name(),
+                song.get_artist(),
+                song.get_path().to_string_lossy()
+            );
+        }
+        println!("{} songs found", ids.len());
+    }
 }
@@ -1,5 +1,7 @@
 use std::{io::prelude::*, net::TcpStream, time::Duration};
 
+use eyre::Result;
+
 use crate::{
     gui::app::{LibMsg, Msg},
     player::PlayerMsg,
@@ -105,6 +107,11 @@ impl Instance {
             }
             "shuffle" | "mix" => Some(Msg::Plr(PlayerMsg::Shuffle)),
             "load-songs" => Some(Msg::Lib(LibMsg::LoadStart)),
+            "purge" => Some(Msg::Lib(LibMsg::Purge)),
+            s if s.starts_with("smart=") => {
+                let name = s["smart=".len()..].to_owned();
+                Some(Msg::Lib(LibMsg::PlaySmart(name)))
+            }
             "exit" | "close" | "quit" => Some(Msg::Close),
             _ => None,
         }
@@ -135,6 +142,10 @@ impl Instance {
         println!("    Mute or unmute, no parameter toggles\n");
         println!("\x1b[93m  shuffle, mix\x1b[0m");
         println!("    Shuffles current playlist\n");
+        println!("\x1b[93m  purge\x1b[0m");
+        println!("    Removes deleted songs from the library\n");
+        println!("\x1b[93m  smart\x1b[0m=<name>");
+        println!("    Plays smart playlist with given name\n");
         println!("\x1b[93m  exit, close, quit\x1b[0m");
         println!("    Closes running instance");
     }
@@ -146,20 +157,24 @@ impl Instance {
         }
     }
 
+    /// Sends given message to the running instance, returns its response
+    pub fn send(msg: &Msg, ip: &str, port: &str) -> Result<String> {
+        let mut stream = TcpStream::connect(format!("{ip}:{port}"))?;
+        let msg = serde_json::to_string::<Msg>(msg)?;
+        stream.write_all(format!("{msg}\n").as_bytes())?;
+
+        let mut response = String::new();
+        _ = stream.read_to_string(&mut response);
+        Ok(response)
+    }
+
     /// Sends given message to the server
     fn send_msg(&self, msg: &Msg, ip: &str, port: &str) {
-        let stream = TcpStream::connect(format!("{ip}:{port}"));
-        match stream {
-            Ok(mut stream) => {
-                if let Ok(msg) = serde_json::to_string::<Msg>(msg) {
-                    _ = stream.write_all(format!("{msg}\n").as_bytes());
-                    let mut response = String::new();
-                    _ = stream.read_to_string(&mut response);
-                    if response.starts_with("Error") {
-                        println!("{response}");
-                    }
-                }
+        match Instance::send(msg, ip, port) {
+            Ok(response) if response.starts_with("Error") => {
+                println!("{response}")
             }
+            Ok(_) => {}
             Err(_) => eprintln!("Error connecting to the server"),
         }
     }
@@ -5,7 +5,7 @@ use serde_derive::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
     fs::{self, File},
-    path::PathBuf,
+    path::{Path, PathBuf},
     time::Duration,
 };
 
@@ -13,19 +13,17 @@ use crate::{
     generate_struct,
     gui::app::BumpApp,
     hotkeys::{Hotkey, Hotkeys},
+    library::{LibWatcher, ReplayGainMode},
 };
 
-use super::ConfMsg;
+use super::{ConfMsg, SearchPath, SmartPlaylist};
 
 generate_struct! {
     #[derive(Clone, Serialize, Deserialize)]
     pub Config {
-        paths: Vec<PathBuf> => {
-            if let Some(dir) = dirs::audio_dir() {
-                vec![dir]
-            } else {
-                vec![PathBuf::from(".")]
-            }
+        paths: Vec<SearchPath> => {
+            let dir = dirs::audio_dir().unwrap_or(PathBuf::from("."));
+            vec![SearchPath::new(dir, true)]
         },
         extensions: Vec<String> => {
             vec![
@@ -38,6 +36,7 @@ generate_struct! {
         library_path: PathBuf => Config::get_config_dir().join("library.json"),
         gui_path: PathBuf => Config::get_config_dir().join("gui.json"),
         player_path: PathBuf => Config::get_config_dir().join("player.json"),
+        art_path: PathBuf => Config::get_cache_dir().join("art"),
         server_ip: String => "127.0.0.1".to_owned(),
         server_port: String => {
             #[cfg(debug_assertions)]
@@ -58,15 +57,51 @@ generate_struct! {
             hotkeys.insert("ctrl+alt+down".to_owned(), "vd".to_owned());
             hotkeys
         },
+        ignore: Vec<String> => Vec::new(),
+        smart_playlists: Vec<SmartPlaylist> => Vec::new(),
+        art_files: Vec<String> => {
+            ["cover.*", "folder.*", "front.*", "album.*"]
+                .map(|p| p.to_owned())
+                .to_vec()
+        },
+        organize_template: String => {
+            "{albumartist}/{year} - {album}/{disc}{track:02} {title}.{ext}"
+                .to_owned()
+        },
+        tag_patterns: Vec<String> => {
+            [
+                "{artist}/{album}/{track} - {title}",
+                "{artist} - {album}/{track} - {title}",
+                "{track} - {title}",
+                "{artist} - {title}",
+            ]
+            .map(|p| p.to_owned())
+            .to_vec()
+        },
+        legacy_encoding: Option<String> => None,
         ;
         fade: Duration => Duration::from_millis(150),
         volume_step: f32 => 0.1,
         recursive_search: bool => true,
+        follow_symlinks: bool => true,
+        max_search_depth: usize => 64,
+        probe_content: bool => false,
         shuffle_current: bool => false,
         autoplay: bool => false,
         start_load: bool => true,
+        watch_paths: bool => true,
         gapless: bool => false,
         enable_hotkeys: bool => true,
+        auto_purge: Option<u32> => None,
+        folder_art_first: bool => false,
+        replay_gain: ReplayGainMode => ReplayGainMode::Off,
+        replay_gain_preamp: f32 => 0.,
+        prevent_clipping: bool => true,
+        auto_analyze: bool => false,
+        write_loudness_tags: bool => false,
+        analysis_pause: Duration => Duration::from_millis(200),
+        normalize_tags: bool => true,
+        merge_artists: bool => true,
         ;
     }
 }
@@ -77,12 +112,22 @@ impl Config {
         let mut path = Config::get_config_dir();
         path.push("config.json");
 
-        match serde_json::from_str::<Config>(
+        let mut conf = match serde_json::from_str::<Config>(
             &fs::read_to_string(path).unwrap_or("".to_owned()),
         ) {
             Ok(conf) => conf,
             Err(_) => Config::default(),
+        };
+
+        // Search paths from older config use the global search settings
+        let recursive = conf.recursive_search;
+        for path in conf.paths.iter_mut() {
+            if path.take_legacy() {
+                path.set_recursive(recursive);
+                conf.changed = true;
+            }
         }
+        conf
     }
 
     /// Saves config to the config directory
@@ -120,14 +165,35 @@ impl Config {
         }
     }
 
-    /// Adds given path to paths
+    /// Adds given path to paths, recursive search is set by the config
     pub fn add_path(&mut self, paths: Vec<PathBuf>) {
         self.changed = true;
         for path in paths {
-            self.paths.push(path);
+            self.paths
+                .push(SearchPath::new(path, self.recursive_search));
         }
     }
 
+    /// Edits search path on given index using the given function
+    pub fn edit_path<F>(&mut self, id: usize, f: F)
+    where
+        F: FnOnce(&mut SearchPath),
+    {
+        if let Some(path) = self.paths.get_mut(id) {
+            self.changed = true;
+            f(path);
+        }
+    }
+
+    /// Gets search path the given path is in, when search paths are nested,
+    /// the innermost one is returned
+    pub fn get_search_path(&self, path: &Path) -> Option<&SearchPath> {
+        self.paths
+            .iter()
+            .filter(|p| path.starts_with(p.get_path()))
+            .max_by_key(|p| p.get_path().components().count())
+    }
+
     /// Removes path on given index
     pub fn remove_path(&mut self, id: usize) {
         if id < self.paths.len() {
@@ -136,6 +202,48 @@ impl Config {
         }
     }
 
+    /// Adds given pattern to the ignore patterns
+    pub fn add_ignore(&mut self, pattern: String) {
+        if !pattern.is_empty() && !self.ignore.contains(&pattern) {
+            self.changed = true;
+            self.ignore.push(pattern);
+        }
+    }
+
+    /// Removes ignore pattern on given index
+    pub fn remove_ignore(&mut self, id: usize) {
+        if id < self.ignore.len() {
+            self.changed = true;
+            self.ignore.remove(id);
+        }
+    }
+
+    /// Adds smart playlist, playlist with the same name is replaced
+    pub fn add_smart_playlist(&mut self, playlist: SmartPlaylist) {
+        self.changed = true;
+        match self
+            .smart_playlists
+            .iter_mut()
+            .find(|p| p.get_name() == playlist.get_name())
+        {
+            Some(p) => *p = playlist,
+            None => self.smart_playlists.push(playlist),
+        }
+    }
+
+    /// Removes smart playlist on given index
+    pub fn remove_smart_playlist(&mut self, id: usize) {
+        if id < self.smart_playlists.len() {
+            self.changed = true;
+            self.smart_playlists.remove(id);
+        }
+    }
+
+    /// Gets smart playlist with the given name
+    pub fn get_smart_playlist(&self, name: &str) -> Option<&SmartPlaylist> {
+        self.smart_playlists.iter().find(|p| p.get_name() == name)
+    }
+
     /// Gets config dir path
     pub fn get_config_dir() -> PathBuf {
         if let Some(mut dir) = dirs::config_dir() {
@@ -146,6 +254,16 @@ impl Config {
         }
     }
 
+    /// Gets cache dir path
+    pub fn get_cache_dir() -> PathBuf {
+        if let Some(mut dir) = dirs::cache_dir() {
+            dir.push(Config::get_app_id());
+            dir
+        } else {
+            PathBuf::from(".")
+        }
+    }
+
     /// Gets server address
     pub fn get_server_address(&self) -> String {
         format!("{}:{}", self.get_server_ip(), self.get_server_port())
@@ -172,18 +290,39 @@ impl Default for Config {
             library_path: Config::default_library_path(),
             gui_path: Config::default_gui_path(),
             player_path: Config::default_player_path(),
+            art_path: Config::default_art_path(),
             recursive_search: Config::default_recursive_search(),
+            follow_symlinks: Config::default_follow_symlinks(),
+            max_search_depth: Config::default_max_search_depth(),
+            probe_content: Config::default_probe_content(),
             shuffle_current: Config::default_shuffle_current(),
             fade: Config::default_fade(),
             volume_step: Config::default_volume_step(),
             autoplay: Config::default_autoplay(),
             start_load: Config::default_start_load(),
+            watch_paths: Config::default_watch_paths(),
             gapless: Config::default_gapless(),
             changed: true,
             server_ip: Config::default_server_ip(),
             server_port: Config::default_server_port(),
             hotkeys: Config::default_hotkeys(),
             enable_hotkeys: Config::default_enable_hotkeys(),
+            auto_purge: Config::default_auto_purge(),
+            ignore: Config::default_ignore(),
+            smart_playlists: Config::default_smart_playlists(),
+            art_files: Config::default_art_files(),
+            organize_template: Config::default_organize_template(),
+            tag_patterns: Config::default_tag_patterns(),
+            legacy_encoding: Config::default_legacy_encoding(),
+            folder_art_first: Config::default_folder_art_first(),
+            replay_gain: Config::default_replay_gain(),
+            replay_gain_preamp: Config::default_replay_gain_preamp(),
+            prevent_clipping: Config::default_prevent_clipping(),
+            auto_analyze: Config::default_auto_analyze(),
+            write_loudness_tags: Config::default_write_loudness_tags(),
+            analysis_pause: Config::default_analysis_pause(),
+            normalize_tags: Config::default_normalize_tags(),
+            merge_artists: Config::default_merge_artists(),
         }
     }
 }
@@ -194,19 +333,80 @@ impl Default for Config {
 impl BumpApp {
     pub fn conf_update(&mut self, msg: ConfMsg) {
         match msg {
-            ConfMsg::AddPath(paths) => self.config.add_path(paths),
-            ConfMsg::RemPath(id) => self.config.remove_path(id),
+            ConfMsg::AddPath(paths) => {
+                self.config.add_path(paths);
+                self.enable_watcher(self.config.get_watch_paths());
+            }
+            ConfMsg::RemPath(id) => {
+                self.config.remove_path(id);
+                self.settings.path_exts.clear();
+                self.settings.path_depths.clear();
+                self.enable_watcher(self.config.get_watch_paths());
+            }
+            ConfMsg::AddIgnore(pattern) => {
+                self.config.add_ignore(pattern.trim().to_owned());
+                self.settings.ignore.clear();
+            }
+            ConfMsg::RemIgnore(id) => self.config.remove_ignore(id),
+            ConfMsg::RemSmartPlaylist(id) => {
+                self.config.remove_smart_playlist(id)
+            }
             ConfMsg::EnableHotkeys(val) => self.enable_hotkeys(val),
             ConfMsg::RecursiveSearch(val) => {
                 self.config.set_recursive_search(val);
             }
+            ConfMsg::PathEnabled(id, val) => {
+                self.config.edit_path(id, |p| p.set_enabled(val));
+                self.enable_watcher(self.config.get_watch_paths());
+            }
+            ConfMsg::PathRecursive(id, val) => {
+                self.config.edit_path(id, |p| p.set_recursive(val));
+                self.enable_watcher(self.config.get_watch_paths());
+            }
+            ConfMsg::PathDepth(id, val) => {
+                self.config.edit_path(id, |p| p.set_max_depth(val));
+                self.settings.path_depths.remove(&id);
+            }
+            ConfMsg::PathExtensions(id, val) => {
+                self.config.edit_path(id, |p| p.set_extensions(val));
+                self.settings.path_exts.remove(&id);
+            }
             ConfMsg::ShuffleCurrent(val) => {
                 self.config.set_shuffle_current(val);
                 self.player.load_config(&self.config);
             }
             ConfMsg::Autoplay(val) => self.config.set_autoplay(val),
             ConfMsg::StartLoad(val) => self.config.set_start_load(val),
+            ConfMsg::WatchPaths(val) => self.enable_watcher(val),
+            ConfMsg::FollowSymlinks(val) => {
+                self.config.set_follow_symlinks(val)
+            }
+            ConfMsg::ProbeContent(val) => self.config.set_probe_content(val),
+            ConfMsg::FolderArtFirst(val) => {
+                self.config.set_folder_art_first(val);
+                self.library.update_folder_art(&self.config);
+                self.update_shown();
+            }
             ConfMsg::Gapless(val) => self.config.set_gapless(val),
+            ConfMsg::ReplayGain(mode) => {
+                self.config.set_replay_gain(mode);
+                self.player.load_config(&self.config);
+                self.player.apply_gain(&self.library);
+            }
+            ConfMsg::PreventClipping(val) => {
+                self.config.set_prevent_clipping(val);
+                self.player.load_config(&self.config);
+                self.player.apply_gain(&self.library);
+            }
+            ConfMsg::AutoAnalyze(val) => {
+                self.config.set_auto_analyze(val);
+                self.auto_analyze();
+            }
+            ConfMsg::WriteLoudnessTags(val) => {
+                self.config.set_write_loudness_tags(val)
+            }
+            ConfMsg::NormalizeTags(val) => self.config.set_normalize_tags(val),
+            ConfMsg::MergeArtists(val) => self.config.set_merge_artists(val),
             ConfMsg::ResetAll => self.config.reset_all(),
         }
     }
@@ -231,4 +431,23 @@ impl BumpApp {
         };
         self.config.set_enable_hotkeys(val);
     }
+
+    /// Enables/disables watching of the library search paths, when enabled
+    /// the watcher is restarted to watch the current paths
+    pub fn enable_watcher(&mut self, mut val: bool) {
+        // Drops the old watcher first, so paths aren't watched twice
+        self.watcher = None;
+        if val {
+            self.watcher =
+                match LibWatcher::new(&self.config, self.sender.clone()) {
+                    Ok(watcher) => Some(watcher),
+                    Err(e) => {
+                        val = false;
+                        error!("Failed to watch library: {e}");
+                        None
+                    }
+                };
+        }
+        self.config.set_watch_paths(val);
+    }
 }
@@ -1,4 +1,6 @@
 mod config;
 mod msg;
+mod search_path;
+mod smart_playlist;
 
-pub use self::{config::*, msg::*};
+pub use self::{config::*, msg::*, search_path::*, smart_playlist::*};
@@ -1,16 +1,35 @@
 use serde_derive::{Deserialize, Serialize};
 use std::path::PathBuf;
 
+use crate::library::ReplayGainMode;
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum ConfMsg {
     AddPath(Vec<PathBuf>),
     RemPath(usize),
+    PathEnabled(usize, bool),
+    PathRecursive(usize, bool),
+    PathDepth(usize, Option<usize>),
+    PathExtensions(usize, Vec<String>),
+    AddIgnore(String),
+    RemIgnore(usize),
+    RemSmartPlaylist(usize),
     EnableHotkeys(bool),
     RecursiveSearch(bool),
+    FollowSymlinks(bool),
+    ProbeContent(bool),
+    FolderArtFirst(bool),
     ShuffleCurrent(bool),
     Autoplay(bool),
     StartLoad(bool),
+    WatchPaths(bool),
     Gapless(bool),
+    ReplayGain(ReplayGainMode),
+    PreventClipping(bool),
+    AutoAnalyze(bool),
+    WriteLoudnessTags(bool),
+    NormalizeTags(bool),
+    MergeArtists(bool),
 
     ResetAll,
 }
@@ -0,0 +1,159 @@
+use std::path::{Path, PathBuf};
+
+use serde_derive::{Deserialize, Serialize};
+
+/// Library search path with its own search settings
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(from = "SearchPathLoad")]
+pub struct SearchPath {
+    /// Path to the searched directory
+    path: PathBuf,
+    /// When true, songs are searched in subdirectories
+    recursive: bool,
+    /// Maximal depth of searched subdirectories, None when not limited
+    max_depth: Option<usize>,
+    /// Extensions of the songs, when empty extensions from config are used
+    extensions: Vec<String>,
+    /// When false, the path isn't searched and its songs are kept as is
+    enabled: bool,
+    /// True when loaded from config saved before search path settings were
+    /// added, such search paths get the settings from the config
+    #[serde(skip)]
+    legacy: bool,
+}
+
+impl SearchPath {
+    /// Creates new enabled [`SearchPath`]
+    pub fn new(path: PathBuf, recursive: bool) -> Self {
+        Self {
+            path,
+            recursive,
+            max_depth: None,
+            extensions: Vec::new(),
+            enabled: true,
+            legacy: false,
+        }
+    }
+
+    /// Checks whether directory in given depth should be searched, depth
+    /// of the search path is 0
+    pub fn search_depth(&self, depth: usize) -> bool {
+        match (self.recursive, self.max_depth) {
+            (false, _) => depth == 0,
+            (true, Some(max)) => depth <= max,
+            (true, None) => true,
+        }
+    }
+
+    /// Gets extensions of the songs, falls back to the given default
+    /// extensions when the search path has no extensions set
+    pub fn extensions<'a>(&'a self, default: &'a [String]) -> &'a [String] {
+        if self.extensions.is_empty() {
+            default
+        } else {
+            &self.extensions
+        }
+    }
+
+    //>=====================================================================<//
+    //                           Getters & Setters                           //
+    //>=====================================================================<//
+
+    /// Gets the path to the searched directory
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Gets whether subdirectories are searched
+    pub fn get_recursive(&self) -> bool {
+        self.recursive
+    }
+
+    /// Sets whether subdirectories are searched
+    pub fn set_recursive(&mut self, recursive: bool) {
+        self.recursive = recursive;
+    }
+
+    /// Gets maximal depth of searched subdirectories
+    pub fn get_max_depth(&self) -> Option<usize> {
+        self.max_depth
+    }
+
+    /// Sets maximal depth of searched subdirectories
+    pub fn set_max_depth(&mut self, max_depth: Option<usize>) {
+        self.max_depth = max_depth;
+    }
+
+    /// Gets extensions set for the search path
+    pub fn get_extensions(&self) -> &Vec<String> {
+        &self.extensions
+    }
+
+    /// Sets extensions for the search path
+    pub fn set_extensions(&mut self, extensions: Vec<String>) {
+        self.extensions = extensions;
+    }
+
+    /// Gets whether the search path is searched
+    pub fn get_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Sets whether the search path is searched
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Checks whether the search path was loaded from older config and
+    /// clears the flag
+    pub fn take_legacy(&mut self) -> bool {
+        std::mem::take(&mut self.legacy)
+    }
+}
+
+/// Search path as saved in the config, older configs contain only paths
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SearchPathLoad {
+    Path(PathBuf),
+    Full {
+        path: PathBuf,
+        #[serde(default = "default_true")]
+        recursive: bool,
+        #[serde(default)]
+        max_depth: Option<usize>,
+        #[serde(default)]
+        extensions: Vec<String>,
+        #[serde(default = "default_true")]
+        enabled: bool,
+    },
+}
+
+impl From<SearchPathLoad> for SearchPath {
+    fn from(value: SearchPathLoad) -> Self {
+        match value {
+            SearchPathLoad::Path(path) => Self {
+                legacy: true,
+                ..SearchPath::new(path, true)
+            },
+            SearchPathLoad::Full {
+                path,
+                recursive,
+                max_depth,
+                extensions,
+                enabled,
+            } => Self {
+                path,
+                recursive,
+                max_depth,
+                extensions,
+                enabled,
+                legacy: false,
+            },
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
@@ -0,0 +1,31 @@
+use serde_derive::{Deserialize, Serialize};
+
+/// Named playlist of the library songs matching the query
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SmartPlaylist {
+    /// Name of the playlist
+    name: String,
+    /// Query the songs of the playlist match
+    query: String,
+}
+
+impl SmartPlaylist {
+    /// Creates new [`SmartPlaylist`]
+    pub fn new(name: String, query: String) -> Self {
+        Self { name, query }
+    }
+
+    //>=====================================================================<//
+    //                           Getters & Setters                           //
+    //>=====================================================================<//
+
+    /// Gets the name of the playlist
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Gets the query the songs of the playlist match
+    pub fn get_query(&self) -> &str {
+        &self.query
+    }
+}
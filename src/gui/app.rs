@@ -1,5 +1,8 @@
 use std::cell::Cell;
+use std::collections::HashSet;
 use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
 
@@ -12,12 +15,16 @@ use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
 
 use crate::config::{ConfMsg, Config};
 use crate::hotkeys::Hotkeys;
-use crate::library::Library;
+use crate::library::{
+    ArtCache, DuplicateMode, FolderArt, LibWatcher, Library, Loudness, Lyrics,
+    OrganizeMove, OrganizePlan, ScanProgress, SongId, SortField,
+};
 use crate::player::{Player, PlayerMsg};
-use crate::server::Server;
+use crate::server::{NowPlaying, Server};
 
 use super::gui::Gui;
 use super::settings::{Settings, SettingsMsg};
+use super::tag_editor::{TagEditor, TagMsg};
 use super::theme::Theme;
 
 pub struct BumpApp {
@@ -32,7 +39,25 @@ pub struct BumpApp {
     pub hard_pause: Option<Instant>,
     listener: Cell<Option<TcpListener>>,
     pub hotkeys: Option<Hotkeys>,
+    pub watcher: Option<LibWatcher>,
     pub settings: Settings,
+    /// Text filtering songs in the library
+    pub filter: String,
+    /// Songs shown in the library, matching the filter and sorted
+    pub shown: Vec<SongId>,
+    /// Error of parsing the filter
+    pub filter_error: Option<String>,
+    /// Lyrics of the song with the given ID, loaded when the lyrics page
+    /// is shown
+    pub lyrics: Option<(SongId, Option<Lyrics>)>,
+    /// Song whose lyrics are being loaded in the background
+    pub lyrics_loading: Option<SongId>,
+    /// Songs selected in the library
+    pub selected: HashSet<SongId>,
+    /// Tags of the selected songs being edited
+    pub tag_editor: TagEditor,
+    /// Playing song shared with the server for the web remote
+    now_playing: Arc<Mutex<NowPlaying>>,
 }
 
 /// All pages enum
@@ -40,14 +65,54 @@ pub struct BumpApp {
 pub enum Page {
     Library,
     Playlist,
+    Lyrics,
     Settings,
+    Tags,
 }
 
-/// Library messages
+/// Library messages, results of background jobs are skipped by serde so
+/// that they can't be sent by the server
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum LibMsg {
     LoadStart,
+    #[serde(skip)]
     LoadEnded,
+    #[serde(skip)]
+    LoadProgress(ScanProgress),
+    LoadCancel,
+    #[serde(skip)]
+    Changed(Vec<PathBuf>),
+    #[serde(skip)]
+    RootsChecked(Vec<PathBuf>),
+    #[serde(skip)]
+    FolderArt(Vec<(PathBuf, Option<FolderArt>)>),
+    Purge,
+    FindDuplicates(DuplicateMode),
+    #[serde(skip)]
+    Duplicates(Vec<Vec<SongId>>),
+    Merge(SongId, Vec<SongId>),
+    Filter(String),
+    PlaySmart(String),
+    Sort(SortField),
+    Organize,
+    OrganizePlan(OrganizePlan),
+    #[serde(skip)]
+    Moved(OrganizeMove),
+    #[serde(skip)]
+    OrganizeEnded,
+    Select(SongId, bool),
+    ClearSelection,
+    WriteInferred,
+    WriteNormalized,
+    #[serde(skip)]
+    TagsWritten(Vec<PathBuf>, usize),
+    Reload,
+    AnalysisStart,
+    AnalysisCancel,
+    #[serde(skip)]
+    Analyzed(SongId, Option<Loudness>),
+    #[serde(skip)]
+    AnalysisEnded,
 }
 
 /// Bump app messages
@@ -58,7 +123,10 @@ pub enum Msg {
     Lib(LibMsg),
     Conf(ConfMsg),
     Settings(SettingsMsg),
+    Tags(TagMsg),
     Tick,
+    #[serde(skip)]
+    Lyrics(SongId, Option<Lyrics>),
     Move(i32, i32),
     Size(u32, u32),
     Close,
@@ -89,18 +157,19 @@ impl Application for BumpApp {
             Msg::Page(msg) => {
                 self.gui.get_wb_state_mut(1).get_mut().scroll_to =
                     self.player.get_current();
-                self.page = msg
+                self.page = msg;
+                // Lyrics are loaded again, their files could change
+                self.lyrics = None;
             }
             Msg::Plr(msg) => self.player_update(msg),
-            Msg::Lib(msg) => {
-                self.library
-                    .handle_msg(&self.config, self.sender.clone(), msg)
-            }
+            Msg::Lib(msg) => self.lib_update(msg),
             Msg::Conf(msg) => self.conf_update(msg),
             Msg::Settings(msg) => {
                 return self.settings_update(msg);
             }
-            Msg::Tick => {}
+            Msg::Tags(msg) => return self.tags_update(msg),
+            Msg::Tick => self.check_roots(),
+            Msg::Lyrics(id, lyrics) => self.lyrics_loaded(id, lyrics),
             Msg::Move(x, y) => self.gui.set_pos(x, y),
             Msg::Size(w, h) => self.gui.set_size(w, h),
             Msg::Close => {
@@ -109,6 +178,9 @@ impl Application for BumpApp {
             }
             Msg::HardPause(i) => self.hard_pause = Some(i),
         };
+        self.update_lyrics();
+        self.library.set_playing(self.player.get_current_id());
+        self.update_now_playing();
         // Handle hard pause
         if let Some(i) = self.hard_pause {
             let now = Instant::now();
@@ -125,7 +197,9 @@ impl Application for BumpApp {
         let page = match self.page {
             Page::Library => self.view_library(),
             Page::Playlist => self.view_playlist(),
+            Page::Lyrics => self.view_lyrics(),
             Page::Settings => self.view_settings(),
+            Page::Tags => self.view_tags(),
         };
 
         column![
@@ -178,13 +252,26 @@ impl BumpApp {
             hard_pause: None,
             listener: Cell::new(listener),
             hotkeys: None,
+            watcher: None,
             settings,
+            filter: String::new(),
+            shown: Vec::new(),
+            filter_error: None,
+            lyrics: None,
+            lyrics_loading: None,
+            selected: HashSet::new(),
+            tag_editor: TagEditor::default(),
+            now_playing: Arc::new(Mutex::new(NowPlaying::default())),
         };
 
         app.enable_hotkeys(app.config.get_enable_hotkeys());
+        app.enable_watcher(app.config.get_watch_paths());
+        app.auto_purge();
+        app.update_shown();
         if app.config.get_start_load() {
             app.library.start_find(&app.config, app.sender.clone());
         }
+        app.auto_analyze();
         app
     }
 
@@ -204,6 +291,21 @@ impl BumpApp {
         }
     }
 
+    /// Updates the playing song shown by the web remote
+    fn update_now_playing(&self) {
+        let playing = match self
+            .player
+            .get_current_id()
+            .and_then(|id| self.library.get_song_ref(id))
+        {
+            Some(song) => NowPlaying::new(song),
+            None => NowPlaying::default(),
+        };
+        if let Ok(mut now_playing) = self.now_playing.lock() {
+            *now_playing = playing;
+        }
+    }
+
     //>=====================================================================<//
     //                             Subscriptions                             //
     //>=====================================================================<//
@@ -255,17 +357,23 @@ impl BumpApp {
         if let Some(listener) = self.listener.take() {
             iced::subscription::unfold(
                 format!("{} server", Config::get_app_id()),
-                listener,
-                |listener| async {
+                (
+                    listener,
+                    ArtCache::new(&self.config),
+                    self.now_playing.clone(),
+                ),
+                |(listener, art, playing)| async {
                     loop {
                         let stream = match listener.accept() {
                             Ok(stream) => stream,
                             _ => continue,
                         };
 
-                        if let Some(msg) = Server::handle_client(&stream.0) {
+                        if let Some(msg) =
+                            Server::handle_client(&stream.0, &art, &playing)
+                        {
                             Server::send_cli_response(&stream.0, "Ok");
-                            return (msg, listener);
+                            return (msg, (listener, art, playing));
                         }
                         Server::send_cli_response(
                             &stream.0,
@@ -1,16 +1,16 @@
 use std::time::Duration;
 
 use iced::widget::{
-    button, column, container, row, slider, svg, text, Rule, Space,
+    button, column, container, image, row, slider, svg, text, Rule, Space,
 };
 use iced::Renderer;
 use iced_core::alignment::{Horizontal, Vertical};
 use iced_core::{Alignment, Length};
 
-use crate::library::Song;
+use crate::library::{ArtCache, ArtSize, Song, SongId, SortField, TagField};
 use crate::player::PlayerMsg;
 
-use super::app::{BumpApp, Msg, Page};
+use super::app::{BumpApp, LibMsg, Msg, Page};
 use super::svg_data::{pp_icon, vol_icon, ICON, NEXT, PREV};
 use super::theme::{
     self, Button, Container, SvgButton as SvgTheme, Text, Theme,
@@ -31,6 +31,7 @@ impl BumpApp {
             Space::new(Length::Shrink, 5),
             self.menu_button("Library", Page::Library),
             self.menu_button("Playlist", Page::Playlist),
+            self.menu_button("Lyrics", Page::Lyrics),
             Space::new(Length::Shrink, Length::Fill),
             self.menu_button("Settings", Page::Settings),
         ]
@@ -53,23 +54,80 @@ impl BumpApp {
         .into()
     }
 
-    /// Create list header
+    /// Create list header, clicking the fields sorts the list. Numbered
+    /// list is the playlist, other lists are the library.
     pub fn list_header(&self, numbered: bool) -> Element {
-        fn header_item(data: &str, fill: u16) -> Element {
-            TextEllipsis::new(data)
+        let (sort, msg): (_, fn(SortField) -> Msg) = if numbered {
+            (self.gui.get_playlist_sort(), |f| {
+                Msg::Plr(PlayerMsg::Sort(f))
+            })
+        } else {
+            (Some(self.gui.get_library_sort()), |f| {
+                Msg::Lib(LibMsg::Sort(f))
+            })
+        };
+        let primary = sort.and_then(|s| s.get_primary());
+        let header_item = |fields: &[(&str, SortField)], fill| {
+            let mut items: Vec<Element> = Vec::new();
+            for (i, (name, field)) in fields.iter().enumerate() {
+                if i > 0 {
+                    items.push(text("/").style(Text::Darker).size(15).into());
+                }
+                let (name, style) = match primary {
+                    Some(key) if key.field == *field => {
+                        let arrow = if key.desc { '↓' } else { '↑' };
+                        (format!("{name} {arrow}"), Text::Normal)
+                    }
+                    _ => (name.to_string(), Text::Darker),
+                };
+                items.push(
+                    button(text(name).style(style).size(15))
+                        .padding(0)
+                        .style(Button::Item)
+                        .on_press(msg(*field))
+                        .into(),
+                );
+            }
+            row(items)
+                .spacing(4)
                 .width(Length::FillPortion(fill))
-                .style(Text::Darker)
-                .size(15)
                 .into()
-        }
+        };
+
         let mut items: Vec<Element> = Vec::new();
         if numbered {
-            items.push(header_item("#", 1));
+            items.push(
+                TextEllipsis::new("#")
+                    .width(Length::FillPortion(1))
+                    .style(Text::Darker)
+                    .size(15)
+                    .into(),
+            );
         }
         items.extend([
-            header_item("Title / Artist", 10),
-            header_item("Album / Year", 9),
-            header_item("Length / Genre", 1),
+            header_item(
+                &[
+                    ("Title", SortField::Title),
+                    ("Artist", SortField::Artist),
+                    ("Album artist", SortField::AlbumArtist),
+                    ("Path", SortField::Path),
+                ],
+                10,
+            ),
+            header_item(
+                &[
+                    ("Album", SortField::Album),
+                    ("Disc", SortField::Disc),
+                    ("Track", SortField::Track),
+                    ("Year", SortField::Year),
+                    ("Added", SortField::Added),
+                ],
+                9,
+            ),
+            header_item(
+                &[("Length", SortField::Length), ("Genre", SortField::Genre)],
+                1,
+            ),
         ]);
         column![
             row(items)
@@ -87,10 +145,16 @@ impl BumpApp {
         &self,
         s: &Song,
         style: Text,
-        c: usize,
+        c: SongId,
         num: Option<usize>,
         new: bool,
     ) -> Element {
+        // Offline songs are greyed out and can't be played
+        let (style, play) = if s.get_offline() {
+            (Text::Darker, None)
+        } else {
+            (style, Some(Msg::Plr(PlayerMsg::PlaySong(c, new))))
+        };
         HoverGrad::new(
             button(
                 column![
@@ -106,7 +170,7 @@ impl BumpApp {
             .height(Length::Fill)
             .padding(0)
             .style(Button::Item)
-            .on_press(Msg::Plr(PlayerMsg::PlaySong(c, new)))
+            .on_press_maybe(play)
             .into(),
         )
         .height(45)
@@ -129,17 +193,25 @@ impl BumpApp {
                     .into(),
             );
         }
+        // Values inferred from the song path are marked
+        let mark = |field: TagField, value: String| {
+            if s.is_inferred(field) {
+                format!("{value} *")
+            } else {
+                value
+            }
+        };
         items.extend([
             self.list_item_col(
-                s.get_name().to_owned(),
+                mark(TagField::Title, s.get_name().to_owned()),
                 style,
-                s.get_artist().to_owned(),
+                mark(TagField::Artist, s.get_artist().to_owned()),
                 10,
             ),
             self.list_item_col(
-                s.get_album().to_owned(),
+                mark(TagField::Album, s.get_album().to_owned()),
                 style,
-                s.get_year_str(),
+                mark(TagField::Year, s.get_year_str()),
                 9,
             ),
             self.list_item_col(
@@ -207,7 +279,7 @@ impl BumpApp {
     /// Gets title bar
     fn title_bar(&self) -> Element {
         let song = self.player.get_current_song(&self.library);
-        column![
+        let title = column![
             TextEllipsis::new(song.get_name().to_owned())
                 .size(16)
                 .style(Text::Light)
@@ -216,8 +288,19 @@ impl BumpApp {
                 .size(14)
                 .style(Text::Dark)
                 .ellipsis("..."),
-        ]
-        .into()
+        ];
+
+        match song.get_art() {
+            Some(art) => {
+                let path =
+                    ArtCache::new(&self.config).path(art, ArtSize::Small);
+                row![image(path).width(45).height(45), title]
+                    .align_items(Alignment::Center)
+                    .spacing(10)
+                    .into()
+            }
+            None => title.into(),
+        }
     }
 
     /// Gets play menu with buttons to play, play next,...
@@ -243,10 +326,25 @@ impl BumpApp {
         .into()
     }
 
+    /// Gets progress of library update, empty when library isn't updating
+    fn scan_progress(&self) -> Element {
+        match self.library.get_progress() {
+            Some(p) => text(format!(
+                "Updating library {}/{}",
+                p.processed, p.discovered
+            ))
+            .size(14)
+            .style(Text::Darker)
+            .into(),
+            None => Space::new(Length::Shrink, Length::Shrink).into(),
+        }
+    }
+
     /// Gets volume menu
     fn volume_menu(&self) -> Element {
         container(
             row![
+                self.scan_progress(),
                 SvgButton::new(vol_icon(
                     self.player.get_volume(),
                     self.player.get_mute()
@@ -7,7 +7,10 @@ use eyre::Result;
 use iced::window::Position;
 use serde_derive::{Deserialize, Serialize};
 
-use crate::config::Config;
+use crate::{
+    config::Config,
+    library::{SortField, SortOrder},
+};
 
 use super::widgets::list_view;
 
@@ -21,6 +24,13 @@ pub struct Gui {
     pos_x: i32,
     /// Window position on y coordinate
     pos_y: i32,
+    /// Order of songs in the library
+    #[serde(default)]
+    library_sort: SortOrder,
+    /// Order the playlist was last sorted by, None when it wasn't sorted
+    /// since it was created or shuffled
+    #[serde(default)]
+    playlist_sort: Option<SortOrder>,
     #[serde(skip)]
     wb_states: Vec<Cell<list_view::State>>,
     /// Stores whether Gui variables changed
@@ -92,6 +102,41 @@ impl Gui {
         self.pos_y = pos_y;
     }
 
+    /// Gets order of songs in the library
+    pub fn get_library_sort(&self) -> &SortOrder {
+        &self.library_sort
+    }
+
+    /// Sorts library by the given field first, see [`SortOrder::sort_by`]
+    pub fn sort_library(&mut self, field: SortField) {
+        self.changed = true;
+        self.library_sort.sort_by(field);
+    }
+
+    /// Gets order the playlist was last sorted by
+    pub fn get_playlist_sort(&self) -> Option<&SortOrder> {
+        self.playlist_sort.as_ref()
+    }
+
+    /// Sorts playlist by the given field first, returns the new order of
+    /// the playlist
+    pub fn sort_playlist(&mut self, field: SortField) -> &SortOrder {
+        self.changed = true;
+        let sort = self
+            .playlist_sort
+            .get_or_insert_with(|| SortOrder::new(Vec::new()));
+        sort.sort_by(field);
+        sort
+    }
+
+    /// Clears order of the playlist, when it's no longer sorted
+    pub fn clear_playlist_sort(&mut self) {
+        if self.playlist_sort.is_some() {
+            self.changed = true;
+            self.playlist_sort = None;
+        }
+    }
+
     /// Gets WrapBox state on given index
     pub fn get_wb_state(&self, index: usize) -> &Cell<list_view::State> {
         &self.wb_states[index]
@@ -113,6 +158,8 @@ impl Default for Gui {
             height: 720,
             pos_x: i32::MAX,
             pos_y: i32::MAX,
+            library_sort: SortOrder::default(),
+            playlist_sort: None,
             wb_states: vec![Cell::<list_view::State>::default(); 2],
             changed: false,
         }
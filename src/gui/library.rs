@@ -1,11 +1,13 @@
 use iced::{
-    widget::{column, container, text},
+    widget::{checkbox, column, container, row, text, text_input, Space},
     Renderer,
 };
-use iced_core::Length;
+use iced_core::{alignment::Horizontal, Alignment, Length};
 
 use super::{
-    app::{BumpApp, Msg},
+    app::{BumpApp, LibMsg, Msg},
+    settings::text_button,
+    tag_editor::TagMsg,
     theme::{Text, Theme},
     widgets::list_view::WrapBox,
 };
@@ -16,7 +18,17 @@ impl BumpApp {
     /// Displays main page
     pub fn view_library(&self) -> Element {
         column![
-            container(text("Library").size(25).style(Text::Light),).padding(5),
+            row![
+                text("Library").size(25).style(Text::Light),
+                Space::new(Length::Fill, Length::Shrink),
+                text_input("Search", &self.filter)
+                    .on_input(|val| Msg::Lib(LibMsg::Filter(val)))
+                    .width(250),
+            ]
+            .align_items(Alignment::Center)
+            .padding(5),
+            self.filter_error(),
+            self.selection(),
             self.list_header(false),
             self.library_songs(),
         ]
@@ -25,24 +37,86 @@ impl BumpApp {
         .into()
     }
 
+    /// Gets error of parsing the library filter
+    fn filter_error(&self) -> Element {
+        match &self.filter_error {
+            Some(error) => text(error)
+                .size(14)
+                .style(Text::Darker)
+                .width(Length::Fill)
+                .horizontal_alignment(Horizontal::Right)
+                .into(),
+            None => Space::new(Length::Shrink, Length::Shrink).into(),
+        }
+    }
+
+    /// Gets number of selected songs with buttons to edit their tags, to
+    /// write their inferred or normalized tags and to clear the selection
+    fn selection(&self) -> Element {
+        if self.selected.is_empty() {
+            return Space::new(Length::Shrink, Length::Shrink).into();
+        }
+        let songs: Vec<_> = self
+            .selected
+            .iter()
+            .filter_map(|&id| self.library.get_song_ref(id))
+            .collect();
+        let inferred = songs.iter().any(|s| !s.get_inferred().is_empty());
+        let normalized = songs.iter().any(|s| !s.get_normalized().is_empty());
+
+        let mut items: Vec<Element> = vec![
+            text(format!("{} selected", self.selected.len()))
+                .style(Text::Darker)
+                .into(),
+            Space::new(Length::Fill, Length::Shrink).into(),
+        ];
+        if inferred {
+            items.push(text_button(
+                "Write inferred tags",
+                Msg::Lib(LibMsg::WriteInferred),
+            ));
+        }
+        if normalized {
+            items.push(text_button(
+                "Write normalized tags",
+                Msg::Lib(LibMsg::WriteNormalized),
+            ));
+        }
+        items.extend([
+            text_button("Edit tags", Msg::Tags(TagMsg::Open)),
+            text_button("Clear selection", Msg::Lib(LibMsg::ClearSelection)),
+        ]);
+        row(items)
+            .spacing(5)
+            .align_items(Alignment::Center)
+            .padding([0, 5])
+            .into()
+    }
+
     pub fn library_songs(&self) -> Element {
-        let songs = self.library.get_songs();
-        let cur = self.player.get_current();
+        let cur = self.player.get_current_id();
 
         WrapBox::with_children(
-            songs
+            self.shown
                 .iter()
-                .enumerate()
-                .filter_map(|(c, s)| {
-                    if s.get_deleted() {
-                        None
-                    } else {
-                        let style = match cur {
-                            Some(value) if value == c => Text::Prim,
-                            _ => Text::Default,
-                        };
-                        Some(self.list_item(s, style, c, None, true))
-                    }
+                .filter_map(|&c| {
+                    let s = self.library.get_song_ref(c)?;
+                    let style = match cur {
+                        Some(value) if value == c => Text::Prim,
+                        _ => Text::Default,
+                    };
+                    let selected = self.selected.contains(&c);
+                    Some(
+                        row![
+                            container(checkbox("", selected, move |val| {
+                                Msg::Lib(LibMsg::Select(c, val))
+                            }))
+                            .height(Length::Fill)
+                            .center_y(),
+                            self.list_item(s, style, c, None, true),
+                        ]
+                        .into(),
+                    )
                 })
                 .collect(),
             self.gui.get_wb_state(0),
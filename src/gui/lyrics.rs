@@ -0,0 +1,122 @@
+use std::thread;
+
+use iced::{
+    widget::{button, column, scrollable, text, Column},
+    Renderer,
+};
+use iced_core::{Length, Padding};
+
+use crate::{
+    library::{Lyrics, SongId},
+    player::PlayerMsg,
+};
+
+use super::{
+    app::{BumpApp, Msg, Page},
+    theme::{Button, Text, Theme},
+};
+
+type Element<'a> = iced::Element<'a, Msg, Renderer<Theme>>;
+
+impl BumpApp {
+    pub fn view_lyrics(&self) -> Element {
+        let song = self.player.get_current_song(&self.library);
+        column![
+            column![
+                text(song.get_name().to_owned()).size(25).style(Text::Light),
+                text(song.get_artist().to_owned()).style(Text::Dark),
+            ]
+            .padding(5),
+            match &self.lyrics {
+                Some((id, Some(lyrics)))
+                    if Some(*id) == self.player.get_current_id() =>
+                {
+                    self.lyrics_lines(lyrics)
+                }
+                _ if self.lyrics_loading.is_some() =>
+                    text("Loading lyrics...")
+                        .style(Text::Darker)
+                        .width(Length::Fill)
+                        .into(),
+                _ => text("No lyrics found")
+                    .style(Text::Darker)
+                    .width(Length::Fill)
+                    .into(),
+            }
+        ]
+        .width(Length::Fill)
+        .padding(Padding::from([5, 15]))
+        .spacing(5)
+        .into()
+    }
+
+    /// Starts loading lyrics of the currently playing song in the
+    /// background when it changed, lyrics are loaded only when the lyrics
+    /// page is shown
+    pub fn update_lyrics(&mut self) {
+        if self.page != Page::Lyrics {
+            return;
+        }
+        let id = match self.player.get_current_id() {
+            Some(id) => id,
+            None => {
+                self.lyrics = None;
+                return;
+            }
+        };
+        if self.lyrics.as_ref().is_some_and(|(i, _)| *i == id)
+            || self.lyrics_loading == Some(id)
+        {
+            return;
+        }
+        let path = match self.library.get_song_ref(id) {
+            Some(song) => song.get_path().to_owned(),
+            None => {
+                self.lyrics = Some((id, None));
+                return;
+            }
+        };
+
+        self.lyrics_loading = Some(id);
+        let sender = self.sender.clone();
+        thread::spawn(move || {
+            let lyrics = Lyrics::load(&path);
+            _ = sender.send(Msg::Lyrics(id, lyrics));
+        });
+    }
+
+    /// Sets loaded lyrics of the song, lyrics of song that is no longer
+    /// playing are dropped
+    pub fn lyrics_loaded(&mut self, id: SongId, lyrics: Option<Lyrics>) {
+        if self.lyrics_loading == Some(id) {
+            self.lyrics_loading = None;
+            self.lyrics = Some((id, lyrics));
+        }
+    }
+
+    /// Gets lines of the lyrics, current line of synced lyrics is
+    /// highlighted and clicking a line seeks to it
+    fn lyrics_lines<'a>(&self, lyrics: &'a Lyrics) -> Element<'a> {
+        let current = lyrics.current_line(self.player.get_timestamp().current);
+        let lines = lyrics.get_lines().iter().enumerate().map(|(i, line)| {
+            let style = match current {
+                Some(c) if c == i => Text::Prim,
+                Some(c) if c > i => Text::Dark,
+                _ => Text::Normal,
+            };
+            let content = text(line.get_text()).size(16).style(style);
+            match line.get_time() {
+                Some(time) => button(content)
+                    .style(Button::Item)
+                    .padding([2, 5])
+                    .on_press(Msg::Plr(PlayerMsg::SeekTo(time)))
+                    .into(),
+                None => content.into(),
+            }
+        });
+
+        scrollable(Column::with_children(lines.collect()).spacing(2))
+            .height(Length::Fill)
+            .into()
+    }
+}
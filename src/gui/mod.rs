@@ -7,5 +7,7 @@ pub mod widgets;
 mod components;
 mod elements;
 mod library;
+mod lyrics;
 mod playlist;
 mod settings;
+mod tag_editor;
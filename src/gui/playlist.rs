@@ -7,7 +7,7 @@ use iced_core::{Length, Padding};
 use crate::player::PlayerMsg;
 
 use super::{
-    app::{BumpApp, Msg},
+    app::{BumpApp, LibMsg, Msg},
     svg_data::SHUFFLE,
     theme::{Button, Text, Theme},
     widgets::{
@@ -44,6 +44,7 @@ impl BumpApp {
                 .on_press(Msg::Plr(PlayerMsg::Shuffle)),
             ]
             .padding(5),
+            self.smart_playlists(),
             self.list_header(true),
             self.playlist_songs(),
         ]
@@ -52,6 +53,34 @@ impl BumpApp {
         .into()
     }
 
+    /// Gets buttons playing the smart playlists
+    fn smart_playlists(&self) -> Element {
+        let playlists = self.config.get_smart_playlists();
+        if playlists.is_empty() {
+            return Space::new(Length::Shrink, Length::Shrink).into();
+        }
+
+        row(playlists
+            .iter()
+            .map(|p| {
+                button(
+                    HoverGrad::new(text(p.get_name()).into())
+                        .padding(Padding::from([3, 5]))
+                        .height(Length::Shrink)
+                        .width(Length::Shrink),
+                )
+                .style(Button::Item)
+                .width(Length::Shrink)
+                .height(Length::Shrink)
+                .on_press(Msg::Lib(LibMsg::PlaySmart(p.get_name().to_owned())))
+                .into()
+            })
+            .collect())
+        .spacing(3)
+        .padding([0, 5, 5, 5])
+        .into()
+    }
+
     fn playlist_songs(&self) -> Element {
         let cur = self.player.get_current_id();
 
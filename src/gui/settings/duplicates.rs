@@ -0,0 +1,108 @@
+use iced::{
+    widget::{column, row, scrollable, text, Space},
+    Renderer,
+};
+use iced_core::{Alignment, Length, Padding};
+
+use crate::{
+    gui::{
+        app::{BumpApp, LibMsg, Msg},
+        theme::{Text, Theme},
+    },
+    library::{DuplicateMode, SongId},
+};
+
+use super::{
+    elements::{text_button, toggler},
+    SettingsMsg,
+};
+
+type Element<'a> = iced::Element<'a, Msg, Renderer<Theme>>;
+
+impl BumpApp {
+    pub fn duplicates_settings(&self) -> Element {
+        let mode = self.settings.dup_mode;
+        let mut col = column![
+            toggler(
+                "Compare decoded audio (slow)".to_owned(),
+                mode == DuplicateMode::Audio,
+                |val| Msg::Settings(SettingsMsg::DuplicateMode(if val {
+                    DuplicateMode::Audio
+                } else {
+                    DuplicateMode::Tags
+                }))
+            ),
+            if self.settings.dup_search {
+                text("Searching for duplicates...")
+                    .style(Text::Darker)
+                    .into()
+            } else {
+                text_button(
+                    "Find duplicates",
+                    Msg::Lib(LibMsg::FindDuplicates(mode)),
+                )
+            },
+        ]
+        .spacing(5);
+
+        match &self.settings.duplicates {
+            Some(groups) if groups.is_empty() => {
+                col = col.push(text("No duplicates found").style(Text::Normal))
+            }
+            Some(groups) => {
+                col = col.push(
+                    text("Choose which song to keep, other songs are hidden")
+                        .style(Text::Normal),
+                );
+                for group in groups {
+                    col = col.push(self.duplicate_group(group));
+                }
+            }
+            None => {}
+        }
+
+        scrollable(col.padding(Padding::from([5, 15]))).into()
+    }
+
+    /// Gets group of duplicate songs with buttons to keep one of them
+    fn duplicate_group(&self, group: &[SongId]) -> Element {
+        let mut items: Vec<Element> = Vec::new();
+        for &id in group {
+            let song = match self.library.get_song_ref(id) {
+                Some(song) => song,
+                None => continue,
+            };
+            let others = group.iter().copied().filter(|&s| s != id).collect();
+
+            items.push(
+                row![
+                    text_button("Keep", Msg::Lib(LibMsg::Merge(id, others))),
+                    column![
+                        text(format!(
+                            "{} - {}",
+                            song.get_name(),
+                            song.get_artist()
+                        ))
+                        .style(Text::Dark),
+                        text(format!(
+                            "{}, {}",
+                            song.get_album(),
+                            song.get_length_str()
+                        ))
+                        .size(14)
+                        .style(Text::Darker),
+                        text(song.get_path().to_string_lossy())
+                            .size(14)
+                            .style(Text::Darker),
+                    ],
+                ]
+                .spacing(10)
+                .align_items(Alignment::Center)
+                .into(),
+            );
+        }
+        items.push(Space::new(Length::Shrink, 10).into());
+
+        column(items).spacing(3).into()
+    }
+}
@@ -8,8 +8,11 @@ use crate::gui::{
     },
 };
 
-use iced::{widget::row, Renderer};
-use iced_core::Length;
+use iced::{
+    widget::{button, row, text},
+    Renderer,
+};
+use iced_core::{Length, Padding};
 
 type Element<'a> = iced::Element<'a, Msg, Renderer<Theme>>;
 
@@ -30,6 +33,18 @@ where
     .into()
 }
 
+/// Gets button with text component
+pub fn text_button(data: &str, msg: Msg) -> Element {
+    button(
+        HoverGrad::new(text(data).style(Text::Normal).into())
+            .width(Length::Shrink)
+            .height(Length::Shrink)
+            .padding(Padding::from([3, 5])),
+    )
+    .on_press(msg)
+    .into()
+}
+
 /// Gets removable item component
 pub fn removable_item<'a>(content: String, msg: Msg) -> Element<'a> {
     HoverGrad::new(
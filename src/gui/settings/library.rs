@@ -1,20 +1,25 @@
 use iced::{
-    widget::{button, column, scrollable, text},
+    widget::{
+        button, column, container, progress_bar, row, scrollable, text,
+        text_input, Space,
+    },
     Renderer,
 };
-use iced_core::{Length, Padding};
+use iced_core::{Alignment, Length, Padding};
 
 use crate::{
-    config::ConfMsg,
+    config::{ConfMsg, SearchPath},
     gui::{
         app::{BumpApp, LibMsg, Msg},
+        svg_data::{PLUS, TICK},
         theme::{Button, Text, Theme},
-        widgets::hover_grad::HoverGrad,
+        widgets::{hover_grad::HoverGrad, svg_button::SvgButton},
     },
+    library::IgnoreRules,
 };
 
 use super::{
-    elements::{removable_item, toggler},
+    elements::{removable_item, text_button, toggler},
     SettingsMsg,
 };
 
@@ -26,27 +31,45 @@ impl BumpApp {
             column![
                 column![
                     text("Songs loading:").height(22).style(Text::Normal),
-                    button(
-                        HoverGrad::new(
-                            text("Update library").style(Text::Normal).into()
-                        )
-                        .width(Length::Shrink)
-                        .height(Length::Shrink)
-                        .padding(Padding::from([3, 5]))
-                    )
-                    .on_press(Msg::Lib(LibMsg::LoadStart)),
+                    self.update_library(),
                     toggler(
                         "Update library on start".to_owned(),
                         self.config.get_start_load(),
                         |val| Msg::Conf(ConfMsg::StartLoad(val))
                     ),
                     toggler(
-                        "Recursive search for songs".to_owned(),
+                        "Watch search paths for changes".to_owned(),
+                        self.config.get_watch_paths(),
+                        |val| Msg::Conf(ConfMsg::WatchPaths(val))
+                    ),
+                    toggler(
+                        "Recursive search in new search paths".to_owned(),
                         self.config.get_recursive_search(),
                         |val| Msg::Conf(ConfMsg::RecursiveSearch(val))
                     ),
+                    toggler(
+                        "Follow symbolic links".to_owned(),
+                        self.config.get_follow_symlinks(),
+                        |val| Msg::Conf(ConfMsg::FollowSymlinks(val))
+                    ),
+                    toggler(
+                        "Detect songs by their content".to_owned(),
+                        self.config.get_probe_content(),
+                        |val| Msg::Conf(ConfMsg::ProbeContent(val))
+                    ),
+                    toggler(
+                        "Prefer art files over art embedded in songs"
+                            .to_owned(),
+                        self.config.get_folder_art_first(),
+                        |val| Msg::Conf(ConfMsg::FolderArtFirst(val))
+                    ),
                 ],
+                self.purge_settings(),
+                self.normalize_settings(),
                 self.get_paths_input(),
+                self.get_ignore_input(),
+                self.smart_playlists_settings(),
+                self.scan_errors(),
             ]
             .spacing(5)
             .padding(Padding::from([5, 15])),
@@ -54,6 +77,148 @@ impl BumpApp {
         .into()
     }
 
+    /// Gets button for updating library, when library is being updated,
+    /// gets progress of the update with button to cancel it
+    fn update_library(&self) -> Element {
+        let progress = match self.library.get_progress() {
+            Some(progress) => progress,
+            None => {
+                return column![
+                    text_button("Update library", Msg::Lib(LibMsg::LoadStart)),
+                    self.scan_stats(),
+                ]
+                .into()
+            }
+        };
+
+        column![
+            row![
+                text_button("Cancel update", Msg::Lib(LibMsg::LoadCancel)),
+                text(format!(
+                    "Processed {}/{} songs, {} failed",
+                    progress.processed, progress.discovered, progress.failed
+                ))
+                .style(Text::Darker),
+            ]
+            .spacing(10)
+            .align_items(Alignment::Center),
+            progress_bar(
+                0.0..=progress.discovered.max(1) as f32,
+                progress.processed as f32
+            )
+            .height(6)
+            .width(300),
+        ]
+        .spacing(5)
+        .into()
+    }
+
+    /// Gets text with statistics of the last library scan
+    fn scan_stats(&self) -> Element {
+        match self.library.get_stats() {
+            Some(stats) => text(format!(
+                "Last update: {} added, {} updated, {} removed",
+                stats.added, stats.updated, stats.removed
+            ))
+            .style(Text::Darker)
+            .into(),
+            None => Space::new(Length::Shrink, Length::Shrink).into(),
+        }
+    }
+
+    /// Gets settings of tag normalization
+    fn normalize_settings(&self) -> Element {
+        let mut col = column![
+            text("Tag normalization:").height(22).style(Text::Normal),
+            toggler(
+                "Trim whitespace, repair UTF-8 and map ID3 genre numbers"
+                    .to_owned(),
+                self.config.get_normalize_tags(),
+                |val| Msg::Conf(ConfMsg::NormalizeTags(val))
+            ),
+            toggler(
+                "Merge artists that differ only by case".to_owned(),
+                self.config.get_merge_artists(),
+                |val| Msg::Conf(ConfMsg::MergeArtists(val))
+            ),
+            text("Encoding of legacy tags, such as windows-1251:")
+                .style(Text::Normal),
+            HoverGrad::new(
+                row![
+                    container(
+                        SvgButton::new(TICK.into())
+                            .width(15)
+                            .height(15)
+                            .on_press(Msg::Settings(
+                                SettingsMsg::LegacyEncodingSave
+                            )),
+                    )
+                    .height(30)
+                    .padding(3)
+                    .center_x()
+                    .center_y(),
+                    text_input("none", &self.settings.legacy_encoding)
+                        .on_input(|val| Msg::Settings(
+                            SettingsMsg::LegacyEncoding(val)
+                        ))
+                        .on_submit(Msg::Settings(
+                            SettingsMsg::LegacyEncodingSave
+                        ))
+                ]
+                .into()
+            )
+            .height(Length::Shrink),
+        ]
+        .spacing(3);
+
+        if let Some(error) = &self.settings.encoding_error {
+            col = col.push(text(error).style(Text::Darker));
+        }
+        col.push(
+            text(
+                "Changes apply to songs loaded again, normalized values are \
+                written to the song files only from the library selection",
+            )
+            .size(14)
+            .style(Text::Darker),
+        )
+        .push(text_button("Reload all songs", Msg::Lib(LibMsg::Reload)))
+        .into()
+    }
+
+    /// Gets settings for removing deleted songs
+    fn purge_settings(&self) -> Element {
+        column![
+            text("Deleted songs:").height(22).style(Text::Normal),
+            text_button("Remove deleted songs", Msg::Lib(LibMsg::Purge)),
+            text("Remove songs deleted for more days than:")
+                .style(Text::Normal),
+            HoverGrad::new(
+                row![
+                    container(
+                        SvgButton::new(TICK.into())
+                            .width(15)
+                            .height(15)
+                            .on_press(Msg::Settings(
+                                SettingsMsg::AutoPurgeSave
+                            )),
+                    )
+                    .height(30)
+                    .padding(3)
+                    .center_x()
+                    .center_y(),
+                    text_input("never", &self.settings.auto_purge).on_input(
+                        |val| Msg::Settings(SettingsMsg::AutoPurge(val))
+                    )
+                ]
+                .into()
+            )
+            .height(Length::Shrink),
+        ]
+        .spacing(3)
+        .into()
+    }
+
     fn get_paths_input(&self) -> Element {
         let mut items: Vec<Element> = Vec::new();
 
@@ -65,9 +230,10 @@ impl BumpApp {
         );
         for (i, path) in self.config.get_paths().iter().enumerate() {
             items.push(removable_item(
-                path.to_string_lossy().to_string(),
+                path.get_path().to_string_lossy().to_string(),
                 Msg::Conf(ConfMsg::RemPath(i)),
             ));
+            items.push(self.search_path_settings(i, path));
         }
         items.push(
             button("Add path")
@@ -78,4 +244,231 @@ impl BumpApp {
 
         column(items).spacing(3).into()
     }
+
+    /// Gets list of ignore patterns with input for adding new pattern
+    fn get_ignore_input(&self) -> Element {
+        let mut items: Vec<Element> = vec![
+            text("Ignore patterns:")
+                .style(Text::Normal)
+                .height(22)
+                .into(),
+            text(format!(
+                "Gitignore-style patterns, also read from {} files",
+                IgnoreRules::FILE
+            ))
+            .size(14)
+            .style(Text::Darker)
+            .into(),
+        ];
+        for (i, pattern) in self.config.get_ignore().iter().enumerate() {
+            items.push(removable_item(
+                pattern.to_owned(),
+                Msg::Conf(ConfMsg::RemIgnore(i)),
+            ));
+        }
+
+        let add = Msg::Conf(ConfMsg::AddIgnore(self.settings.ignore.clone()));
+        items.push(
+            HoverGrad::new(
+                row![
+                    container(
+                        SvgButton::new(PLUS.into())
+                            .width(15)
+                            .height(15)
+                            .on_press(add.clone()),
+                    )
+                    .height(30)
+                    .padding(3)
+                    .center_x()
+                    .center_y(),
+                    text_input("*.wav", &self.settings.ignore)
+                        .on_input(|val| Msg::Settings(SettingsMsg::Ignore(
+                            val
+                        )))
+                        .on_submit(add)
+                ]
+                .into(),
+            )
+            .height(Length::Shrink)
+            .into(),
+        );
+
+        column(items).spacing(3).into()
+    }
+
+    /// Gets list of smart playlists with inputs for adding new playlist
+    fn smart_playlists_settings(&self) -> Element {
+        let mut items: Vec<Element> =
+            vec![
+            text("Smart playlists:")
+                .style(Text::Normal)
+                .height(22)
+                .into(),
+            text("Songs matching query, e.g. artist:\"Daft Punk\" year:>=2000")
+                .size(14)
+                .style(Text::Darker)
+                .into(),
+        ];
+        for (i, playlist) in
+            self.config.get_smart_playlists().iter().enumerate()
+        {
+            items.push(removable_item(
+                format!("{}: {}", playlist.get_name(), playlist.get_query()),
+                Msg::Conf(ConfMsg::RemSmartPlaylist(i)),
+            ));
+        }
+
+        let add = Msg::Settings(SettingsMsg::SmartSave);
+        items.push(
+            HoverGrad::new(
+                row![
+                    container(
+                        SvgButton::new(PLUS.into())
+                            .width(15)
+                            .height(15)
+                            .on_press(add.clone()),
+                    )
+                    .height(30)
+                    .padding(3)
+                    .center_x()
+                    .center_y(),
+                    text_input("name", &self.settings.smart_name)
+                        .on_input(|val| Msg::Settings(SettingsMsg::SmartName(
+                            val
+                        )))
+                        .on_submit(add.clone())
+                        .width(150),
+                    text_input(
+                        "genre:house length:<5m",
+                        &self.settings.smart_query
+                    )
+                    .on_input(|val| Msg::Settings(SettingsMsg::SmartQuery(
+                        val
+                    )))
+                    .on_submit(add)
+                ]
+                .spacing(3)
+                .into(),
+            )
+            .height(Length::Shrink)
+            .into(),
+        );
+        if let Some(error) = &self.settings.smart_error {
+            items.push(text(error).size(14).style(Text::Darker).into());
+        }
+
+        column(items).spacing(3).into()
+    }
+
+    /// Gets settings of the search path on the given index
+    fn search_path_settings(&self, id: usize, path: &SearchPath) -> Element {
+        let exts = match self.settings.path_exts.get(&id) {
+            Some(exts) => exts.to_owned(),
+            None => path.get_extensions().join(", "),
+        };
+        let save = Msg::Conf(ConfMsg::PathExtensions(
+            id,
+            exts.split(',')
+                .map(|e| e.trim().trim_start_matches('.').to_owned())
+                .filter(|e| !e.is_empty())
+                .collect(),
+        ));
+        let depth = match self.settings.path_depths.get(&id) {
+            Some(depth) => depth.to_owned(),
+            None => path
+                .get_max_depth()
+                .map(|d| d.to_string())
+                .unwrap_or_default(),
+        };
+        // Empty depth is unlimited, invalid depth can't be saved
+        let save_depth = match depth.trim() {
+            "" => Some(None),
+            d => d.parse().ok().map(Some),
+        }
+        .map(|d| Msg::Conf(ConfMsg::PathDepth(id, d)));
+        let mut depth_input = text_input("unlimited", &depth)
+            .on_input(move |val| {
+                Msg::Settings(SettingsMsg::PathDepth(id, val))
+            })
+            .width(100);
+        let mut depth_save = SvgButton::new(TICK.into()).width(15).height(15);
+        if let Some(save) = save_depth {
+            depth_save = depth_save.on_press(save.clone());
+            depth_input = depth_input.on_submit(save);
+        }
+
+        column![
+            row![
+                toggler(
+                    "Enabled".to_owned(),
+                    path.get_enabled(),
+                    move |val| { Msg::Conf(ConfMsg::PathEnabled(id, val)) }
+                ),
+                toggler(
+                    "Recursive".to_owned(),
+                    path.get_recursive(),
+                    move |val| Msg::Conf(ConfMsg::PathRecursive(id, val))
+                ),
+                text("Max depth:").style(Text::Normal),
+                container(depth_save)
+                    .height(30)
+                    .padding(3)
+                    .center_x()
+                    .center_y(),
+                depth_input,
+            ]
+            .spacing(5)
+            .align_items(Alignment::Center),
+            HoverGrad::new(
+                row![
+                    container(
+                        SvgButton::new(TICK.into())
+                            .width(15)
+                            .height(15)
+                            .on_press(save.clone()),
+                    )
+                    .height(30)
+                    .padding(3)
+                    .center_x()
+                    .center_y(),
+                    text_input("extensions from config", &exts)
+                        .on_input(move |val| Msg::Settings(
+                            SettingsMsg::PathExtensions(id, val)
+                        ))
+                        .on_submit(save)
+                ]
+                .into(),
+            )
+            .height(Length::Shrink),
+        ]
+        .spacing(3)
+        .padding(Padding::from([0, 0, 5, 30]))
+        .into()
+    }
+
+    /// Gets list of song files that failed to load
+    fn scan_errors(&self) -> Element {
+        let errors = self.library.get_errors();
+        if errors.is_empty() {
+            return Space::new(Length::Shrink, Length::Shrink).into();
+        }
+
+        let mut items: Vec<Element> =
+            vec![text(format!("Failed to load ({}):", errors.len()))
+                .style(Text::Normal)
+                .height(22)
+                .into()];
+        for error in errors {
+            items.push(
+                column![
+                    text(error.path.to_string_lossy()).style(Text::Dark),
+                    text(&error.error).size(14).style(Text::Darker),
+                ]
+                .padding([0, 10])
+                .into(),
+            );
+        }
+
+        column(items).spacing(3).into()
+    }
 }
@@ -1,9 +1,13 @@
+mod duplicates;
 mod elements;
 mod hotkeys;
 mod library;
 mod msg;
+mod organize;
 mod playback;
 mod settings;
 
 pub use self::msg::*;
 pub use self::settings::*;
+
+pub(super) use self::elements::text_button;
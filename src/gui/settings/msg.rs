@@ -1,5 +1,7 @@
 use serde_derive::{Deserialize, Serialize};
 
+use crate::library::DuplicateMode;
+
 use super::SettingsPage;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -10,6 +12,21 @@ pub enum SettingsMsg {
     FadeSave,
     VolJump(String),
     VolJumpSave,
+    Preamp(String),
+    PreampSave,
     Hotkey(String),
     HotkeySave,
+    AutoPurge(String),
+    AutoPurgeSave,
+    Ignore(String),
+    SmartName(String),
+    SmartQuery(String),
+    SmartSave,
+    PathExtensions(usize, String),
+    PathDepth(usize, String),
+    DuplicateMode(DuplicateMode),
+    OrganizeTemplate(String),
+    OrganizePreview,
+    LegacyEncoding(String),
+    LegacyEncodingSave,
 }
@@ -0,0 +1,122 @@
+use iced::{
+    widget::{column, progress_bar, row, scrollable, text, text_input},
+    Renderer,
+};
+use iced_core::{Alignment, Length, Padding};
+
+use crate::{
+    gui::{
+        app::{BumpApp, LibMsg, Msg},
+        theme::{Text, Theme},
+        widgets::hover_grad::HoverGrad,
+    },
+    library::OrganizeMove,
+};
+
+use super::{elements::text_button, SettingsMsg};
+
+type Element<'a> = iced::Element<'a, Msg, Renderer<Theme>>;
+
+/// Maximal number of planned moves shown in the preview
+const MAX_SHOWN: usize = 500;
+
+impl BumpApp {
+    pub fn organize_settings(&self) -> Element {
+        let mut col = column![
+            text("Path template:").style(Text::Normal),
+            HoverGrad::new(
+                text_input(
+                    "{artist}/{album}/{track:02} {title}.{ext}",
+                    &self.settings.organize_template
+                )
+                .on_input(|val| Msg::Settings(SettingsMsg::OrganizeTemplate(
+                    val
+                )))
+                .into()
+            )
+            .height(Length::Shrink),
+            text(
+                "Fields: title, artist, album, albumartist, year, date, \
+                track, tracktotal, disc, disctotal, genre, composer, ext"
+            )
+            .size(14)
+            .style(Text::Darker),
+            text_button(
+                "Preview",
+                Msg::Settings(SettingsMsg::OrganizePreview)
+            ),
+        ]
+        .spacing(5);
+
+        if let Some((done, total)) = self.library.get_organize_progress() {
+            col = col.push(
+                column![
+                    text(format!("Moving songs {done}/{total}"))
+                        .style(Text::Darker),
+                    progress_bar(0.0..=total.max(1) as f32, done as f32)
+                        .height(6)
+                        .width(300),
+                ]
+                .spacing(5),
+            );
+        }
+        if let Some(result) = &self.settings.organize_result {
+            col = col.push(text(result).style(Text::Normal));
+        }
+        if let Some(plan) = &self.settings.organize {
+            col = col.push(self.organize_preview(&plan.moves));
+        }
+
+        scrollable(col.padding(Padding::from([5, 15]))).into()
+    }
+
+    /// Gets planned moves of the song files with button to move them
+    fn organize_preview<'a>(&self, moves: &'a [OrganizeMove]) -> Element<'a> {
+        let valid = moves.iter().filter(|m| m.conflict.is_none()).count();
+        let mut items: Vec<Element> = Vec::new();
+
+        items.push(
+            row![
+                text(format!(
+                    "{valid} songs will be moved, {} have conflicts",
+                    moves.len() - valid
+                ))
+                .style(Text::Normal),
+                if valid > 0 && !self.library.is_organizing() {
+                    text_button("Organize", Msg::Lib(LibMsg::Organize))
+                } else {
+                    text("").into()
+                },
+            ]
+            .spacing(10)
+            .align_items(Alignment::Center)
+            .into(),
+        );
+
+        for m in moves.iter().take(MAX_SHOWN) {
+            let mut item = column![
+                text(m.from.to_string_lossy()).size(14).style(Text::Darker),
+                text(format!("→ {}", m.to.to_string_lossy()))
+                    .size(14)
+                    .style(Text::Dark),
+            ];
+            if let Some(conflict) = m.conflict_str() {
+                item = item.push(
+                    text(format!("Not moved, {conflict}"))
+                        .size(14)
+                        .style(Text::Prim),
+                );
+            }
+            items.push(item.into());
+        }
+        if moves.len() > MAX_SHOWN {
+            items.push(
+                text(format!("and {} more", moves.len() - MAX_SHOWN))
+                    .style(Text::Darker)
+                    .into(),
+            );
+        }
+
+        column(items).spacing(5).into()
+    }
+}
@@ -1,20 +1,27 @@
 use iced::{
-    widget::{column, container, row, scrollable, text, text_input},
+    widget::{
+        button, column, container, progress_bar, row, scrollable, text,
+        text_input,
+    },
     Renderer,
 };
-use iced_core::{Length, Padding};
+use iced_core::{Alignment, Length, Padding};
 
 use crate::{
     config::ConfMsg,
     gui::{
-        app::{BumpApp, Msg},
+        app::{BumpApp, LibMsg, Msg},
         svg_data::TICK,
-        theme::{Text, Theme},
+        theme::{Button, Text, Theme},
         widgets::{hover_grad::HoverGrad, svg_button::SvgButton},
     },
+    library::ReplayGainMode,
 };
 
-use super::{elements::toggler, SettingsMsg};
+use super::{
+    elements::{text_button, toggler},
+    SettingsMsg,
+};
 
 type Element<'a> = iced::Element<'a, Msg, Renderer<Theme>>;
 
@@ -87,10 +94,112 @@ impl BumpApp {
                     )
                     .height(Length::Shrink),
                 ]
-                .spacing(3)
+                .spacing(3),
+                self.replay_gain_settings(),
+                self.loudness_settings(),
             ]
             .padding(Padding::from([5, 15])),
         )
         .into()
     }
+
+    /// Gets settings of ReplayGain, its mode, preamp and clipping
+    /// prevention
+    fn replay_gain_settings(&self) -> Element {
+        let mode = self.config.get_replay_gain();
+        let mode_button = |name, m| -> Element {
+            button(
+                HoverGrad::new(text(name).into())
+                    .width(Length::Shrink)
+                    .height(Length::Shrink)
+                    .padding(Padding::from([3, 5])),
+            )
+            .style(Button::Menu(mode == m))
+            .on_press(Msg::Conf(ConfMsg::ReplayGain(m)))
+            .into()
+        };
+
+        column![
+            text("ReplayGain:").height(22).style(Text::Normal),
+            row![
+                mode_button("Off", ReplayGainMode::Off),
+                mode_button("Track", ReplayGainMode::Track),
+                mode_button("Album", ReplayGainMode::Album),
+                mode_button("Auto", ReplayGainMode::Auto),
+            ]
+            .spacing(3),
+            text("Preamp (dB):").style(Text::Normal),
+            HoverGrad::new(
+                row![
+                    container(
+                        SvgButton::new(TICK.into())
+                            .width(15)
+                            .height(15)
+                            .on_press(Msg::Settings(SettingsMsg::PreampSave)),
+                    )
+                    .height(30)
+                    .padding(3)
+                    .center_x()
+                    .center_y(),
+                    text_input("0", &self.settings.preamp).on_input(|val| {
+                        Msg::Settings(SettingsMsg::Preamp(val))
+                    })
+                ]
+                .into()
+            )
+            .height(Length::Shrink),
+            toggler(
+                "Prevent clipping".to_owned(),
+                self.config.get_prevent_clipping(),
+                |val| Msg::Conf(ConfMsg::PreventClipping(val))
+            ),
+        ]
+        .spacing(3)
+        .into()
+    }
+
+    /// Gets settings of loudness analysis, with button to start it or with
+    /// its progress when it's running
+    fn loudness_settings(&self) -> Element {
+        let analysis: Element = match self.library.get_analysis_progress() {
+            Some((done, total)) => column![
+                row![
+                    text_button(
+                        "Cancel analysis",
+                        Msg::Lib(LibMsg::AnalysisCancel)
+                    ),
+                    text(format!("Analyzed {done}/{total} songs"))
+                        .style(Text::Darker),
+                ]
+                .spacing(10)
+                .align_items(Alignment::Center),
+                progress_bar(0.0..=total.max(1) as f32, done as f32)
+                    .height(6)
+                    .width(300),
+            ]
+            .spacing(5)
+            .into(),
+            None => text_button(
+                "Analyze loudness",
+                Msg::Lib(LibMsg::AnalysisStart),
+            ),
+        };
+
+        column![
+            text("Loudness analysis:").height(22).style(Text::Normal),
+            analysis,
+            toggler(
+                "Analyze songs without ReplayGain automatically".to_owned(),
+                self.config.get_auto_analyze(),
+                |val| Msg::Conf(ConfMsg::AutoAnalyze(val))
+            ),
+            toggler(
+                "Write analyzed ReplayGain to song files".to_owned(),
+                self.config.get_write_loudness_tags(),
+                |val| Msg::Conf(ConfMsg::WriteLoudnessTags(val))
+            ),
+        ]
+        .spacing(3)
+        .into()
+    }
 }
@@ -1,4 +1,4 @@
-use std::{path::PathBuf, time::Duration};
+use std::{collections::HashMap, path::PathBuf, time::Duration};
 
 use eyre::{Report, Result};
 use iced::{
@@ -10,13 +10,16 @@ use log::error;
 use serde_derive::{Deserialize, Serialize};
 
 use crate::{
-    config::{ConfMsg, Config},
+    config::{ConfMsg, Config, SmartPlaylist},
     gui::{
         app::{BumpApp, Msg},
         theme::{Button, Theme},
         widgets::hover_grad::HoverGrad,
     },
     hotkeys::Hotkey,
+    library::{
+        DuplicateMode, OrganizePlan, Query, ReplayGain, SongId, TagNormalizer,
+    },
 };
 
 use super::SettingsMsg;
@@ -28,13 +31,45 @@ pub enum SettingsPage {
     Library,
     Playback,
     Hotkeys,
+    Duplicates,
+    Organize,
 }
 
 pub struct Settings {
     page: SettingsPage,
     pub fade: String,
     pub vol_jmp: String,
+    /// ReplayGain preamp in dB
+    pub preamp: String,
     pub hotkey: String,
+    pub auto_purge: String,
+    pub ignore: String,
+    /// Name of the new smart playlist
+    pub smart_name: String,
+    /// Query of the new smart playlist
+    pub smart_query: String,
+    /// Error of adding the new smart playlist
+    pub smart_error: Option<String>,
+    /// Edited extensions of search paths by their index
+    pub path_exts: HashMap<usize, String>,
+    /// Edited max depths of search paths by their index
+    pub path_depths: HashMap<usize, String>,
+    /// How duplicate songs are detected
+    pub dup_mode: DuplicateMode,
+    /// True when duplicate songs are being searched
+    pub dup_search: bool,
+    /// Groups of duplicate songs, None when not searched yet
+    pub duplicates: Option<Vec<Vec<SongId>>>,
+    /// Template of song file paths the library is organized by
+    pub organize_template: String,
+    /// Planned moves of song files, None when not previewed yet
+    pub organize: Option<OrganizePlan>,
+    /// Result of organizing the library or error of the template
+    pub organize_result: Option<String>,
+    /// Encoding of text in legacy tags
+    pub legacy_encoding: String,
+    /// Error of the legacy encoding
+    pub encoding_error: Option<String>,
 }
 
 impl Settings {
@@ -50,10 +85,22 @@ impl Settings {
         );
 
         let vol_jmp = format!("{}", config.get_volume_step());
+        let preamp = config.get_replay_gain_preamp().to_string();
+        let auto_purge = config
+            .get_auto_purge()
+            .map(|days| days.to_string())
+            .unwrap_or_default();
 
         Self {
             fade,
             vol_jmp,
+            preamp,
+            auto_purge,
+            organize_template: config.get_organize_template().to_owned(),
+            legacy_encoding: config
+                .get_legacy_encoding()
+                .clone()
+                .unwrap_or_default(),
             ..Default::default()
         }
     }
@@ -65,7 +112,23 @@ impl Default for Settings {
             page: SettingsPage::Library,
             fade: "00:00.150".to_owned(),
             vol_jmp: "0.1".to_owned(),
+            preamp: "0".to_owned(),
             hotkey: "".to_owned(),
+            auto_purge: "".to_owned(),
+            ignore: "".to_owned(),
+            smart_name: "".to_owned(),
+            smart_query: "".to_owned(),
+            smart_error: None,
+            path_exts: HashMap::new(),
+            path_depths: HashMap::new(),
+            dup_mode: DuplicateMode::default(),
+            dup_search: false,
+            duplicates: None,
+            organize_template: "".to_owned(),
+            organize: None,
+            organize_result: None,
+            legacy_encoding: "".to_owned(),
+            encoding_error: None,
         }
     }
 }
@@ -78,6 +141,8 @@ impl BumpApp {
                 SettingsPage::Library => self.library_settings(),
                 SettingsPage::Playback => self.playback_settings(),
                 SettingsPage::Hotkeys => self.hotkeys_settings(),
+                SettingsPage::Duplicates => self.duplicates_settings(),
+                SettingsPage::Organize => self.organize_settings(),
             }
         ]
         .width(Length::Fill)
@@ -121,6 +186,25 @@ impl BumpApp {
                 }
                 Command::none()
             }
+            SettingsMsg::Preamp(val) => {
+                self.settings.preamp = val;
+                Command::none()
+            }
+            SettingsMsg::PreampSave => {
+                if let Ok(val) = self.settings.preamp.trim().parse::<f32>() {
+                    let max = ReplayGain::MAX_PREAMP;
+                    let val = if val.is_finite() {
+                        val.clamp(-max, max)
+                    } else {
+                        0.
+                    };
+                    self.settings.preamp = val.to_string();
+                    self.config.set_replay_gain_preamp(val);
+                    self.player.load_config(&self.config);
+                    self.player.apply_gain(&self.library);
+                }
+                Command::none()
+            }
             SettingsMsg::Hotkey(val) => {
                 self.settings.hotkey = val;
                 Command::none()
@@ -129,6 +213,74 @@ impl BumpApp {
                 _ = self.add_hotkey();
                 Command::none()
             }
+            SettingsMsg::AutoPurge(val) => {
+                self.settings.auto_purge = val;
+                Command::none()
+            }
+            SettingsMsg::AutoPurgeSave => {
+                let val = self.settings.auto_purge.trim();
+                if val.is_empty() {
+                    self.config.set_auto_purge(None);
+                } else if let Ok(days) = val.parse::<u32>() {
+                    self.config.set_auto_purge(Some(days));
+                    self.auto_purge();
+                }
+                Command::none()
+            }
+            SettingsMsg::Ignore(val) => {
+                self.settings.ignore = val;
+                Command::none()
+            }
+            SettingsMsg::SmartName(val) => {
+                self.settings.smart_name = val;
+                Command::none()
+            }
+            SettingsMsg::SmartQuery(val) => {
+                self.settings.smart_query = val;
+                Command::none()
+            }
+            SettingsMsg::SmartSave => {
+                self.add_smart_playlist();
+                Command::none()
+            }
+            SettingsMsg::PathExtensions(id, val) => {
+                self.settings.path_exts.insert(id, val);
+                Command::none()
+            }
+            SettingsMsg::PathDepth(id, val) => {
+                self.settings.path_depths.insert(id, val);
+                Command::none()
+            }
+            SettingsMsg::DuplicateMode(mode) => {
+                self.settings.dup_mode = mode;
+                Command::none()
+            }
+            SettingsMsg::OrganizeTemplate(val) => {
+                self.settings.organize_template = val;
+                self.settings.organize = None;
+                Command::none()
+            }
+            SettingsMsg::OrganizePreview => {
+                self.preview_organize();
+                Command::none()
+            }
+            SettingsMsg::LegacyEncoding(val) => {
+                self.settings.legacy_encoding = val;
+                self.settings.encoding_error = None;
+                Command::none()
+            }
+            SettingsMsg::LegacyEncodingSave => {
+                let val = self.settings.legacy_encoding.trim();
+                if val.is_empty() {
+                    self.config.set_legacy_encoding(None);
+                } else if TagNormalizer::is_encoding(val) {
+                    self.config.set_legacy_encoding(Some(val.to_owned()));
+                } else {
+                    self.settings.encoding_error =
+                        Some(format!("Unknown encoding '{val}'"));
+                }
+                Command::none()
+            }
         }
     }
 
@@ -161,6 +313,28 @@ impl BumpApp {
             )
             .style(Button::Menu(self.settings.page == SettingsPage::Hotkeys))
             .on_press(Msg::Settings(SettingsMsg::Page(SettingsPage::Hotkeys))),
+            button(
+                HoverGrad::new(text("Duplicates").into())
+                    .width(Length::Shrink)
+                    .height(Length::Shrink)
+                    .padding(Padding::from([3, 5]))
+            )
+            .style(Button::Menu(
+                self.settings.page == SettingsPage::Duplicates
+            ))
+            .on_press(Msg::Settings(SettingsMsg::Page(
+                SettingsPage::Duplicates
+            ))),
+            button(
+                HoverGrad::new(text("Organize").into())
+                    .width(Length::Shrink)
+                    .height(Length::Shrink)
+                    .padding(Padding::from([3, 5]))
+            )
+            .style(Button::Menu(self.settings.page == SettingsPage::Organize))
+            .on_press(Msg::Settings(SettingsMsg::Page(
+                SettingsPage::Organize
+            ))),
         ]
         .spacing(5)
         .into()
@@ -201,6 +375,46 @@ impl BumpApp {
             Err(Report::msg("Invalid format"))
         }
     }
+
+    /// Plans organizing of the library by the template from the settings,
+    /// the template is saved to config when it's valid
+    fn preview_organize(&mut self) {
+        self.settings.organize_result = None;
+        let template = self.settings.organize_template.trim();
+        match self.library.plan_organize(&self.config, template) {
+            Ok(plan) => {
+                self.config.set_organize_template(template.to_owned());
+                self.settings.organize = Some(plan);
+            }
+            Err(e) => {
+                self.settings.organize = None;
+                self.settings.organize_result =
+                    Some(format!("Invalid template: {e}"));
+            }
+        }
+    }
+
+    /// Adds smart playlist from the settings, when its query is invalid,
+    /// the error is shown instead
+    fn add_smart_playlist(&mut self) {
+        let name = self.settings.smart_name.trim();
+        self.settings.smart_error = if name.is_empty() {
+            Some("Smart playlist must have a name".to_owned())
+        } else {
+            match Query::parse(&self.settings.smart_query) {
+                Ok(_) => {
+                    self.config.add_smart_playlist(SmartPlaylist::new(
+                        name.to_owned(),
+                        self.settings.smart_query.trim().to_owned(),
+                    ));
+                    self.settings.smart_name.clear();
+                    self.settings.smart_query.clear();
+                    None
+                }
+                Err(e) => Some(format!("Invalid query: {e}")),
+            }
+        };
+    }
 }
 
 pub async fn pick_folder() -> Option<Vec<PathBuf>> {
@@ -0,0 +1,329 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use iced::{
+    widget::{
+        column, image, row, scrollable, text, text_input, Column, Space,
+    },
+    Command, Renderer,
+};
+use iced_core::{alignment::Horizontal, Alignment, Length, Padding};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::library::{
+    ArtCache, ArtEdit, ArtSize, Library, SongId, TagEdit, TagField,
+};
+
+use super::{
+    app::{BumpApp, Msg, Page},
+    settings::text_button,
+    theme::{Text, Theme},
+};
+
+type Element<'a> = iced::Element<'a, Msg, Renderer<Theme>>;
+
+/// Tag editor messages
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum TagMsg {
+    /// Opens the editor with the songs selected in the library
+    Open,
+    Field(TagField, String),
+    /// Discards changes of the field, songs keep their existing values
+    Keep(TagField),
+    PickArt,
+    /// Art file picked in the dialog, None when it was cancelled
+    ArtPicked(Option<PathBuf>),
+    Art(ArtEdit),
+    Save,
+}
+
+/// State of the tag editor
+#[derive(Default)]
+pub struct TagEditor {
+    /// Songs whose tags are edited
+    ids: Vec<SongId>,
+    /// Values of the fields shared by all the edited songs, None when the
+    /// songs have different values
+    values: BTreeMap<TagField, Option<String>>,
+    /// Changes made in the editor
+    edit: TagEdit,
+    /// Error of saving the changes
+    error: Option<String>,
+    /// True while the changes are being written
+    saving: bool,
+}
+
+impl TagEditor {
+    /// Creates editor of the songs with the given IDs
+    pub fn new(library: &Library, ids: Vec<SongId>) -> Self {
+        let songs: Vec<_> = ids
+            .iter()
+            .filter_map(|&id| library.get_song_ref(id))
+            .collect();
+        let values = TagField::ALL
+            .iter()
+            .map(|&field| {
+                let mut values = songs.iter().map(|s| field.get(s));
+                let first = values.next().unwrap_or_default();
+                let value = values.all(|v| v == first).then_some(first);
+                (field, value)
+            })
+            .collect();
+
+        Self {
+            ids,
+            values,
+            ..Default::default()
+        }
+    }
+
+    /// Gets value of the field shown in the editor, None when the songs
+    /// keep their different values
+    fn get_value(&self, field: TagField) -> Option<&str> {
+        match self.edit.fields.get(&field) {
+            Some(value) => Some(value),
+            None => self.values.get(&field).and_then(|v| v.as_deref()),
+        }
+    }
+
+    /// Sets value of the field, value same as the shared value of the
+    /// songs isn't a change
+    fn set_value(&mut self, field: TagField, value: String) {
+        if self
+            .values
+            .get(&field)
+            .is_some_and(|v| v == &Some(value.clone()))
+        {
+            self.edit.fields.remove(&field);
+        } else {
+            self.edit.fields.insert(field, value);
+        }
+    }
+}
+
+impl BumpApp {
+    pub fn view_tags(&self) -> Element {
+        let editor = &self.tag_editor;
+        let title = match editor.ids.as_slice() {
+            [id] => self
+                .library
+                .get_song_ref(*id)
+                .map(|s| s.get_name().to_owned())
+                .unwrap_or_default(),
+            ids => format!("{} songs", ids.len()),
+        };
+
+        let mut fields: Vec<Element> = TagField::ALL
+            .iter()
+            .map(|&field| self.tag_field(field))
+            .collect();
+        fields.push(self.tag_art());
+
+        column![
+            row![
+                column![
+                    text("Edit tags").size(25).style(Text::Light),
+                    text(title).style(Text::Dark),
+                ],
+                Space::new(Length::Fill, Length::Shrink),
+                text_button("Cancel", Msg::Page(Page::Library)),
+                text_button("Save", Msg::Tags(TagMsg::Save)),
+            ]
+            .spacing(5)
+            .align_items(Alignment::Center)
+            .padding(5),
+            match (&editor.error, editor.saving) {
+                (_, true) => text("Saving...")
+                    .size(14)
+                    .style(Text::Darker)
+                    .width(Length::Fill)
+                    .horizontal_alignment(Horizontal::Right)
+                    .into(),
+                (Some(error), _) => text(error)
+                    .size(14)
+                    .style(Text::Darker)
+                    .width(Length::Fill)
+                    .horizontal_alignment(Horizontal::Right)
+                    .into(),
+                (None, _) => Element::from(Space::new(Length::Shrink, 0)),
+            },
+            scrollable(
+                Column::with_children(fields)
+                    .spacing(3)
+                    .padding(Padding::from([5, 15])),
+            ),
+        ]
+        .width(Length::Fill)
+        .spacing(5)
+        .into()
+    }
+
+    /// Tag editor update function
+    pub fn tags_update(&mut self, msg: TagMsg) -> Command<Msg> {
+        match msg {
+            TagMsg::Open => {
+                let ids: Vec<SongId> = self
+                    .shown
+                    .iter()
+                    .copied()
+                    .filter(|id| self.selected.contains(id))
+                    .collect();
+                if !ids.is_empty() {
+                    self.tag_editor = TagEditor::new(&self.library, ids);
+                    self.page = Page::Tags;
+                }
+            }
+            TagMsg::Field(field, value) => {
+                self.tag_editor.set_value(field, value)
+            }
+            TagMsg::Keep(field) => {
+                self.tag_editor.edit.fields.remove(&field);
+            }
+            TagMsg::PickArt => {
+                return Command::perform(pick_art(), |path| {
+                    Msg::Tags(TagMsg::ArtPicked(path))
+                })
+            }
+            TagMsg::ArtPicked(path) => {
+                if let Some(path) = path {
+                    self.tag_editor.edit.art = ArtEdit::Set(path);
+                }
+            }
+            TagMsg::Art(art) => self.tag_editor.edit.art = art,
+            TagMsg::Save => self.save_tags(),
+        }
+        Command::none()
+    }
+
+    /// Starts writing the changes of the tag editor to the songs
+    fn save_tags(&mut self) {
+        let editor = &mut self.tag_editor;
+        if editor.edit.is_empty() {
+            self.page = Page::Library;
+            return;
+        }
+        if editor.saving {
+            return;
+        }
+
+        editor.error = match self.library.edit_tags(
+            &editor.ids,
+            &editor.edit,
+            self.sender.clone(),
+        ) {
+            Ok(_) => {
+                editor.saving = true;
+                None
+            }
+            Err(e) => Some(e.to_string()),
+        };
+    }
+
+    /// Ends saving of the tag editor, when all songs were written, returns
+    /// to the library
+    pub fn tags_written(&mut self, failed: usize) {
+        let editor = &mut self.tag_editor;
+        if !editor.saving {
+            return;
+        }
+        editor.saving = false;
+
+        if failed > 0 {
+            editor.error =
+                Some(format!("Failed to write tags of {failed} songs"));
+            return;
+        }
+        self.selected.clear();
+        if self.page == Page::Tags {
+            self.page = Page::Library;
+        }
+    }
+
+    /// Gets row with the field of the tag editor
+    fn tag_field(&self, field: TagField) -> Element {
+        let editor = &self.tag_editor;
+        let mixed = editor.values.get(&field).is_some_and(|v| v.is_none());
+        let placeholder = if mixed { "Keep existing" } else { "" };
+
+        let mut items: Vec<Element> = vec![
+            text(field.name()).width(120).style(Text::Normal).into(),
+            text_input(placeholder, editor.get_value(field).unwrap_or(""))
+                .on_input(move |v| Msg::Tags(TagMsg::Field(field, v)))
+                .into(),
+        ];
+        let inferred = editor
+            .ids
+            .iter()
+            .filter_map(|&id| self.library.get_song_ref(id))
+            .any(|s| s.is_inferred(field));
+        if inferred && !editor.edit.fields.contains_key(&field) {
+            items.push(
+                text("Inferred from path")
+                    .size(14)
+                    .style(Text::Darker)
+                    .into(),
+            );
+        }
+        if editor.edit.fields.contains_key(&field) {
+            items.push(text_button(
+                if mixed { "Keep existing" } else { "Revert" },
+                Msg::Tags(TagMsg::Keep(field)),
+            ));
+        }
+        row(items).spacing(5).align_items(Alignment::Center).into()
+    }
+
+    /// Gets art of the tag editor, the new art or the art shared by the
+    /// edited songs
+    fn tag_art(&self) -> Element {
+        let editor = &self.tag_editor;
+        let mut arts = editor
+            .ids
+            .iter()
+            .filter_map(|&id| self.library.get_song_ref(id))
+            .map(|s| s.get_art());
+        let first = arts.next().flatten();
+        let shared = first.filter(|&a| arts.all(|b| b == Some(a)));
+
+        let (preview, status): (Option<PathBuf>, _) = match &editor.edit.art {
+            ArtEdit::Set(path) => (Some(path.to_owned()), "New art"),
+            ArtEdit::Remove => (None, "Art will be removed"),
+            ArtEdit::Keep => match shared {
+                Some(id) => (
+                    Some(ArtCache::new(&self.config).path(id, ArtSize::Small)),
+                    "",
+                ),
+                None if editor.ids.len() > 1 => (None, "Keep existing"),
+                None => (None, "No art"),
+            },
+        };
+
+        let mut items: Vec<Element> =
+            vec![text("Art").width(120).style(Text::Normal).into()];
+        if let Some(path) = preview {
+            items.push(image(path).width(64).height(64).into());
+        }
+        items.extend([
+            text(status).style(Text::Darker).into(),
+            Space::new(Length::Fill, Length::Shrink).into(),
+            text_button("Choose...", Msg::Tags(TagMsg::PickArt)),
+            text_button("Remove", Msg::Tags(TagMsg::Art(ArtEdit::Remove))),
+        ]);
+        if editor.edit.art != ArtEdit::Keep {
+            items.push(text_button(
+                "Keep existing",
+                Msg::Tags(TagMsg::Art(ArtEdit::Keep)),
+            ));
+        }
+        row(items).spacing(5).align_items(Alignment::Center).into()
+    }
+}
+
+/// Lets the user choose an image file
+async fn pick_art() -> Option<PathBuf> {
+    rfd::AsyncFileDialog::new()
+        .set_title("Choose art...")
+        .add_filter("Images", &["jpg", "jpeg", "png", "bmp", "gif"])
+        .pick_file()
+        .await
+        .map(|handle| handle.path().to_owned())
+}
@@ -0,0 +1,122 @@
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use log::{info, warn};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::gui::app::{LibMsg, Msg};
+
+use super::{Loudness, SongId};
+
+/// Handle to a running loudness analysis of songs, used to get its progress
+/// and to cancel it. Clones share the same analysis.
+#[derive(Clone)]
+pub struct Analysis {
+    state: Arc<AnalysisState>,
+}
+
+struct AnalysisState {
+    /// Number of songs to analyze
+    total: usize,
+    /// Number of songs that were already analyzed
+    done: AtomicUsize,
+    cancelled: AtomicBool,
+    /// Song that is playing, its file isn't written to
+    playing: Mutex<Option<SongId>>,
+}
+
+impl Analysis {
+    /// Starts analysis of the given songs in a background thread. Songs are
+    /// analyzed one by one with the given pause between them, so that the
+    /// analysis doesn't slow down the rest of the system. Loudness of each
+    /// song is sent to the app as soon as it's measured, when `write_tags`
+    /// is true, it's also written to the song file as ReplayGain, unless
+    /// the song is playing.
+    pub fn start(
+        songs: Vec<(SongId, PathBuf)>,
+        pause: Duration,
+        write_tags: bool,
+        sender: UnboundedSender<Msg>,
+    ) -> Self {
+        let analysis = Self {
+            state: Arc::new(AnalysisState {
+                total: songs.len(),
+                done: AtomicUsize::new(0),
+                cancelled: AtomicBool::new(false),
+                playing: Mutex::new(None),
+            }),
+        };
+
+        let handle = analysis.clone();
+        thread::spawn(move || {
+            for (id, path) in songs {
+                if handle.is_cancelled() {
+                    break;
+                }
+
+                let loudness = match Loudness::analyze(&path, &|| {
+                    handle.is_cancelled()
+                }) {
+                    Ok(loudness) => Some(loudness),
+                    Err(_) if handle.is_cancelled() => break,
+                    Err(e) => {
+                        warn!("Failed to analyze {path:?}: {e}");
+                        None
+                    }
+                };
+                let write = write_tags && !handle.is_playing(id);
+                if let (Some(loudness), true) = (loudness, write) {
+                    if let Err(e) =
+                        loudness.get_replay_gain().write_tags(&path)
+                    {
+                        warn!("Failed to write ReplayGain to {path:?}: {e}");
+                    }
+                }
+
+                handle.state.done.fetch_add(1, Ordering::Relaxed);
+                _ = sender.send(Msg::Lib(LibMsg::Analyzed(id, loudness)));
+                thread::sleep(pause);
+            }
+
+            let (done, total) = handle.get_progress();
+            info!("Loudness analysis: {done} of {total} songs analyzed");
+            _ = sender.send(Msg::Lib(LibMsg::AnalysisEnded));
+        });
+
+        analysis
+    }
+
+    /// Cancels the analysis, songs analyzed so far keep their loudness
+    pub fn cancel(&self) {
+        self.state.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Checks whether the analysis was cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.state.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Sets the song that is playing, so that its file isn't written to
+    pub fn set_playing(&self, id: Option<SongId>) {
+        if let Ok(mut playing) = self.state.playing.lock() {
+            *playing = id;
+        }
+    }
+
+    /// Checks whether the song with the given ID is playing
+    fn is_playing(&self, id: SongId) -> bool {
+        self.state.playing.lock().is_ok_and(|p| *p == Some(id))
+    }
+
+    /// Gets number of analyzed songs and number of all songs to analyze
+    pub fn get_progress(&self) -> (usize, usize) {
+        (self.state.done.load(Ordering::Relaxed), self.state.total)
+    }
+}
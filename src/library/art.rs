@@ -0,0 +1,260 @@
+use std::{
+    fmt::Display,
+    fs,
+    hash::Hasher,
+    io::Cursor,
+    path::{Path, PathBuf},
+    thread,
+};
+
+use eyre::Result;
+use image::{DynamicImage, ImageOutputFormat};
+use serde_derive::{Deserialize, Serialize};
+use twox_hash::XxHash64;
+
+use crate::config::Config;
+
+use super::Fingerprint;
+
+/// Handle of the art in the [`ArtCache`], it's the hash of the art content,
+/// so songs with the same art share it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ArtId(u64);
+
+/// Size of the art thumbnail
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArtSize {
+    /// Used in lists and the player bar
+    Small,
+    Medium,
+    /// Used for album views
+    Large,
+}
+
+/// Art image found in a directory of songs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FolderArt {
+    /// Path to the image file
+    path: PathBuf,
+    /// Fingerprint of the image file when it was saved to the cache
+    fingerprint: Fingerprint,
+    /// Handle of the art in the cache
+    id: ArtId,
+}
+
+/// Cache of art thumbnails, thumbnails are saved as files named by the
+/// art handle and size
+#[derive(Debug, Clone)]
+pub struct ArtCache {
+    dir: PathBuf,
+}
+
+impl ArtCache {
+    /// Creates [`ArtCache`] in the art directory from the config
+    pub fn new(config: &Config) -> Self {
+        Self {
+            dir: config.get_art_path().to_owned(),
+        }
+    }
+
+    /// Saves thumbnails of the given picture to the cache, thumbnails that
+    /// are already cached aren't created again. Returns handle of the art.
+    pub fn save(&self, data: &[u8]) -> Result<ArtId> {
+        let mut hasher = XxHash64::with_seed(0);
+        hasher.write(data);
+        let id = ArtId(hasher.finish());
+
+        let missing: Vec<ArtSize> = ArtSize::ALL
+            .into_iter()
+            .filter(|&s| !self.path(id, s).exists())
+            .collect();
+        if missing.is_empty() {
+            return Ok(id);
+        }
+
+        fs::create_dir_all(&self.dir)?;
+        let image = image::load_from_memory(data)?;
+        for size in missing {
+            self.save_thumbnail(&image, &self.path(id, size), size)?;
+        }
+        Ok(id)
+    }
+
+    /// Gets path to the thumbnail of the art in the given size
+    pub fn path(&self, id: ArtId, size: ArtSize) -> PathBuf {
+        self.dir.join(ArtCache::file_name(id, size))
+    }
+
+    /// Gets name of the thumbnail file of the art in the given size
+    pub fn file_name(id: ArtId, size: ArtSize) -> String {
+        format!("{id}_{}.jpg", size.pixels())
+    }
+
+    /// Gets path to the cached thumbnail file with the given name, None
+    /// when the name isn't name of a thumbnail
+    pub fn get_file(&self, name: &str) -> Option<PathBuf> {
+        let (id, size) = name.strip_suffix(".jpg")?.split_once('_')?;
+        let valid = id.len() == 16
+            && id.chars().all(|c| c.is_ascii_hexdigit())
+            && ArtSize::ALL.iter().any(|s| s.pixels().to_string() == size);
+        valid.then(|| self.dir.join(name))
+    }
+
+    /// Saves thumbnail of the image in the given size to the given path
+    fn save_thumbnail(
+        &self,
+        image: &DynamicImage,
+        path: &Path,
+        size: ArtSize,
+    ) -> Result<()> {
+        let px = size.pixels();
+        let thumb = if image.width() > px || image.height() > px {
+            image.thumbnail(px, px)
+        } else {
+            image.clone()
+        };
+
+        let mut data = Cursor::new(Vec::new());
+        DynamicImage::ImageRgb8(thumb.to_rgb8())
+            .write_to(&mut data, ImageOutputFormat::Jpeg(85))?;
+
+        // Songs are loaded in parallel, so the same art can be saved by
+        // more threads, the rename makes sure the file is always complete
+        let tmp =
+            path.with_extension(format!("{:?}.tmp", thread::current().id()));
+        fs::write(&tmp, data.into_inner())?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+impl FolderArt {
+    /// Extensions of image files that can be used as art
+    const EXTENSIONS: [&'static str; 6] =
+        ["jpg", "jpeg", "png", "gif", "bmp", "webp"];
+
+    /// Saves the image on the given path to the art cache
+    pub fn load(path: PathBuf, cache: &ArtCache) -> Result<Self> {
+        let fingerprint = Fingerprint::load(&path)?;
+        let id = cache.save(&fs::read(&path)?)?;
+        Ok(Self {
+            path,
+            fingerprint,
+            id,
+        })
+    }
+
+    /// Gets handle of the art in the cache
+    pub fn get_id(&self) -> ArtId {
+        self.id
+    }
+
+    /// Checks whether the art was loaded from the given image file and the
+    /// file didn't change since
+    pub fn is_current(&self, path: &Path) -> bool {
+        self.path == path
+            && Fingerprint::load(path).is_ok_and(|f| f == self.fingerprint)
+    }
+
+    /// Finds art image in the given directory. Images matching earlier
+    /// patterns are preferred, patterns are case insensitive and can
+    /// contain `*` and `?` wildcards.
+    pub fn find_file(dir: &Path, patterns: &[String]) -> Option<PathBuf> {
+        let names = FolderArt::image_names(dir);
+        patterns.iter().find_map(|p| {
+            names
+                .iter()
+                .find(|n| FolderArt::matches(p, n))
+                .map(|n| dir.join(n))
+        })
+    }
+
+    /// Finds all art images in the given directory matching any of the
+    /// patterns
+    pub fn find_files(dir: &Path, patterns: &[String]) -> Vec<PathBuf> {
+        FolderArt::image_names(dir)
+            .iter()
+            .filter(|n| patterns.iter().any(|p| FolderArt::matches(p, n)))
+            .map(|n| dir.join(n))
+            .collect()
+    }
+
+    /// Gets sorted names of the image files in the directory
+    fn image_names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = match fs::read_dir(dir) {
+            Ok(entries) => entries
+                .flatten()
+                .filter(|e| e.file_type().is_ok_and(|t| !t.is_dir()))
+                .filter_map(|e| e.file_name().into_string().ok())
+                .filter(|n| {
+                    Path::new(n).extension().is_some_and(|e| {
+                        let e = e.to_string_lossy().to_lowercase();
+                        FolderArt::EXTENSIONS.contains(&e.as_str())
+                    })
+                })
+                .collect(),
+            Err(_) => return Vec::new(),
+        };
+        names.sort();
+        names
+    }
+
+    /// Checks whether the file name matches the pattern case insensitively
+    fn matches(pattern: &str, name: &str) -> bool {
+        let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+        let name: Vec<char> = name.to_lowercase().chars().collect();
+        wildcard_match(&pattern, &name)
+    }
+}
+
+impl ArtSize {
+    /// All the thumbnail sizes
+    pub const ALL: [ArtSize; 3] =
+        [ArtSize::Small, ArtSize::Medium, ArtSize::Large];
+
+    /// Gets maximal width and height of the thumbnail in pixels
+    pub fn pixels(&self) -> u32 {
+        match self {
+            ArtSize::Small => 64,
+            ArtSize::Medium => 256,
+            ArtSize::Large => 512,
+        }
+    }
+}
+
+impl Display for ArtId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+/// Checks whether the name matches the pattern, `*` matches any number of
+/// characters and `?` matches any character
+fn wildcard_match(pattern: &[char], name: &[char]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // Position of the last star in the pattern and the name it matched to
+    let mut star = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                // The star matches one more character
+                Some((sp, sn)) => {
+                    star = Some((sp, sn + 1));
+                    p = sp + 1;
+                    n = sn + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
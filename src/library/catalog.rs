@@ -0,0 +1,312 @@
+use std::{
+    cmp::Ordering, collections::HashMap, path::PathBuf, time::Duration,
+};
+
+use super::{art::ArtId, search::normalize, sort::natural_cmp, Song, SongId};
+
+/// Album made of the library songs
+#[derive(Debug, Clone, PartialEq)]
+pub struct Album {
+    /// Name of the album
+    name: String,
+    /// Artist of the album, [`Album::VARIOUS`] for compilations
+    artist: String,
+    /// True when the album contains songs of various artists
+    compilation: bool,
+    /// Songs of the album in the track order
+    songs: Vec<SongId>,
+    /// Total length of the songs of the album
+    length: Duration,
+    /// Earliest release year of the songs of the album
+    year: Option<i32>,
+    /// Earliest release date of the songs of the album
+    date: Option<String>,
+    /// Cover art of the album, art of its first song that has art
+    art: Option<ArtId>,
+}
+
+/// Artist with their songs and albums
+#[derive(Debug, Clone, PartialEq)]
+pub struct Artist {
+    /// Name of the artist
+    name: String,
+    /// Songs of the artist, ordered by albums and tracks
+    songs: Vec<SongId>,
+    /// Indexes of albums of the artist in [`Catalog::get_albums`], albums the
+    /// artist only has songs on are included
+    albums: Vec<usize>,
+}
+
+/// Albums and artists made of the library songs, deleted songs and
+/// duplicates are skipped
+#[derive(Debug, Clone, Default)]
+pub struct Catalog {
+    albums: Vec<Album>,
+    artists: Vec<Artist>,
+}
+
+impl Catalog {
+    /// Creates [`Catalog`] of the given songs
+    pub fn new(songs: &[Song]) -> Self {
+        let songs: Vec<&Song> = songs
+            .iter()
+            .filter(|s| !s.get_deleted() && s.get_duplicate_of().is_none())
+            .collect();
+
+        // Songs without album artist are grouped by their directory, so
+        // that albums with the same name aren't merged
+        let mut groups: HashMap<
+            (String, String, Option<PathBuf>),
+            Vec<&Song>,
+        > = HashMap::new();
+        for song in songs.iter() {
+            let album = match tag(song.get_album()) {
+                Some(album) => album,
+                None => continue,
+            };
+            let key = match tag(song.get_album_artist()) {
+                Some(artist) => (normalize(album), normalize(artist), None),
+                None => (
+                    normalize(album),
+                    String::new(),
+                    song.get_path().parent().map(|p| p.to_owned()),
+                ),
+            };
+            groups.entry(key).or_default().push(song);
+        }
+
+        let mut albums: Vec<Album> =
+            groups.into_values().map(Album::new).collect();
+        albums.sort_by(|a, b| {
+            a.compilation
+                .cmp(&b.compilation)
+                .then_with(|| {
+                    natural_cmp(&normalize(&a.artist), &normalize(&b.artist))
+                })
+                .then(
+                    a.year
+                        .unwrap_or(i32::MAX)
+                        .cmp(&b.year.unwrap_or(i32::MAX)),
+                )
+                .then_with(|| {
+                    natural_cmp(&normalize(&a.name), &normalize(&b.name))
+                })
+        });
+
+        let artists = Catalog::artists(&songs, &albums);
+        Self { albums, artists }
+    }
+
+    /// Gets albums ordered by their artist, year and name, compilations
+    /// are last
+    pub fn get_albums(&self) -> &[Album] {
+        &self.albums
+    }
+
+    /// Gets artists ordered by their name
+    pub fn get_artists(&self) -> &[Artist] {
+        &self.artists
+    }
+
+    /// Gets artists of the songs with their albums
+    fn artists(songs: &[&Song], albums: &[Album]) -> Vec<Artist> {
+        let mut artists: HashMap<String, Artist> = HashMap::new();
+
+        // Songs in albums are added by albums, so they're in track order
+        let mut rest: HashMap<SongId, &Song> =
+            songs.iter().map(|s| (s.get_id(), *s)).collect();
+        for (i, album) in albums.iter().enumerate() {
+            for id in album.songs.iter() {
+                let song = match rest.remove(id) {
+                    Some(song) => song,
+                    None => continue,
+                };
+                if let Some(name) = tag(song.get_artist()) {
+                    let artist = get_artist(&mut artists, name);
+                    artist.songs.push(song.get_id());
+                    if artist.albums.last() != Some(&i) {
+                        artist.albums.push(i);
+                    }
+                }
+            }
+            match tag(&album.artist) {
+                Some(name) if !album.compilation => {
+                    let artist = get_artist(&mut artists, name);
+                    if !artist.albums.contains(&i) {
+                        artist.albums.push(i);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // Songs without album keep the library order
+        for song in songs.iter().filter(|s| rest.contains_key(&s.get_id())) {
+            if let Some(name) = tag(song.get_artist()) {
+                get_artist(&mut artists, name).songs.push(song.get_id());
+            }
+        }
+
+        let mut artists: Vec<Artist> = artists.into_values().collect();
+        artists.sort_by(|a, b| {
+            natural_cmp(&normalize(&a.name), &normalize(&b.name))
+        });
+        artists
+    }
+}
+
+impl Album {
+    /// Name of the artist of compilations
+    pub const VARIOUS: &'static str = "Various Artists";
+
+    /// Creates album from its songs
+    fn new(mut songs: Vec<&Song>) -> Self {
+        songs.sort_by(|a, b| track_cmp(a, b));
+
+        let name = songs[0].get_album().to_owned();
+        let (artist, compilation) = match tag(songs[0].get_album_artist()) {
+            Some(artist) => (artist.to_owned(), is_various(artist)),
+            None => Album::guess_artist(&songs),
+        };
+        let artist = if compilation {
+            Album::VARIOUS.to_owned()
+        } else {
+            artist
+        };
+
+        Self {
+            name,
+            artist,
+            compilation,
+            length: songs.iter().map(|s| *s.get_length()).sum(),
+            year: songs.iter().filter_map(|s| s.get_year()).min(),
+            date: songs
+                .iter()
+                .filter_map(|s| s.get_date())
+                .min()
+                .map(|d| d.to_owned()),
+            art: songs.iter().find_map(|s| s.get_art()),
+            songs: songs.iter().map(|s| s.get_id()).collect(),
+        }
+    }
+
+    /// Guesses album artist of songs without album artist, album is
+    /// compilation when no artist has at least half of the songs
+    fn guess_artist(songs: &[&Song]) -> (String, bool) {
+        let mut counts: HashMap<String, (usize, &str)> = HashMap::new();
+        for song in songs {
+            if let Some(artist) = tag(song.get_artist()) {
+                counts.entry(normalize(artist)).or_insert((0, artist)).0 += 1;
+            }
+        }
+
+        match counts.into_values().max_by_key(|(count, _)| *count) {
+            Some((count, artist)) if count * 2 >= songs.len() => {
+                (artist.to_owned(), is_various(artist))
+            }
+            Some(_) => (String::new(), true),
+            None => ("-".to_owned(), false),
+        }
+    }
+
+    //>=====================================================================<//
+    //                           Getters & Setters                           //
+    //>=====================================================================<//
+
+    /// Gets name of the album
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Gets artist of the album, [`Album::VARIOUS`] for compilations
+    pub fn get_artist(&self) -> &str {
+        &self.artist
+    }
+
+    /// Checks whether the album contains songs of various artists
+    pub fn is_compilation(&self) -> bool {
+        self.compilation
+    }
+
+    /// Gets songs of the album in the track order
+    pub fn get_songs(&self) -> &[SongId] {
+        &self.songs
+    }
+
+    /// Gets total length of the album
+    pub fn get_length(&self) -> Duration {
+        self.length
+    }
+
+    /// Gets release year of the album
+    pub fn get_year(&self) -> Option<i32> {
+        self.year
+    }
+
+    /// Gets release date of the album as saved in the tags
+    pub fn get_date(&self) -> Option<&str> {
+        self.date.as_deref()
+    }
+
+    /// Gets handle of the cover art of the album
+    pub fn get_art(&self) -> Option<ArtId> {
+        self.art
+    }
+}
+
+impl Artist {
+    /// Gets name of the artist
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Gets songs of the artist, ordered by albums and tracks
+    pub fn get_songs(&self) -> &[SongId] {
+        &self.songs
+    }
+
+    /// Gets indexes of the albums of the artist in [`Catalog::get_albums`]
+    pub fn get_albums(&self) -> &[usize] {
+        &self.albums
+    }
+}
+
+/// Gets artist with the given name, the artist is added when missing
+fn get_artist<'a>(
+    artists: &'a mut HashMap<String, Artist>,
+    name: &str,
+) -> &'a mut Artist {
+    artists.entry(normalize(name)).or_insert_with(|| Artist {
+        name: name.to_owned(),
+        songs: Vec::new(),
+        albums: Vec::new(),
+    })
+}
+
+/// Gets tag value, None when the tag isn't set
+fn tag(value: &str) -> Option<&str> {
+    let value = value.trim();
+    (!value.is_empty() && value != "-").then_some(value)
+}
+
+/// Checks whether artist name means various artists
+fn is_various(artist: &str) -> bool {
+    matches!(
+        normalize(artist).as_str(),
+        "various artists" | "various" | "va"
+    )
+}
+
+/// Compares songs by their disc and track, songs without track are last
+/// and ordered by name
+fn track_cmp(a: &Song, b: &Song) -> Ordering {
+    let track = |s: &Song| (s.get_track().is_none(), s.get_track());
+    a.get_disc()
+        .unwrap_or(1)
+        .cmp(&b.get_disc().unwrap_or(1))
+        .then(track(a).cmp(&track(b)))
+        .then_with(|| {
+            natural_cmp(&normalize(a.get_name()), &normalize(b.get_name()))
+        })
+        .then_with(|| a.get_path().cmp(b.get_path()))
+}
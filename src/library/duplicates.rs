@@ -0,0 +1,311 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    path::Path,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::Duration,
+};
+
+use eyre::{Report, Result};
+use log::info;
+use serde_derive::{Deserialize, Serialize};
+use symphonia::{
+    core::{
+        audio::SampleBuffer, codecs::CODEC_TYPE_NULL, errors::Error,
+        io::MediaSourceStream, probe::Hint,
+    },
+    default::{get_codecs, get_probe},
+};
+
+use super::{search, Library, Song, SongId};
+
+/// How duplicate songs are detected
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum DuplicateMode {
+    /// Songs with the same title and artist and similar length
+    #[default]
+    Tags,
+    /// Songs with similar length and similar decoded audio
+    Audio,
+}
+
+impl Library {
+    /// Maximal difference in length of duplicate songs
+    const DUPLICATE_TOLERANCE: Duration = Duration::from_secs(2);
+
+    /// Finds groups of duplicate songs. Deleted songs and songs that are
+    /// already marked as duplicates are skipped.
+    pub fn find_duplicates(&self, mode: DuplicateMode) -> Vec<Vec<SongId>> {
+        let mut songs: Vec<&Song> = self
+            .get_songs()
+            .iter()
+            .filter(|s| !s.get_deleted() && s.get_duplicate_of().is_none())
+            .collect();
+        songs.sort_by_key(|s| *s.get_length());
+
+        let groups = match mode {
+            DuplicateMode::Tags => tags_duplicates(&songs),
+            DuplicateMode::Audio => audio_duplicates(&songs),
+        };
+        info!("Found {} groups of duplicate songs", groups.len());
+        groups
+    }
+}
+
+/// Finds duplicates by their normalized title and artist
+fn tags_duplicates(songs: &[&Song]) -> Vec<Vec<SongId>> {
+    let mut keys: HashMap<String, Vec<&Song>> = HashMap::new();
+    for song in songs {
+        if let Some(key) = tags_key(song) {
+            keys.entry(key).or_default().push(song);
+        }
+    }
+
+    let mut groups = Vec::new();
+    for songs in keys.into_values() {
+        let mut group: Vec<&Song> = Vec::new();
+        for song in songs {
+            let close = group.last().is_some_and(|last| {
+                *song.get_length() - *last.get_length()
+                    <= Library::DUPLICATE_TOLERANCE
+            });
+            if !close {
+                push_group(&mut groups, &group);
+                group.clear();
+            }
+            group.push(song);
+        }
+        push_group(&mut groups, &group);
+    }
+    groups.sort();
+    groups
+}
+
+/// Finds duplicates by their decoded audio
+fn audio_duplicates(songs: &[&Song]) -> Vec<Vec<SongId>> {
+    let prints = load_prints(songs);
+
+    let mut groups = Vec::new();
+    let mut grouped = vec![false; songs.len()];
+    for i in 0..songs.len() {
+        let print = match &prints[i] {
+            Some(print) if !grouped[i] => print,
+            _ => continue,
+        };
+
+        let mut group = vec![songs[i]];
+        for j in i + 1..songs.len() {
+            if *songs[j].get_length() - *songs[i].get_length()
+                > Library::DUPLICATE_TOLERANCE
+            {
+                break;
+            }
+            if !grouped[j]
+                && prints[j].as_ref().is_some_and(|p| print.similar(p))
+            {
+                grouped[j] = true;
+                group.push(songs[j]);
+            }
+        }
+        push_group(&mut groups, &group);
+    }
+    groups
+}
+
+/// Adds group of songs to the groups when it contains duplicates
+fn push_group(groups: &mut Vec<Vec<SongId>>, group: &[&Song]) {
+    if group.len() > 1 {
+        groups.push(group.iter().map(|s| s.get_id()).collect());
+    }
+}
+
+/// Gets key of the song made of its normalized title and artist. File name
+/// is used when the song has no title.
+fn tags_key(song: &Song) -> Option<String> {
+    let mut name = normalize(song.get_name());
+    if name.is_empty() {
+        name = normalize(&song.get_path().file_stem()?.to_string_lossy());
+    }
+    if name.is_empty() {
+        return None;
+    }
+    Some(format!("{}\0{name}", normalize(song.get_artist())))
+}
+
+/// Normalizes tag for comparing, case, diacritics, punctuation and
+/// whitespace are ignored
+fn normalize(tag: &str) -> String {
+    search::normalize(tag)
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect()
+}
+
+/// Loads audio prints of the songs in parallel, songs that don't have any
+/// song with similar length aren't loaded
+fn load_prints(songs: &[&Song]) -> Vec<Option<AudioPrint>> {
+    let has_pair = |i: usize| {
+        let len = *songs[i].get_length();
+        let close = |s: &&Song| {
+            s.get_length().abs_diff(len) <= Library::DUPLICATE_TOLERANCE
+        };
+        songs.get(i.wrapping_sub(1)).is_some_and(close)
+            || songs.get(i + 1).is_some_and(close)
+    };
+
+    let next = AtomicUsize::new(0);
+    let workers = thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(songs.len());
+    let mut prints: Vec<Option<AudioPrint>> =
+        (0..songs.len()).map(|_| None).collect();
+
+    thread::scope(|s| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                s.spawn(|| {
+                    let mut prints = Vec::new();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        if i >= songs.len() {
+                            break;
+                        }
+                        if has_pair(i) {
+                            let path = songs[i].get_path();
+                            prints.push((i, AudioPrint::load(path).ok()));
+                        }
+                    }
+                    prints
+                })
+            })
+            .collect();
+
+        for (i, print) in handles
+            .into_iter()
+            .flat_map(|h| h.join().unwrap_or_default())
+        {
+            prints[i] = print;
+        }
+    });
+    prints
+}
+
+/// Coarse fingerprint of decoded audio, loudness of the song in short
+/// windows from its beginning
+struct AudioPrint(Vec<f32>);
+
+impl AudioPrint {
+    /// Length of one window in seconds
+    const WINDOW: f32 = 0.1;
+    /// Number of windows in the print
+    const WINDOWS: usize = 300;
+    /// Minimal number of windows for the print to be compared
+    const MIN_WINDOWS: usize = 20;
+    /// Maximal shift in windows between compared prints, songs can start
+    /// with silence of different length
+    const MAX_SHIFT: usize = 10;
+    /// Minimal correlation of similar prints
+    const SIMILARITY: f32 = 0.9;
+
+    /// Decodes beginning of the song on the given path and creates its
+    /// print
+    fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path)?;
+        let stream =
+            MediaSourceStream::new(Box::new(file), Default::default());
+        let mut probe = get_probe().format(
+            &Hint::new(),
+            stream,
+            &Default::default(),
+            &Default::default(),
+        )?;
+
+        let track = probe
+            .format
+            .default_track()
+            .filter(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+            .ok_or(Report::msg("File doesn't contain audio"))?;
+        let track_id = track.id;
+        let mut decoder =
+            get_codecs().make(&track.codec_params, &Default::default())?;
+
+        let mut windows = Vec::new();
+        let mut sum = 0.;
+        let mut count = 0;
+        while windows.len() < AudioPrint::WINDOWS {
+            let packet = match probe.format.next_packet() {
+                Ok(packet) => packet,
+                Err(_) => break,
+            };
+            if packet.track_id() != track_id {
+                continue;
+            }
+            let decoded = match decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                Err(Error::DecodeError(_)) => continue,
+                Err(e) => return Err(e.into()),
+            };
+
+            let spec = *decoded.spec();
+            let window = spec.rate as f32 * AudioPrint::WINDOW;
+            let window = (window as usize * spec.channels.count()).max(1);
+            let mut buf =
+                SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+            buf.copy_interleaved_ref(decoded);
+
+            for sample in buf.samples() {
+                sum += sample * sample;
+                count += 1;
+                if count >= window {
+                    windows.push((sum / count as f32).sqrt());
+                    sum = 0.;
+                    count = 0;
+                }
+            }
+        }
+
+        if windows.len() < AudioPrint::MIN_WINDOWS {
+            return Err(Report::msg("Song is too short"));
+        }
+        Ok(Self(windows))
+    }
+
+    /// Checks whether the prints are similar
+    fn similar(&self, other: &AudioPrint) -> bool {
+        (0..=AudioPrint::MAX_SHIFT)
+            .flat_map(|s| [(s, 0), (0, s)])
+            .any(|(a, b)| {
+                let a = self.0.get(a..).unwrap_or_default();
+                let b = other.0.get(b..).unwrap_or_default();
+                correlation(a, b) >= AudioPrint::SIMILARITY
+            })
+    }
+}
+
+/// Gets correlation of the given values, values of different length are
+/// compared only to the length of the shorter
+fn correlation(a: &[f32], b: &[f32]) -> f32 {
+    let len = a.len().min(b.len());
+    if len < AudioPrint::MIN_WINDOWS {
+        return 0.;
+    }
+    let (a, b) = (&a[..len], &b[..len]);
+
+    let mean_a = a.iter().sum::<f32>() / len as f32;
+    let mean_b = b.iter().sum::<f32>() / len as f32;
+
+    let mut cov = 0.;
+    let mut var_a = 0.;
+    let mut var_b = 0.;
+    for (a, b) in a.iter().zip(b) {
+        cov += (a - mean_a) * (b - mean_b);
+        var_a += (a - mean_a) * (a - mean_a);
+        var_b += (b - mean_b) * (b - mean_b);
+    }
+
+    if var_a == 0. || var_b == 0. {
+        return 0.;
+    }
+    cov / (var_a * var_b).sqrt()
+}
@@ -0,0 +1,29 @@
+use std::{fs, io, path::Path};
+
+/// Identifies a file independently of the path it's reached by, used to
+/// detect symlink cycles and files reached by more than one path
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FileId(Inner);
+
+#[cfg(unix)]
+type Inner = (u64, u64);
+
+#[cfg(not(unix))]
+type Inner = std::path::PathBuf;
+
+impl FileId {
+    /// Gets [`FileId`] of the file on the given path, symlinks are followed
+    #[cfg(unix)]
+    pub fn of(path: &Path) -> io::Result<Self> {
+        use std::os::unix::fs::MetadataExt;
+
+        let meta = fs::metadata(path)?;
+        Ok(Self((meta.dev(), meta.ino())))
+    }
+
+    /// Gets [`FileId`] of the file on the given path, symlinks are followed
+    #[cfg(not(unix))]
+    pub fn of(path: &Path) -> io::Result<Self> {
+        Ok(Self(fs::canonicalize(path)?))
+    }
+}
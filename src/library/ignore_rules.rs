@@ -0,0 +1,101 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use log::warn;
+
+use crate::config::Config;
+
+/// Gitignore-style rules for ignoring files and directories when searching
+/// for songs. Rules are given globally in the config and by ignore files in
+/// the searched directories.
+#[derive(Clone, Default)]
+pub struct IgnoreRules {
+    /// Matchers ordered from the least to the most specific
+    matchers: Vec<Arc<Gitignore>>,
+}
+
+impl IgnoreRules {
+    /// Name of the file containing ignore rules for its directory
+    pub const FILE: &'static str = ".bumpignore";
+
+    /// Creates [`IgnoreRules`] with the given patterns relative to the
+    /// given search path
+    pub fn new(root: &Path, patterns: &[String]) -> Self {
+        let mut builder = GitignoreBuilder::new(root);
+        for pattern in patterns {
+            if let Err(e) = builder.add_line(None, pattern) {
+                warn!("Invalid ignore pattern '{pattern}': {e}");
+            }
+        }
+
+        let mut rules = IgnoreRules::default();
+        rules.push(builder.build());
+        rules
+    }
+
+    /// Gets rules for the given directory, which is found under one of the
+    /// search paths. Returns None when the directory is ignored.
+    pub fn for_dir(config: &Config, dir: &Path) -> Option<Self> {
+        let root = config.get_search_path(dir).map_or(dir, |p| p.get_path());
+
+        let mut rules = IgnoreRules::new(root, config.get_ignore());
+        rules = rules.enter(root);
+
+        let mut path = root.to_owned();
+        for part in dir.strip_prefix(root).ok()?.components() {
+            path.push(part);
+            if rules.is_ignored(&path, true) {
+                return None;
+            }
+            rules = rules.enter(&path);
+        }
+        Some(rules)
+    }
+
+    /// Gets rules for the content of the given directory, adding rules from
+    /// the directory ignore file
+    pub fn enter(&self, dir: &Path) -> Self {
+        let mut rules = self.clone();
+
+        let file: PathBuf = dir.join(IgnoreRules::FILE);
+        if file.is_file() {
+            let mut builder = GitignoreBuilder::new(dir);
+            if let Some(e) = builder.add(&file) {
+                warn!("Invalid ignore file {}: {e}", file.to_string_lossy());
+            }
+            rules.push(builder.build());
+        }
+        rules
+    }
+
+    /// Checks whether the given path is ignored
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        // More specific rules can override the less specific ones
+        for matcher in self.matchers.iter().rev() {
+            let res = matcher.matched(path, is_dir);
+            if !res.is_none() {
+                return res.is_ignore();
+            }
+        }
+        false
+    }
+}
+
+//>=========================================================================<//
+//                             Private functions                             //
+//>=========================================================================<//
+impl IgnoreRules {
+    /// Adds built matcher to the rules
+    fn push(&mut self, matcher: Result<Gitignore, ignore::Error>) {
+        match matcher {
+            Ok(matcher) if !matcher.is_empty() => {
+                self.matchers.push(Arc::new(matcher))
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Failed to build ignore rules: {e}"),
+        }
+    }
+}
@@ -1,37 +1,150 @@
 use crate::{
-    config::Config,
-    gui::app::{LibMsg, Msg},
+    config::{Config, SearchPath},
+    gui::app::{BumpApp, LibMsg, Msg},
 };
 use std::{
+    collections::{BTreeSet, HashMap, HashSet},
     fs::{self, read_dir, File},
+    path::{Path, PathBuf},
     thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime},
 };
 
-use super::song::Song;
-use eyre::Result;
+use super::{
+    analysis::Analysis,
+    art::{ArtCache, FolderArt},
+    catalog::{Album, Artist, Catalog},
+    duplicates::DuplicateMode,
+    file_id::FileId,
+    ignore_rules::IgnoreRules,
+    loudness::Loudness,
+    normalize::TagNormalizer,
+    organizer::{
+        OrganizeConflict, OrganizeMove, OrganizePlan, Organizing, PathTemplate,
+    },
+    query::Query,
+    scan::{Scan, ScanError, ScanProgress, ScanStats},
+    search::SearchIndex,
+    song::{Fingerprint, Song, SongId},
+    tag_edit::{TagEdit, TagField},
+    tag_infer::TagPattern,
+};
+use eyre::{Report, Result};
+use log::{error, info, warn};
 use serde_derive::{Deserialize, Serialize};
 use tokio::sync::mpsc::UnboundedSender;
 
+/// Version of the saved library, before version 1 songs didn't have
+/// track, disc, album artist, composer, comment and date, and songs without
+/// year had `i32::MAX` as year, before version 2 songs didn't have art,
+/// before version 3 songs didn't have ReplayGain and before version 4
+/// album artist, track and disc weren't read from all tags of the song
+const LIBRARY_VERSION: u32 = 4;
+
 #[derive(Serialize, Deserialize)]
 pub struct Library {
+    /// Version of the saved library
+    #[serde(default)]
+    version: u32,
     /// All songs in library
     songs: Vec<Song>,
+    /// ID that will be given to the next added song
+    #[serde(default)]
+    next_id: u64,
+    /// Song files that failed to load
+    #[serde(default)]
+    errors: Vec<ScanError>,
+    /// Art images found in the directories of the songs, by the directory
+    #[serde(default)]
+    folder_art: HashMap<PathBuf, FolderArt>,
     #[serde(skip)]
     load_process: Option<JoinHandle<Library>>,
+    /// Index of songs by their path
+    #[serde(skip)]
+    index: HashMap<PathBuf, usize>,
+    /// Index of songs by their ID
+    #[serde(skip)]
+    ids: HashMap<SongId, usize>,
+    /// Statistics of the last library scan
+    #[serde(skip)]
+    stats: Option<ScanStats>,
+    /// Changed paths received while finding songs
+    #[serde(skip)]
+    pending: Vec<PathBuf>,
+    /// Currently running scan
+    #[serde(skip)]
+    scan: Option<Scan>,
+    /// Last reported progress of the running scan
+    #[serde(skip)]
+    progress: Option<ScanProgress>,
+    /// Search paths that can't be reached
+    #[serde(skip)]
+    offline: HashSet<PathBuf>,
+    /// When the search paths were last checked for being reachable
+    #[serde(skip)]
+    roots_checked: Option<Instant>,
+    /// True while the search paths are being checked
+    #[serde(skip)]
+    checking_roots: bool,
+    /// Full-text index of the songs
+    #[serde(skip)]
+    search: SearchIndex,
+    /// Albums and artists of the songs, rebuilt when songs change
+    #[serde(skip)]
+    catalog: Catalog,
+    /// Currently running loudness analysis
+    #[serde(skip)]
+    analysis: Option<Analysis>,
+    /// Songs whose loudness couldn't be measured
+    #[serde(skip)]
+    not_analyzed: HashSet<SongId>,
+    /// Loudness measured while finding songs, finding would overwrite it
+    #[serde(skip)]
+    pending_loudness: Vec<(SongId, Loudness)>,
+    /// Folder art loaded while finding songs, finding would overwrite it
+    #[serde(skip)]
+    pending_art: Vec<(PathBuf, Option<FolderArt>)>,
+    /// Currently running organizing of the song files
+    #[serde(skip)]
+    organizing: Option<Organizing>,
+    /// Directories changed by the running organizing
+    #[serde(skip)]
+    organized: HashSet<PathBuf>,
+    /// Changed directories whose art wasn't loaded yet
+    #[serde(skip)]
+    art_dirs: HashSet<PathBuf>,
 }
 
 impl Library {
+    /// Minimal time between two checks of search paths being reachable
+    const ROOTS_INTERVAL: Duration = Duration::from_secs(5);
+
     /// Loads songs from the library
     pub fn load(config: &Config) -> Library {
         let path = config.get_library_path();
 
-        match fs::read_to_string(path) {
+        let mut lib = match fs::read_to_string(path) {
             Err(_) => Library::default(),
             Ok(l) => match serde_json::from_str::<Library>(&l) {
-                Err(_) => Library::default(),
+                Err(e) => {
+                    error!("Failed to load library: {e}");
+                    Library::default()
+                }
                 Ok(lib) => lib,
             },
+        };
+        lib.upgrade();
+        lib.assign_ids();
+        lib.reindex();
+        lib.search = SearchIndex::new(&lib.songs);
+        lib.catalog = Catalog::new(&lib.songs);
+        // Songs deleted before the time of deletion was saved are considered
+        // deleted since now
+        for song in lib.songs.iter_mut().filter(|s| s.get_deleted()) {
+            song.set_deleted(true);
         }
+        lib.update_roots(config);
+        lib
     }
 
     /// Saves songs to the library
@@ -45,62 +158,351 @@ impl Library {
         Ok(())
     }
 
-    /// Finds songs from song directories
-    pub fn find(&mut self, config: &Config) {
-        let mut paths = config.get_paths().clone();
-        let mut i = 0;
+    /// Finds songs from song directories, only songs that are new or whose
+    /// file changed since the last scan are loaded. Directories are walked
+    /// first and then the songs are loaded in parallel.
+    pub fn find(&mut self, config: &Config, scan: &Scan) {
+        let mut stats = ScanStats::default();
+        let mut found = vec![false; self.songs.len()];
 
-        for s in &mut self.songs {
-            s.set_deleted(true);
+        // Songs in disabled and offline search paths are kept as they are
+        self.update_roots(config);
+        for (song, found) in self.songs.iter().zip(found.iter_mut()) {
+            *found = song.get_offline()
+                || config
+                    .get_search_path(song.get_path())
+                    .is_some_and(|p| !p.get_enabled());
         }
 
-        while i < paths.len() {
-            let dir = &paths[i];
-            i += 1;
-            let dir = match read_dir(dir) {
-                Ok(dir) => dir,
-                Err(_) => continue,
-            };
-
-            for f in dir {
-                let f = match f {
-                    Ok(f) => f,
-                    Err(_) => continue,
-                };
-                let path = f.path();
+        let mut files = Vec::new();
+        let mut seen = HashSet::new();
+        for dir in config.get_paths().iter().filter(|p| p.get_enabled()) {
+            if !self.offline.contains(dir.get_path()) {
+                Library::walk_dir(
+                    config,
+                    dir.get_path(),
+                    &mut files,
+                    &mut seen,
+                    scan,
+                );
+            }
+        }
 
-                if path.is_dir() {
-                    if config.get_recursive_search() {
-                        paths.push(path.clone());
-                    }
-                    continue;
+        let mut errors = Vec::new();
+        let mut load = Vec::new();
+        for path in files {
+            if scan.is_cancelled() {
+                return;
+            }
+            match self.check_song(&path, &mut found, &mut stats) {
+                Ok(true) => load.push(path),
+                Ok(false) => scan.processed(false),
+                Err(e) => {
+                    errors.push(ScanError::new(path, e));
+                    scan.processed(true);
                 }
+            }
+        }
 
-                if let Some(ext) = path.extension() {
-                    let ext = ext.to_string_lossy();
-                    if !config
-                        .get_extensions()
-                        .iter()
-                        .any(|e| e == ext.as_ref())
+        let songs = scan.load_songs(
+            &load,
+            &ArtCache::new(config),
+            &TagNormalizer::new(config),
+        );
+        if scan.is_cancelled() {
+            return;
+        }
+        for (path, song) in songs {
+            match song {
+                Ok(song) => self.add_song(song, &mut found, &mut stats),
+                Err(e) => errors.push(ScanError::new(path, e)),
+            }
+        }
+
+        // Errors in search paths that weren't searched are kept
+        self.errors.retain(|e| {
+            config.get_search_path(&e.path).is_some_and(|p| {
+                !p.get_enabled() || self.offline.contains(p.get_path())
+            })
+        });
+        self.errors.extend(errors);
+
+        for (song, found) in self.songs.iter_mut().zip(found) {
+            if !found && !song.get_deleted() {
+                song.set_deleted(true);
+                stats.removed += 1;
+            }
+        }
+        self.find_folder_art(config, None);
+        self.infer_tags(config, None);
+        self.merge_artists(config);
+
+        info!(
+            "Library scan: {} added, {} updated, {} removed",
+            stats.added, stats.updated, stats.removed
+        );
+        self.stats = Some(stats);
+    }
+
+    /// Updates songs on the given changed paths. Paths can be files or
+    /// directories, that were created, modified, moved or deleted
+    pub fn update_paths(&mut self, config: &Config, paths: Vec<PathBuf>) {
+        // Finding would overwrite the changes and organizing changes the
+        // paths, so they are applied later
+        if self.load_process.is_some() || self.is_organizing() {
+            self.pending.extend(paths);
+            return;
+        }
+
+        let mut stats = ScanStats::default();
+        let mut found = vec![false; self.songs.len()];
+
+        // Changed art images update art of the songs in their directory
+        let mut dirs = HashSet::new();
+        for path in paths.iter() {
+            dirs.insert(path.to_owned());
+            if let Some(dir) = path.parent() {
+                dirs.insert(dir.to_owned());
+            }
+        }
+
+        for path in paths {
+            // Unmounted drive would otherwise get all its songs deleted,
+            // offline search paths are detected by `check_roots`
+            let offline = Library::get_search_path(config, &path)
+                .map(|p| p.get_path())
+                .is_some_and(|p| {
+                    self.offline.contains(p) || !self.is_reachable(p)
+                });
+            if offline {
+                continue;
+            }
+
+            if path.is_dir() {
+                self.find_dir(config, &path, &mut found, &mut stats);
+            } else if path.is_file() {
+                let searched = Library::get_search_path(config, &path)
+                    .is_some_and(|root| {
+                        Library::is_song_file(config, root, &path)
+                            && path.parent().is_some_and(|p| {
+                                root.search_depth(Library::depth(root, p))
+                            })
+                    });
+                if searched && !Library::is_ignored(config, &path) {
+                    self.find_song(config, &path, &mut found, &mut stats);
+                }
+            } else {
+                self.errors.retain(|e| !e.path.starts_with(&path));
+                for song in self.songs.iter_mut() {
+                    if song.get_path().starts_with(&path)
+                        && !song.get_deleted()
                     {
-                        continue;
-                    }
-                    if let Ok(song) = Song::load(&path) {
-                        let mut exist = false;
-                        for s in &mut self.songs {
-                            if s.get_path() == song.get_path() {
-                                s.set_deleted(false);
-                                exist = true;
-                                break;
-                            }
-                        }
-                        if !exist {
-                            self.songs.push(song);
-                        }
+                        song.set_deleted(true);
+                        stats.removed += 1;
                     }
                 }
             }
         }
+
+        let tags_changed =
+            self.infer_tags(config, Some(&dirs)) | self.merge_artists(config);
+        // Art is loaded in the background by `start_folder_art`
+        self.art_dirs.extend(dirs);
+        if stats != ScanStats::default() || tags_changed {
+            self.catalog = Catalog::new(&self.songs);
+            info!(
+                "Library changed: {} added, {} updated, {} removed",
+                stats.added, stats.updated, stats.removed
+            );
+        }
+    }
+
+    /// Starts checking whether the search paths can be reached in the
+    /// background, checks are done only once in a while. Search paths that
+    /// are empty or can't be read are sent as a message.
+    pub fn start_check_roots(
+        &mut self,
+        config: &Config,
+        sender: UnboundedSender<Msg>,
+    ) {
+        let now = Instant::now();
+        let recent = self
+            .roots_checked
+            .is_some_and(|t| now - t < Library::ROOTS_INTERVAL);
+        if self.checking_roots || recent {
+            return;
+        }
+        self.roots_checked = Some(now);
+        self.checking_roots = true;
+
+        let roots: Vec<PathBuf> = config
+            .get_paths()
+            .iter()
+            .filter(|p| p.get_enabled())
+            .map(|p| p.get_path().to_owned())
+            .collect();
+        thread::spawn(move || {
+            let empty = roots
+                .into_iter()
+                .filter(|r| {
+                    read_dir(r).map_or(true, |mut d| d.next().is_none())
+                })
+                .collect();
+            _ = sender.send(Msg::Lib(LibMsg::RootsChecked(empty)));
+        });
+    }
+
+    /// Marks songs in the search paths that can't be reached offline by
+    /// the check of the search paths, `empty` are search paths that are
+    /// empty or can't be read. Returns search paths that became reachable
+    /// again.
+    pub fn set_roots_checked(
+        &mut self,
+        config: &Config,
+        empty: Vec<PathBuf>,
+    ) -> Vec<PathBuf> {
+        self.checking_roots = false;
+        // Finding checks the search paths itself
+        if self.is_loading() {
+            return Vec::new();
+        }
+
+        let offline = empty
+            .into_iter()
+            .filter(|r| {
+                config.get_paths().iter().any(|p| p.get_path() == r)
+                    && self.has_songs(r)
+            })
+            .collect();
+        self.set_offline(config, offline)
+    }
+
+    /// Removes deleted songs from the library. When `missing` is given,
+    /// only songs deleted for at least the given time are removed. Returns
+    /// IDs of the removed songs.
+    pub fn purge(&mut self, missing: Option<Duration>) -> Vec<SongId> {
+        // Finding would bring the songs back
+        if self.is_loading() {
+            warn!("Can't purge library while it's being updated");
+            return Vec::new();
+        }
+
+        let now = SystemTime::now();
+        let purge = |s: &Song| match (s.get_deleted_since(), missing) {
+            _ if !s.get_deleted() => false,
+            (Some(since), Some(missing)) => {
+                now.duration_since(since).unwrap_or_default() >= missing
+            }
+            _ => true,
+        };
+
+        let removed: Vec<SongId> = self
+            .songs
+            .iter()
+            .filter(|s| purge(s))
+            .map(|s| s.get_id())
+            .collect();
+        if removed.is_empty() {
+            return removed;
+        }
+
+        self.songs.retain(|s| !purge(s));
+        for &id in removed.iter() {
+            self.search.remove(id);
+        }
+        for song in self.songs.iter_mut() {
+            if song
+                .get_duplicate_of()
+                .is_some_and(|d| removed.contains(&d))
+            {
+                song.set_duplicate_of(None);
+            }
+        }
+        self.reindex();
+        self.catalog = Catalog::new(&self.songs);
+        info!("Purged {} deleted songs from the library", removed.len());
+        removed
+    }
+
+    /// Marks the given songs as duplicates of the song to keep. Returns IDs
+    /// of songs that were marked.
+    pub fn merge(&mut self, keep: SongId, others: &[SongId]) -> Vec<SongId> {
+        // Finding would overwrite the changes
+        if self.is_loading() {
+            warn!("Can't merge songs while library is being updated");
+            return Vec::new();
+        }
+        if self.get_song_ref(keep).is_none() {
+            return Vec::new();
+        }
+
+        let mut merged = Vec::new();
+        for song in self.songs.iter_mut() {
+            let id = song.get_id();
+            // Songs merged into the merged songs are moved as well
+            let dup = song.get_duplicate_of();
+            if id != keep
+                && (others.contains(&id)
+                    || dup.is_some_and(|d| others.contains(&d)))
+            {
+                song.set_duplicate_of(Some(keep));
+                merged.push(id);
+            }
+        }
+        self.catalog = Catalog::new(&self.songs);
+        merged
+    }
+
+    /// Searches songs by their name, artist, album, genre and path. Returns
+    /// IDs of the found songs ordered from the best match. Deleted songs
+    /// and duplicates are skipped.
+    pub fn search(&self, query: &str) -> Vec<SongId> {
+        self.search
+            .search(query)
+            .into_iter()
+            .map(|(id, _)| id)
+            .filter(|&id| {
+                self.get_song_ref(id).is_some_and(|s| {
+                    !s.get_deleted() && s.get_duplicate_of().is_none()
+                })
+            })
+            .collect()
+    }
+
+    /// Finds songs matching the query. When the query contains words
+    /// without field, songs are ordered from the best match. Deleted songs
+    /// and duplicates are skipped, unless the query filters by them.
+    pub fn query(&self, query: &Query) -> Vec<SongId> {
+        let ids: Vec<SongId> = match query.get_text() {
+            Some(text) => self
+                .search
+                .search(&text)
+                .into_iter()
+                .map(|(id, _)| id)
+                .collect(),
+            None => self.songs.iter().map(|s| s.get_id()).collect(),
+        };
+
+        let hidden = query.filters_hidden();
+        ids.into_iter()
+            .filter(|&id| {
+                self.get_song_ref(id).is_some_and(|s| {
+                    (hidden
+                        || !s.get_deleted() && s.get_duplicate_of().is_none())
+                        && query.matches(s)
+                })
+            })
+            .collect()
+    }
+
+    /// Gets albums of the library, see [`Catalog::get_albums`]
+    pub fn albums(&self) -> &[Album] {
+        self.catalog.get_albums()
+    }
+
+    /// Gets artists of the library, see [`Catalog::get_artists`]
+    pub fn artists(&self) -> &[Artist] {
+        self.catalog.get_artists()
     }
 
     /// Gets songs from the library
@@ -108,26 +510,76 @@ impl Library {
         &self.songs
     }
 
-    /// Gets song on given ID
-    pub fn get_song(&self, id: usize) -> Song {
-        if let Some(song) = self.songs.get(id) {
+    /// Gets song with given ID
+    pub fn get_song(&self, id: SongId) -> Song {
+        if let Some(song) = self.get_song_ref(id) {
             song.clone()
         } else {
             Song::default()
         }
     }
 
+    /// Gets reference to song with given ID
+    pub fn get_song_ref(&self, id: SongId) -> Option<&Song> {
+        self.ids.get(&id).map(|&i| &self.songs[i])
+    }
+
+    /// Gets IDs of all the songs in the library
+    pub fn get_ids(&self) -> Vec<SongId> {
+        self.songs
+            .iter()
+            .filter(|s| s.get_duplicate_of().is_none())
+            .map(|s| s.get_id())
+            .collect()
+    }
+
     /// Gets number of songs in the library
     pub fn count(&self) -> usize {
         self.songs.len()
     }
 
+    /// Gets statistics of the last library scan
+    pub fn get_stats(&self) -> Option<ScanStats> {
+        self.stats
+    }
+
+    /// Gets song files that failed to load
+    pub fn get_errors(&self) -> &[ScanError] {
+        &self.errors
+    }
+
+    /// Gets progress of the running scan, None when not finding songs
+    pub fn get_progress(&self) -> Option<ScanProgress> {
+        self.progress
+    }
+
+    /// Sets progress of the running scan
+    pub fn set_progress(&mut self, progress: ScanProgress) {
+        if self.is_loading() {
+            self.progress = Some(progress);
+        }
+    }
+
+    /// Checks whether songs are being found
+    pub fn is_loading(&self) -> bool {
+        self.load_process.is_some()
+    }
+
     /// Starts finding new songs
     pub fn start_find(&mut self, conf: &Config, sender: UnboundedSender<Msg>) {
+        if self.is_loading() || self.is_organizing() {
+            return;
+        }
+
         let mut lib = self.clone();
         let config = conf.clone();
+        let scan = Scan::new(sender.clone());
+        self.scan = Some(scan.clone());
+        self.progress = Some(ScanProgress::default());
+
         let load = thread::spawn(move || {
-            lib.find(&config);
+            lib.find(&config, &scan);
+            scan.finish();
 
             _ = sender.send(Msg::Lib(LibMsg::LoadEnded));
 
@@ -136,32 +588,1299 @@ impl Library {
         self.load_process = Some(load);
     }
 
+    /// Cancels finding new songs, songs found so far are discarded
+    pub fn cancel_find(&self) {
+        if let Some(scan) = &self.scan {
+            scan.cancel();
+        }
+    }
+
     /// Ends finding new songs
-    pub fn end_find(&mut self) {
+    pub fn end_find(&mut self, config: &Config) {
         if let Some(process) = self.load_process.take() {
-            self.songs = process.join().unwrap().songs;
+            let lib = process.join().unwrap();
+            let cancelled = self.scan.take().is_some_and(|s| s.is_cancelled());
+            if !cancelled {
+                self.songs = lib.songs;
+                self.next_id = lib.next_id;
+                self.index = lib.index;
+                self.ids = lib.ids;
+                self.stats = lib.stats;
+                self.offline = lib.offline;
+                self.errors = lib.errors;
+                self.folder_art = lib.folder_art;
+                self.search = lib.search;
+                self.catalog = Catalog::new(&self.songs);
+            }
         }
+        self.progress = None;
+
+        for (id, loudness) in std::mem::take(&mut self.pending_loudness) {
+            if let Some(song) = self.get_song_mut(id) {
+                song.set_loudness(Some(loudness));
+            }
+        }
+        let arts = std::mem::take(&mut self.pending_art);
+        if !arts.is_empty() {
+            self.set_folder_art(config, arts);
+        }
+
+        if !self.pending.is_empty() {
+            let pending = std::mem::take(&mut self.pending);
+            self.update_paths(config, pending);
+        }
+    }
+
+    /// Starts writing the tag changes to the files of the songs with the
+    /// given IDs in the background, written songs are reloaded when the
+    /// writing ends. Returns error when the changes are invalid.
+    pub fn edit_tags(
+        &self,
+        ids: &[SongId],
+        edit: &TagEdit,
+        sender: UnboundedSender<Msg>,
+    ) -> Result<()> {
+        edit.check()?;
+        let edits = ids
+            .iter()
+            .filter_map(|&id| self.get_song_ref(id))
+            .map(|s| (s.get_path().to_owned(), edit.clone()))
+            .collect();
+        Library::write_tags(edits, sender);
+        Ok(())
+    }
+
+    /// Starts writing the values inferred from the song paths to the tags
+    /// of the songs with the given IDs in the background
+    pub fn write_inferred(
+        &self,
+        ids: &[SongId],
+        sender: UnboundedSender<Msg>,
+    ) {
+        self.write_fields(ids, Song::get_inferred, sender)
     }
 
-    pub fn handle_msg(
+    /// Starts writing the normalized values to the tags of the songs with
+    /// the given IDs in the background
+    pub fn write_normalized(
+        &self,
+        ids: &[SongId],
+        sender: UnboundedSender<Msg>,
+    ) {
+        self.write_fields(ids, Song::get_normalized, sender)
+    }
+
+    /// Marks all songs to be loaded again from their files by the next
+    /// library update, so that changed normalization applies to them
+    pub fn reload(&mut self) {
+        for song in self.songs.iter_mut() {
+            song.reset_fingerprint();
+        }
+    }
+
+    /// Marks the songs on the given paths to be loaded again by the next
+    /// update, tags can change without changing size and modification time
+    /// of the file
+    pub fn reset_fingerprints(&mut self, paths: &[PathBuf]) {
+        for path in paths.iter() {
+            if let Some(&i) = self.index.get(path) {
+                self.songs[i].reset_fingerprint();
+            }
+        }
+    }
+
+    /// Plans moving of the song files to paths given by the template,
+    /// relative to their search paths. Songs already on their path and
+    /// songs that can't be reached aren't moved.
+    pub fn plan_organize(
+        &self,
+        config: &Config,
+        template: &str,
+    ) -> Result<OrganizePlan> {
+        let parsed = PathTemplate::parse(template)?;
+        let mut moves: Vec<OrganizeMove> = self
+            .songs
+            .iter()
+            .filter(|s| !s.get_deleted() && !s.get_offline())
+            .filter_map(|s| {
+                let root = Library::get_search_path(config, s.get_path())?;
+                let to = root.get_path().join(parsed.render(s));
+                // Song moved where it isn't searched would be deleted by
+                // the next library update
+                let conflict = (!Library::is_searched(config, root, &to))
+                    .then_some(OrganizeConflict::OutOfScope);
+                (&to != s.get_path()).then(|| OrganizeMove {
+                    id: s.get_id(),
+                    from: s.get_path().to_owned(),
+                    to,
+                    conflict,
+                })
+            })
+            .collect();
+        OrganizeMove::find_conflicts(&mut moves);
+        Ok(OrganizePlan {
+            template: template.to_owned(),
+            moves,
+        })
+    }
+
+    /// Starts moving the song files as planned in the background, moves
+    /// with conflicts are skipped. Songs keep their IDs, so playlists
+    /// aren't affected.
+    pub fn start_organize(
+        &mut self,
+        config: &Config,
+        plan: &OrganizePlan,
+        sender: UnboundedSender<Msg>,
+    ) -> Result<()> {
+        let moves = self.check_organize(config, plan)?;
+        info!("Organizing library: moving {} songs", moves.len());
+        self.organizing =
+            Some(Organizing::start(config.clone(), moves, sender));
+        Ok(())
+    }
+
+    /// Moves the song files as planned, moves with conflicts are skipped.
+    /// Returns number of moved songs.
+    pub fn organize(
+        &mut self,
+        config: &Config,
+        plan: &OrganizePlan,
+    ) -> Result<usize> {
+        let moves = self.check_organize(config, plan)?;
+        let organizing = Organizing::new(moves.len());
+        let mut moved = Vec::new();
+        organizing.run(config, &moves, |m| moved.push(m.clone()));
+
+        for m in moved.iter() {
+            self.set_moved(m);
+        }
+        self.organizing = Some(organizing);
+        let moved = self.end_organize(config);
+        let dirs = std::mem::take(&mut self.art_dirs);
+        self.find_folder_art(config, Some(&dirs));
+        Ok(moved)
+    }
+
+    /// Updates path of the song moved by organizing
+    pub fn set_moved(&mut self, m: &OrganizeMove) {
+        let i = match self.index.get(&m.from) {
+            Some(&i) if self.songs[i].get_id() == m.id => i,
+            _ => return,
+        };
+        self.index.remove(&m.from);
+        self.index.insert(m.to.clone(), i);
+        self.songs[i].set_path(m.to.clone());
+        self.search.insert(&self.songs[i]);
+        self.organized.extend(m.from.parent().map(|d| d.to_owned()));
+        self.organized.extend(m.to.parent().map(|d| d.to_owned()));
+    }
+
+    /// Ends organizing of the library, updates art and inferred tags of
+    /// the songs in the changed directories. Returns number of moved
+    /// songs.
+    pub fn end_organize(&mut self, config: &Config) -> usize {
+        let moved = self.organizing.take().map_or(0, |o| o.get_moved());
+        let dirs = std::mem::take(&mut self.organized);
+        self.infer_tags(config, Some(&dirs));
+        self.catalog = Catalog::new(&self.songs);
+        self.art_dirs.extend(dirs);
+
+        if !self.pending.is_empty() {
+            let pending = std::mem::take(&mut self.pending);
+            self.update_paths(config, pending);
+        }
+        moved
+    }
+
+    /// Gets progress of organizing, number of tried moves and number of
+    /// all planned moves. None when the library isn't being organized.
+    pub fn get_organize_progress(&self) -> Option<(usize, usize)> {
+        self.organizing.as_ref().map(|o| o.get_progress())
+    }
+
+    /// Checks whether the song files are being moved
+    pub fn is_organizing(&self) -> bool {
+        self.organizing.is_some()
+    }
+
+    /// Checks that the library wasn't changed since the moves were
+    /// planned, planning again must give the same moves. Returns the moves
+    /// without conflicts.
+    fn check_organize(
+        &self,
+        config: &Config,
+        plan: &OrganizePlan,
+    ) -> Result<Vec<OrganizeMove>> {
+        // Finding would bring back the old paths
+        if self.is_loading() || self.is_organizing() {
+            return Err(Report::msg(
+                "Can't organize library while it's being updated",
+            ));
+        }
+        if &self.plan_organize(config, &plan.template)? != plan {
+            return Err(Report::msg(
+                "Library changed since the preview, preview it again",
+            ));
+        }
+        Ok(plan
+            .moves
+            .iter()
+            .filter(|m| m.conflict.is_none())
+            .cloned()
+            .collect())
+    }
+
+    /// Starts loudness analysis of songs that don't have ReplayGain in
+    /// the background, does nothing when it's already running
+    pub fn start_analysis(
         &mut self,
         config: &Config,
         sender: UnboundedSender<Msg>,
-        msg: LibMsg,
     ) {
+        if self.analysis.is_some() {
+            return;
+        }
+
+        let songs: Vec<(SongId, PathBuf)> = self
+            .songs
+            .iter()
+            .filter(|s| {
+                s.needs_analysis() && !self.not_analyzed.contains(&s.get_id())
+            })
+            .map(|s| (s.get_id(), s.get_path().to_owned()))
+            .collect();
+        if songs.is_empty() {
+            return;
+        }
+
+        info!("Analyzing loudness of {} songs", songs.len());
+        self.analysis = Some(Analysis::start(
+            songs,
+            config.get_analysis_pause(),
+            config.get_write_loudness_tags(),
+            sender,
+        ));
+    }
+
+    /// Sets the song that is playing, the loudness analysis doesn't write
+    /// to its file
+    pub fn set_playing(&self, id: Option<SongId>) {
+        if let Some(analysis) = &self.analysis {
+            analysis.set_playing(id);
+        }
+    }
+
+    /// Cancels the loudness analysis
+    pub fn cancel_analysis(&self) {
+        if let Some(analysis) = &self.analysis {
+            analysis.cancel();
+        }
+    }
+
+    /// Ends the loudness analysis
+    pub fn end_analysis(&mut self) {
+        self.analysis = None;
+    }
+
+    /// Gets progress of the loudness analysis, number of analyzed songs and
+    /// number of all songs to analyze. None when the analysis isn't running.
+    pub fn get_analysis_progress(&self) -> Option<(usize, usize)> {
+        self.analysis
+            .as_ref()
+            .filter(|a| !a.is_cancelled())
+            .map(|a| a.get_progress())
+    }
+
+    /// Sets measured loudness of the song, None when the song couldn't be
+    /// analyzed
+    pub fn set_loudness(&mut self, id: SongId, loudness: Option<Loudness>) {
+        let loudness = match loudness {
+            Some(loudness) => loudness,
+            None => {
+                self.not_analyzed.insert(id);
+                return;
+            }
+        };
+        if self.is_loading() {
+            self.pending_loudness.push((id, loudness));
+        }
+        if let Some(song) = self.get_song_mut(id) {
+            song.set_loudness(Some(loudness));
+        }
+    }
+}
+
+//>=========================================================================<//
+//                          Library message handling                         //
+//>=========================================================================<//
+impl BumpApp {
+    /// Handles library update
+    pub fn lib_update(&mut self, msg: LibMsg) {
         match msg {
-            LibMsg::LoadStart => self.start_find(config, sender),
-            LibMsg::LoadEnded => self.end_find(),
+            LibMsg::LoadStart => {
+                self.library.start_find(&self.config, self.sender.clone())
+            }
+            LibMsg::LoadEnded => {
+                self.library.end_find(&self.config);
+                self.auto_purge();
+                self.update_shown();
+                self.auto_analyze();
+            }
+            LibMsg::LoadProgress(progress) => {
+                self.library.set_progress(progress)
+            }
+            LibMsg::LoadCancel => self.library.cancel_find(),
+            LibMsg::Changed(paths) => {
+                self.library.update_paths(&self.config, paths);
+                self.update_shown();
+            }
+            LibMsg::Purge => {
+                self.purge(None);
+                self.update_shown();
+            }
+            LibMsg::FindDuplicates(mode) => self.find_duplicates(mode),
+            LibMsg::Duplicates(groups) => {
+                self.settings.dup_search = false;
+                self.settings.duplicates = Some(groups);
+            }
+            LibMsg::Merge(keep, others) => {
+                self.merge(keep, others);
+                self.update_shown();
+            }
+            LibMsg::Filter(filter) => {
+                self.filter = filter;
+                self.update_shown();
+            }
+            LibMsg::PlaySmart(name) => self.play_smart(&name),
+            LibMsg::Sort(field) => {
+                self.gui.sort_library(field);
+                self.update_shown();
+            }
+            LibMsg::Organize => {
+                if let Some(plan) = self.settings.organize.take() {
+                    self.organize(&plan);
+                }
+            }
+            LibMsg::OrganizePlan(plan) => self.organize(&plan),
+            LibMsg::Moved(m) => self.library.set_moved(&m),
+            LibMsg::RootsChecked(empty) => self.roots_checked(empty),
+            LibMsg::OrganizeEnded => {
+                let moved = self.library.end_organize(&self.config);
+                self.settings.organize_result =
+                    Some(format!("Moved {moved} songs"));
+                self.update_shown();
+            }
+            LibMsg::Select(id, selected) => {
+                if selected {
+                    self.selected.insert(id);
+                } else {
+                    self.selected.remove(&id);
+                }
+            }
+            LibMsg::ClearSelection => self.selected.clear(),
+            LibMsg::WriteNormalized => {
+                let ids: Vec<SongId> = self.selected.iter().copied().collect();
+                self.library.write_normalized(&ids, self.sender.clone());
+                self.selected.clear();
+            }
+            LibMsg::Reload => {
+                // Update in progress would bring back the old songs
+                if !self.library.is_loading() {
+                    self.library.reload();
+                    self.library.start_find(&self.config, self.sender.clone());
+                }
+            }
+            LibMsg::WriteInferred => {
+                let ids: Vec<SongId> = self.selected.iter().copied().collect();
+                self.library.write_inferred(&ids, self.sender.clone());
+                self.selected.clear();
+            }
+            LibMsg::TagsWritten(paths, failed) => {
+                self.library.reset_fingerprints(&paths);
+                self.library.update_paths(&self.config, paths);
+                self.tags_written(failed);
+                self.update_shown();
+            }
+            LibMsg::AnalysisStart => self
+                .library
+                .start_analysis(&self.config, self.sender.clone()),
+            LibMsg::AnalysisCancel => self.library.cancel_analysis(),
+            LibMsg::Analyzed(id, loudness) => {
+                self.library.set_loudness(id, loudness);
+                if self.player.get_current_id() == Some(id) {
+                    self.player.apply_gain(&self.library);
+                }
+            }
+            LibMsg::AnalysisEnded => self.library.end_analysis(),
+            LibMsg::FolderArt(arts) => {
+                if self.library.set_folder_art(&self.config, arts) {
+                    self.update_shown();
+                }
+            }
+        }
+        self.library
+            .start_folder_art(&self.config, self.sender.clone());
+    }
+
+    /// Starts loudness analysis when it's enabled in config, songs are
+    /// analyzed only after they're found
+    pub fn auto_analyze(&mut self) {
+        if self.config.get_auto_analyze() && !self.library.is_loading() {
+            self.library
+                .start_analysis(&self.config, self.sender.clone());
         }
     }
+
+    /// Finds songs shown in the library, songs matching the library filter
+    /// sorted by the library sort order. The filter is parsed as [`Query`].
+    pub fn update_shown(&mut self) {
+        self.filter_error = None;
+        let ids = match Query::parse(&self.filter) {
+            Ok(query) => self.library.query(&query),
+            Err(e) => {
+                self.filter_error = Some(e.to_string());
+                Vec::new()
+            }
+        };
+        let songs: Vec<&Song> = ids
+            .iter()
+            .filter_map(|&id| self.library.get_song_ref(id))
+            .collect();
+        self.shown = self.gui.get_library_sort().sort(&songs);
+    }
+
+    /// Replaces the playlist with songs of the smart playlist with the
+    /// given name and plays it
+    pub fn play_smart(&mut self, name: &str) {
+        let query = match self.config.get_smart_playlist(name) {
+            Some(playlist) => Query::parse(playlist.get_query()),
+            None => {
+                warn!("Smart playlist '{name}' doesn't exist");
+                return;
+            }
+        };
+        match query {
+            Ok(query) => {
+                let ids = self.library.query(&query);
+                let songs: Vec<&Song> = ids
+                    .iter()
+                    .filter_map(|&id| self.library.get_song_ref(id))
+                    .collect();
+                let ids = self.gui.get_library_sort().sort(&songs);
+                self.player.play_songs(&self.library, ids);
+                self.gui.clear_playlist_sort();
+                self.hard_pause = None;
+            }
+            Err(e) => error!("Invalid query of smart playlist '{name}': {e}"),
+        }
+    }
+
+    /// Starts checking whether the search paths can be reached
+    pub fn check_roots(&mut self) {
+        // Finding checks the search paths itself
+        if !self.library.is_loading() {
+            self.library
+                .start_check_roots(&self.config, self.sender.clone());
+        }
+    }
+
+    /// Updates offline search paths by their check, when some came back,
+    /// library is updated and the search paths are watched again
+    fn roots_checked(&mut self, empty: Vec<PathBuf>) {
+        if !self
+            .library
+            .set_roots_checked(&self.config, empty)
+            .is_empty()
+        {
+            self.library.start_find(&self.config, self.sender.clone());
+            self.enable_watcher(self.config.get_watch_paths());
+        }
+    }
+
+    /// Starts moving song files as planned, the plan is refused when the
+    /// library changed since it was made
+    pub fn organize(&mut self, plan: &OrganizePlan) {
+        self.settings.organize = None;
+        self.settings.organize_result = match self.library.start_organize(
+            &self.config,
+            plan,
+            self.sender.clone(),
+        ) {
+            Ok(_) => None,
+            Err(e) => {
+                error!("Failed to organize library: {e}");
+                Some(e.to_string())
+            }
+        };
+    }
+
+    /// Purges songs deleted for longer than set in config
+    pub fn auto_purge(&mut self) {
+        if let Some(days) = self.config.get_auto_purge() {
+            self.purge(Some(Duration::from_secs(days as u64 * 24 * 60 * 60)));
+        }
+    }
+
+    /// Finds duplicate songs in the background, found duplicates are sent
+    /// as a message
+    fn find_duplicates(&mut self, mode: DuplicateMode) {
+        if self.settings.dup_search {
+            return;
+        }
+        self.settings.dup_search = true;
+
+        let lib = self.library.clone();
+        let sender = self.sender.clone();
+        thread::spawn(move || {
+            let groups = lib.find_duplicates(mode);
+            _ = sender.send(Msg::Lib(LibMsg::Duplicates(groups)));
+        });
+    }
+
+    /// Keeps the given song and marks the other songs as its duplicates,
+    /// the duplicates are replaced by the song in the playlist
+    fn merge(&mut self, keep: SongId, others: Vec<SongId>) {
+        let merged = self.library.merge(keep, &others);
+        if !merged.is_empty() {
+            self.player.replace_songs(&merged, keep);
+        }
+        if let Some(groups) = self.settings.duplicates.as_mut() {
+            groups.retain(|g| !g.contains(&keep));
+        }
+    }
+
+    /// Purges deleted songs from the library and removes them from the
+    /// playlist
+    fn purge(&mut self, missing: Option<Duration>) {
+        let removed = self.library.purge(missing);
+        if !removed.is_empty() {
+            self.player.remove_songs(&removed);
+        }
+    }
+}
+
+//>=========================================================================<//
+//                             Private functions                             //
+//>=========================================================================<//
+impl Library {
+    /// Gets mutable reference to song with the given ID
+    fn get_song_mut(&mut self, id: SongId) -> Option<&mut Song> {
+        self.ids.get(&id).map(|&i| &mut self.songs[i])
+    }
+
+    /// Loads song on given path if it's new or its file changed, marks it
+    /// as found and updates the scan statistics
+    fn find_song(
+        &mut self,
+        config: &Config,
+        path: &Path,
+        found: &mut Vec<bool>,
+        stats: &mut ScanStats,
+    ) {
+        let res = match self.check_song(path, found, stats) {
+            Ok(true) => Song::load(
+                path,
+                &ArtCache::new(config),
+                &TagNormalizer::new(config),
+            )
+            .map(|song| self.add_song(song, found, stats)),
+            Ok(false) => Ok(()),
+            Err(e) => Err(e),
+        };
+
+        self.errors.retain(|e| e.path != path);
+        if let Err(e) = res {
+            self.errors.push(ScanError::new(path.to_owned(), e));
+        }
+    }
+
+    /// Checks song file on given path against the library. When song is
+    /// unchanged, it's marked as found. Returns whether the song needs to
+    /// be loaded, error when the file can't be accessed.
+    fn check_song(
+        &mut self,
+        path: &Path,
+        found: &mut [bool],
+        stats: &mut ScanStats,
+    ) -> Result<bool> {
+        let fingerprint = Fingerprint::load(path)?;
+
+        let i = match self.index.get(path) {
+            Some(&i) => i,
+            None => return Ok(true),
+        };
+
+        if found[i] {
+            return Ok(false);
+        }
+
+        let song = &mut self.songs[i];
+        if song.get_fingerprint() != &fingerprint {
+            return Ok(true);
+        }
+
+        found[i] = true;
+        if song.get_deleted() {
+            song.set_deleted(false);
+            stats.added += 1;
+        }
+        Ok(false)
+    }
+
+    /// Adds loaded song to the library, replacing song on the same path
+    fn add_song(
+        &mut self,
+        mut song: Song,
+        found: &mut Vec<bool>,
+        stats: &mut ScanStats,
+    ) {
+        match self.index.get(song.get_path()) {
+            Some(&i) if found[i] => {}
+            Some(&i) => {
+                if self.songs[i].get_deleted() {
+                    stats.added += 1;
+                } else {
+                    stats.updated += 1;
+                }
+                song.set_id(self.songs[i].get_id());
+                song.set_duplicate_of(self.songs[i].get_duplicate_of());
+                song.set_added(self.songs[i].get_added());
+                // Changed tags don't change the loudness of the audio
+                if song.get_length() == self.songs[i].get_length() {
+                    song.set_loudness(self.songs[i].get_loudness());
+                }
+                self.search.insert(&song);
+                self.songs[i] = song;
+                found[i] = true;
+            }
+            None => {
+                song.set_id(self.new_id());
+                song.set_added(Some(SystemTime::now()));
+                self.search.insert(&song);
+                self.index
+                    .insert(song.get_path().to_owned(), self.songs.len());
+                self.ids.insert(song.get_id(), self.songs.len());
+                self.songs.push(song);
+                found.push(true);
+                stats.added += 1;
+            }
+        }
+    }
+
+    /// Finds art images in the directories of the songs and sets them as
+    /// folder art of the songs. When `dirs` is given, only songs in those
+    /// directories are updated. Returns whether art of any song changed.
+    fn find_folder_art(
+        &mut self,
+        config: &Config,
+        dirs: Option<&HashSet<PathBuf>>,
+    ) -> bool {
+        let song_dirs = self.song_dirs(dirs);
+        // Art of directories that no longer contain songs is forgotten
+        if dirs.is_none() {
+            self.folder_art.retain(|dir, _| song_dirs.contains(dir));
+        }
+        let arts =
+            Library::load_folder_art(config, song_dirs, &self.folder_art);
+        self.set_folder_art(config, arts)
+    }
+
+    /// Starts loading art images of the directories changed by the last
+    /// updates in the background, loaded art is sent as a message
+    pub fn start_folder_art(
+        &mut self,
+        config: &Config,
+        sender: UnboundedSender<Msg>,
+    ) {
+        if self.art_dirs.is_empty() {
+            return;
+        }
+        let dirs = std::mem::take(&mut self.art_dirs);
+        let dirs = self.song_dirs(Some(&dirs));
+        let known: HashMap<PathBuf, FolderArt> = dirs
+            .iter()
+            .filter_map(|d| {
+                Some((d.to_owned(), self.folder_art.get(d)?.clone()))
+            })
+            .collect();
+
+        let config = config.clone();
+        thread::spawn(move || {
+            let arts = Library::load_folder_art(&config, dirs, &known);
+            _ = sender.send(Msg::Lib(LibMsg::FolderArt(arts)));
+        });
+    }
+
+    /// Sets loaded art images of the directories as folder art of their
+    /// songs. Returns whether art of any song changed.
+    pub fn set_folder_art(
+        &mut self,
+        config: &Config,
+        arts: Vec<(PathBuf, Option<FolderArt>)>,
+    ) -> bool {
+        if self.is_loading() {
+            self.pending_art.extend(arts.iter().cloned());
+        }
+        let mut dirs = HashSet::new();
+        for (dir, art) in arts {
+            match art {
+                Some(art) => _ = self.folder_art.insert(dir.clone(), art),
+                None => _ = self.folder_art.remove(&dir),
+            }
+            dirs.insert(dir);
+        }
+        let changed = self.apply_folder_art(config, Some(&dirs));
+        if changed {
+            self.catalog = Catalog::new(&self.songs);
+        }
+        changed
+    }
+
+    /// Sets folder art of the songs again, so that changed preference of
+    /// folder art applies
+    pub fn update_folder_art(&mut self, config: &Config) {
+        if self.apply_folder_art(config, None) {
+            self.catalog = Catalog::new(&self.songs);
+        }
+    }
+
+    /// Sets known art of their directories to the songs, when `dirs` is
+    /// given, only to songs in the given directories. Returns whether art
+    /// of any song changed.
+    fn apply_folder_art(
+        &mut self,
+        config: &Config,
+        dirs: Option<&HashSet<PathBuf>>,
+    ) -> bool {
+        let mut changed = false;
+        for song in self.songs.iter_mut() {
+            if song.get_deleted() || song.get_offline() {
+                continue;
+            }
+            let dir = match song.get_path().parent() {
+                Some(dir) if dirs.is_none_or(|d| d.contains(dir)) => dir,
+                _ => continue,
+            };
+
+            let art = self.folder_art.get(dir).map(|a| a.get_id());
+            let old = song.get_art();
+            song.set_folder_art(art, config.get_folder_art_first());
+            changed |= song.get_art() != old;
+        }
+        changed
+    }
+
+    /// Gets directories of the songs that can be played, when `dirs` is
+    /// given, only the given directories
+    fn song_dirs(&self, dirs: Option<&HashSet<PathBuf>>) -> HashSet<PathBuf> {
+        self.songs
+            .iter()
+            .filter(|s| !s.get_deleted() && !s.get_offline())
+            .filter_map(|s| s.get_path().parent())
+            .filter(|dir| dirs.is_none_or(|d| d.contains(*dir)))
+            .map(|dir| dir.to_owned())
+            .collect()
+    }
+
+    /// Writes the values of the given fields of the songs with the given
+    /// IDs to their tags
+    fn write_fields(
+        &self,
+        ids: &[SongId],
+        fields: fn(&Song) -> &BTreeSet<TagField>,
+        sender: UnboundedSender<Msg>,
+    ) {
+        let edits = ids
+            .iter()
+            .filter_map(|&id| self.get_song_ref(id))
+            .map(|song| {
+                let edit = TagEdit {
+                    fields: fields(song)
+                        .iter()
+                        .map(|&f| (f, f.get(song)))
+                        .collect(),
+                    ..Default::default()
+                };
+                (song.get_path().to_owned(), edit)
+            })
+            .filter(|(_, edit)| !edit.is_empty())
+            .collect();
+        Library::write_tags(edits, sender);
+    }
+
+    /// Writes the tag changes to the song files in a background thread.
+    /// Paths of the written songs and number of songs that failed to be
+    /// written are sent as a message, so that the songs are reloaded.
+    fn write_tags(
+        edits: Vec<(PathBuf, TagEdit)>,
+        sender: UnboundedSender<Msg>,
+    ) {
+        thread::spawn(move || {
+            let mut paths = Vec::new();
+            let mut failed = 0;
+            for (path, edit) in edits {
+                match edit.write(&path) {
+                    Ok(_) => paths.push(path),
+                    Err(e) => {
+                        error!(
+                            "Failed to write tags of {}: {e}",
+                            path.display()
+                        );
+                        failed += 1;
+                    }
+                }
+            }
+            info!("Tags of {} songs written", paths.len());
+            _ = sender.send(Msg::Lib(LibMsg::TagsWritten(paths, failed)));
+        });
+    }
+
+    /// Merges artists that differ only by case, songs get the most common
+    /// spelling of their artist and album artist. Values inferred from
+    /// paths aren't changed. Returns whether any song changed.
+    fn merge_artists(&mut self, config: &Config) -> bool {
+        if !config.get_merge_artists() {
+            return false;
+        }
+        let fields = [TagField::Artist, TagField::AlbumArtist];
+
+        let mut counts: HashMap<String, HashMap<String, usize>> =
+            HashMap::new();
+        for song in self.songs.iter().filter(|s| !s.get_deleted()) {
+            for field in fields {
+                let name = field.get(song);
+                if !name.is_empty() {
+                    *counts
+                        .entry(name.to_lowercase())
+                        .or_default()
+                        .entry(name)
+                        .or_default() += 1;
+                }
+            }
+        }
+        // Ties are resolved by the name, so the result doesn't change
+        let names: HashMap<String, String> = counts
+            .into_iter()
+            .filter(|(_, names)| names.len() > 1)
+            .filter_map(|(key, names)| {
+                let name = names
+                    .into_iter()
+                    .max_by(|(a, ac), (b, bc)| ac.cmp(bc).then(b.cmp(a)))?;
+                Some((key, name.0))
+            })
+            .collect();
+        if names.is_empty() {
+            return false;
+        }
+
+        let mut changed = false;
+        for song in self.songs.iter_mut().filter(|s| !s.get_deleted()) {
+            let mut song_changed = false;
+            for field in fields {
+                let name = field.get(song);
+                let merged = match names.get(&name.to_lowercase()) {
+                    Some(merged) if *merged != name => merged,
+                    _ => continue,
+                };
+                if !song.is_inferred(field) {
+                    song.set_normalized(field, merged);
+                    song_changed = true;
+                }
+            }
+            if song_changed {
+                self.search.insert(song);
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Infers tags missing in the song files from their paths by the tag
+    /// patterns from config. When `dirs` is given, only songs in those
+    /// directories are updated. Returns whether tags of any song changed.
+    fn infer_tags(
+        &mut self,
+        config: &Config,
+        dirs: Option<&HashSet<PathBuf>>,
+    ) -> bool {
+        let patterns: Vec<TagPattern> = config
+            .get_tag_patterns()
+            .iter()
+            .filter_map(|p| match TagPattern::parse(p) {
+                Ok(pattern) => Some(pattern),
+                Err(e) => {
+                    warn!("Invalid tag pattern '{p}': {e}");
+                    None
+                }
+            })
+            .collect();
+        let mut changed = false;
+
+        for i in 0..self.songs.len() {
+            let song = &self.songs[i];
+            if song.get_deleted()
+                || !song
+                    .get_path()
+                    .parent()
+                    .is_some_and(|d| dirs.is_none_or(|dirs| dirs.contains(d)))
+            {
+                continue;
+            }
+            // Patterns match only the path inside the search path
+            let path = song.get_path().to_owned();
+            let path = match Library::get_search_path(config, &path) {
+                Some(root) => {
+                    path.strip_prefix(root.get_path()).unwrap_or(&path)
+                }
+                None => path.file_name().map(Path::new).unwrap_or(&path),
+            };
+
+            if self.songs[i].infer_tags(path, &patterns) {
+                self.search.insert(&self.songs[i]);
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Loads art images of the given directories, art that didn't change
+    /// since it was loaded is kept. None is given for directories without
+    /// art.
+    fn load_folder_art(
+        config: &Config,
+        dirs: HashSet<PathBuf>,
+        known: &HashMap<PathBuf, FolderArt>,
+    ) -> Vec<(PathBuf, Option<FolderArt>)> {
+        let cache = ArtCache::new(config);
+        dirs.into_iter()
+            .map(|dir| {
+                let path =
+                    match FolderArt::find_file(&dir, config.get_art_files()) {
+                        Some(path) => path,
+                        None => return (dir, None),
+                    };
+                if let Some(art) = known.get(&dir) {
+                    if art.is_current(&path) {
+                        return (dir, Some(art.clone()));
+                    }
+                }
+
+                match FolderArt::load(path, &cache) {
+                    Ok(art) => (dir, Some(art)),
+                    Err(e) => {
+                        warn!("Failed to load art in {}: {e}", dir.display());
+                        (dir, None)
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Finds songs in the given directory, respecting recursive search
+    fn find_dir(
+        &mut self,
+        config: &Config,
+        dir: &Path,
+        found: &mut Vec<bool>,
+        stats: &mut ScanStats,
+    ) {
+        let mut files = Vec::new();
+        Library::walk_dir(
+            config,
+            dir,
+            &mut files,
+            &mut HashSet::new(),
+            &Scan::default(),
+        );
+        for path in files {
+            self.find_song(config, &path, found, stats);
+        }
+    }
+
+    /// Walks the given directory, respecting search path settings and ignore
+    /// rules, and collects paths to all the song files. Files and
+    /// directories already in `seen` are skipped.
+    fn walk_dir(
+        config: &Config,
+        dir: &Path,
+        files: &mut Vec<PathBuf>,
+        seen: &mut HashSet<FileId>,
+        scan: &Scan,
+    ) {
+        let root = match Library::get_search_path(config, dir) {
+            Some(root) => root,
+            None => return,
+        };
+        let rules = match IgnoreRules::for_dir(config, dir) {
+            Some(rules) => rules,
+            None => return,
+        };
+
+        let depth = Library::depth(root, dir);
+        if !root.search_depth(depth) {
+            return;
+        }
+
+        // Symlinks are followed only after everything else is walked, so
+        // songs reached by more than one path keep their real path
+        let mut paths = vec![(dir.to_owned(), depth, rules)];
+        let mut links = Vec::new();
+        let mut linked_files = Vec::new();
+        while let Some((dir, depth, rules)) =
+            paths.pop().or_else(|| links.pop())
+        {
+            if scan.is_cancelled() {
+                return;
+            }
+            // Directory was already walked, it's symlink cycle or nested
+            // search path
+            if FileId::of(&dir).is_ok_and(|id| !seen.insert(id)) {
+                continue;
+            }
+            let mut entries: Vec<_> = match read_dir(&dir) {
+                Ok(dir) => dir.flatten().collect(),
+                Err(_) => continue,
+            };
+            entries.sort_by_key(|e| e.file_name());
+
+            let count = files.len();
+            for entry in entries {
+                let path = entry.path();
+                let link = entry.file_type().is_ok_and(|t| t.is_symlink());
+                if link && !config.get_follow_symlinks() {
+                    continue;
+                }
+
+                if path.is_dir() {
+                    if depth < config.get_max_search_depth()
+                        && root.search_depth(depth + 1)
+                        && !rules.is_ignored(&path, true)
+                    {
+                        let rules = rules.enter(&path);
+                        if link {
+                            links.push((path, depth + 1, rules));
+                        } else {
+                            paths.push((path, depth + 1, rules));
+                        }
+                    }
+                } else if !rules.is_ignored(&path, false)
+                    && Library::is_song_file(config, root, &path)
+                {
+                    if link {
+                        linked_files.push(path);
+                    } else if Library::first_seen(seen, &path) {
+                        files.push(path);
+                    }
+                }
+            }
+            scan.discovered(files.len() - count);
+        }
+
+        let count = files.len();
+        for path in linked_files {
+            if Library::first_seen(seen, &path) {
+                files.push(path);
+            }
+        }
+        scan.discovered(files.len() - count);
+    }
+
+    /// Checks whether file on the given path wasn't reached before by
+    /// other path and marks it as seen
+    fn first_seen(seen: &mut HashSet<FileId>, path: &Path) -> bool {
+        match FileId::of(path) {
+            Ok(id) => seen.insert(id),
+            Err(_) => true,
+        }
+    }
+
+    /// Gets enabled search path containing the given path
+    fn get_search_path<'a>(
+        config: &'a Config,
+        path: &Path,
+    ) -> Option<&'a SearchPath> {
+        config.get_search_path(path).filter(|p| p.get_enabled())
+    }
+
+    /// Gets depth of the given directory in the search path
+    fn depth(root: &SearchPath, dir: &Path) -> usize {
+        dir.strip_prefix(root.get_path())
+            .map_or(0, |p| p.components().count())
+    }
+
+    /// Checks whether file on given path has one of the song extensions of
+    /// the search path. When enabled, other files are probed for audio.
+    fn is_song_file(config: &Config, root: &SearchPath, path: &Path) -> bool {
+        let ext = path.extension().map(|e| e.to_string_lossy());
+        let has_ext = ext.is_some_and(|ext| {
+            root.extensions(config.get_extensions())
+                .iter()
+                .any(|e| e == ext.as_ref())
+        });
+        has_ext || (config.get_probe_content() && Song::probe(path))
+    }
+
+    /// Creates new unique song ID
+    fn new_id(&mut self) -> SongId {
+        self.next_id = self.next_id.max(1);
+        let id = SongId::new(self.next_id);
+        self.next_id += 1;
+        id
+    }
+
+    /// Upgrades library saved by older version
+    fn upgrade(&mut self) {
+        if self.version >= LIBRARY_VERSION {
+            return;
+        }
+        for song in self.songs.iter_mut() {
+            song.upgrade(self.version);
+        }
+        info!(
+            "Library upgraded from version {} to {LIBRARY_VERSION}, update \
+             the library to load new song tags",
+            self.version
+        );
+        self.version = LIBRARY_VERSION;
+    }
+
+    /// Assigns IDs to the songs without ID, libraries saved before song IDs
+    /// were added don't have them
+    fn assign_ids(&mut self) {
+        let max = self.songs.iter().map(|s| s.get_id().get()).max();
+        self.next_id = self.next_id.max(max.unwrap_or(0) + 1);
+
+        for i in 0..self.songs.len() {
+            if !self.songs[i].get_id().is_assigned() {
+                let id = self.new_id();
+                self.songs[i].set_id(id);
+            }
+        }
+    }
+
+    /// Checks whether song file on the given path in the search path
+    /// would be found by the library update, its directory isn't too deep
+    /// and it isn't ignored
+    fn is_searched(config: &Config, root: &SearchPath, path: &Path) -> bool {
+        let depth = match path.parent() {
+            Some(dir) => Library::depth(root, dir),
+            None => return false,
+        };
+        depth <= config.get_max_search_depth()
+            && root.search_depth(depth)
+            && !Library::is_ignored(config, path)
+    }
+
+    /// Checks whether file on given path is ignored by the ignore rules
+    fn is_ignored(config: &Config, path: &Path) -> bool {
+        match path.parent().and_then(|p| IgnoreRules::for_dir(config, p)) {
+            Some(rules) => rules.is_ignored(path, false),
+            None => true,
+        }
+    }
+
+    /// Finds search paths that can't be reached and marks their songs
+    /// offline. Returns search paths that became reachable again.
+    fn update_roots(&mut self, config: &Config) -> Vec<PathBuf> {
+        let offline: HashSet<PathBuf> = config
+            .get_paths()
+            .iter()
+            .filter(|p| p.get_enabled() && !self.is_reachable(p.get_path()))
+            .map(|p| p.get_path().to_owned())
+            .collect();
+        self.set_offline(config, offline)
+    }
+
+    /// Marks songs in the given search paths offline and songs in the
+    /// other search paths online. Returns search paths that became
+    /// reachable again.
+    fn set_offline(
+        &mut self,
+        config: &Config,
+        offline: HashSet<PathBuf>,
+    ) -> Vec<PathBuf> {
+        for path in offline.difference(&self.offline) {
+            warn!("Search path {} is offline", path.to_string_lossy());
+        }
+        let online: Vec<PathBuf> =
+            self.offline.difference(&offline).cloned().collect();
+        for path in online.iter() {
+            info!("Search path {} is back online", path.to_string_lossy());
+        }
+        self.offline = offline;
+
+        for song in self.songs.iter_mut() {
+            let offline = config
+                .get_search_path(song.get_path())
+                .is_some_and(|p| self.offline.contains(p.get_path()));
+            song.set_offline(offline);
+        }
+        online
+    }
+
+    /// Checks whether search path can be reached. Empty directory is
+    /// unreachable when it should contain songs, because that's how mount
+    /// points of unmounted drives usually look.
+    fn is_reachable(&self, root: &Path) -> bool {
+        match read_dir(root) {
+            Ok(mut dir) => dir.next().is_some() || !self.has_songs(root),
+            Err(_) => false,
+        }
+    }
+
+    /// Checks whether the search path contains songs that aren't deleted
+    fn has_songs(&self, root: &Path) -> bool {
+        self.songs
+            .iter()
+            .any(|s| !s.get_deleted() && s.get_path().starts_with(root))
+    }
+
+    /// Rebuilds the indexes of songs by their path and ID
+    fn reindex(&mut self) {
+        self.index = self
+            .songs
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (s.get_path().to_owned(), i))
+            .collect();
+        self.ids = self
+            .songs
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (s.get_id(), i))
+            .collect();
+    }
 }
 
 /// Implements default for Library
 impl Default for Library {
     fn default() -> Self {
         Library {
+            version: LIBRARY_VERSION,
             songs: Vec::new(),
+            next_id: 1,
+            errors: Vec::new(),
+            folder_art: HashMap::new(),
             load_process: None,
+            index: HashMap::new(),
+            ids: HashMap::new(),
+            stats: None,
+            pending: Vec::new(),
+            scan: None,
+            progress: None,
+            offline: HashSet::new(),
+            roots_checked: None,
+            checking_roots: false,
+            search: SearchIndex::default(),
+            catalog: Catalog::default(),
+            analysis: None,
+            not_analyzed: HashSet::new(),
+            pending_loudness: Vec::new(),
+            pending_art: Vec::new(),
+            organizing: None,
+            organized: HashSet::new(),
+            art_dirs: HashSet::new(),
         }
     }
 }
@@ -169,8 +1888,51 @@ impl Default for Library {
 impl Clone for Library {
     fn clone(&self) -> Self {
         Self {
+            version: self.version,
             songs: self.songs.clone(),
+            next_id: self.next_id,
+            errors: self.errors.clone(),
+            folder_art: self.folder_art.clone(),
             load_process: None,
+            index: self.index.clone(),
+            ids: self.ids.clone(),
+            stats: self.stats,
+            pending: Vec::new(),
+            scan: None,
+            progress: None,
+            offline: self.offline.clone(),
+            roots_checked: None,
+            checking_roots: false,
+            search: self.search.clone(),
+            catalog: self.catalog.clone(),
+            analysis: None,
+            not_analyzed: self.not_analyzed.clone(),
+            pending_loudness: Vec::new(),
+            pending_art: Vec::new(),
+            organizing: None,
+            organized: HashSet::new(),
+            art_dirs: HashSet::new(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn organize_out_of_non_recursive_root() {
+        let mut config = Config::default();
+        while !config.get_paths().is_empty() {
+            config.remove_path(0);
+        }
+        config.add_path(vec![PathBuf::from("/music")]);
+        config.edit_path(0, |p| p.set_recursive(false));
+        let root = &config.get_paths()[0];
+
+        let song = Path::new("/music/song.mp3");
+        let nested = Path::new("/music/Artist/Album/01 Song.mp3");
+        assert!(Library::is_searched(&config, root, song));
+        assert!(!Library::is_searched(&config, root, nested));
+    }
+}
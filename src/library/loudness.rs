@@ -0,0 +1,342 @@
+use std::{f64::consts::PI, fs::File, path::Path};
+
+use eyre::{Report, Result};
+use serde_derive::{Deserialize, Serialize};
+use symphonia::{
+    core::{
+        audio::SampleBuffer, codecs::CODEC_TYPE_NULL, errors::Error,
+        io::MediaSourceStream, probe::Hint,
+    },
+    default::{get_codecs, get_probe},
+};
+
+use super::ReplayGain;
+
+/// Loudness of a song measured by EBU R128
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Loudness {
+    /// Integrated loudness in LUFS
+    pub integrated: f32,
+    /// True peak in dBTP
+    pub true_peak: f32,
+    /// Loudness range in LU
+    pub range: f32,
+}
+
+impl Loudness {
+    /// Loudness ReplayGain normalizes songs to, in LUFS
+    pub const REFERENCE: f32 = -18.;
+    /// Loudness of silent songs, quieter blocks are gated out
+    const SILENCE: f64 = -70.;
+    /// Maximal gain in dB applied by the measured ReplayGain
+    const MAX_GAIN: f32 = 20.;
+
+    /// Decodes the song on the given path and measures its loudness. The
+    /// measuring stops with error when `cancelled` returns true.
+    pub fn analyze(path: &Path, cancelled: &dyn Fn() -> bool) -> Result<Self> {
+        let file = File::open(path)?;
+        let stream =
+            MediaSourceStream::new(Box::new(file), Default::default());
+        let mut probe = get_probe().format(
+            &Hint::new(),
+            stream,
+            &Default::default(),
+            &Default::default(),
+        )?;
+
+        let track = probe
+            .format
+            .default_track()
+            .filter(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+            .ok_or(Report::msg("File doesn't contain audio"))?;
+        let track_id = track.id;
+        let mut decoder =
+            get_codecs().make(&track.codec_params, &Default::default())?;
+
+        let mut meter: Option<Meter> = None;
+        loop {
+            if cancelled() {
+                return Err(Report::msg("Analysis was cancelled"));
+            }
+            let packet = match probe.format.next_packet() {
+                Ok(packet) => packet,
+                Err(_) => break,
+            };
+            if packet.track_id() != track_id {
+                continue;
+            }
+            let decoded = match decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                Err(Error::DecodeError(_)) => continue,
+                Err(e) => return Err(e.into()),
+            };
+
+            let spec = *decoded.spec();
+            let mut buf =
+                SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+            buf.copy_interleaved_ref(decoded);
+            if meter.is_none() {
+                meter = Some(Meter::new(spec.rate, spec.channels.count())?);
+            }
+            if let Some(meter) = meter.as_mut() {
+                meter.process(buf.samples());
+            }
+        }
+
+        match meter {
+            Some(meter) => meter.loudness(),
+            None => Err(Report::msg("File doesn't contain audio")),
+        }
+    }
+
+    /// Gets track ReplayGain that normalizes the song to the ReplayGain
+    /// reference loudness, the gain is limited so that quiet songs aren't
+    /// amplified too much
+    pub fn get_replay_gain(&self) -> ReplayGain {
+        let gain = Loudness::REFERENCE - self.integrated;
+        ReplayGain {
+            track_gain: Some(
+                gain.clamp(-Loudness::MAX_GAIN, Loudness::MAX_GAIN),
+            ),
+            track_peak: Some(10_f32.powf(self.true_peak / 20.)),
+            ..Default::default()
+        }
+    }
+}
+
+//>=========================================================================<//
+//                             Loudness meter                                //
+//>=========================================================================<//
+
+/// Measures loudness of decoded audio by ITU-R BS.1770
+struct Meter {
+    /// Weights of the channels
+    weights: Vec<f64>,
+    /// K-weighting filters of the channels
+    filters: Vec<[Biquad; 2]>,
+    peaks: Vec<TruePeak>,
+    /// Interpolation coefficients of the true peak oversampling
+    coefs: [[f32; TruePeak::TAPS]; TruePeak::OVERSAMPLING - 1],
+    /// Length of a segment in frames
+    segment_len: usize,
+    /// Number of frames in the current segment
+    frames: usize,
+    /// Weighted sum of squares of the current segment
+    sum: f64,
+    /// Mean weighted power of 100 ms segments
+    segments: Vec<f64>,
+}
+
+impl Meter {
+    /// Creates meter of audio with the given sample rate and number of
+    /// channels, audio without channels can't be measured
+    fn new(rate: u32, channels: usize) -> Result<Self> {
+        if channels == 0 {
+            return Err(Report::msg("Song has no audio channels"));
+        }
+        // 5.1 audio has LFE channel, which isn't measured, and surround
+        // channels that are louder
+        let weights = if channels == 6 {
+            vec![1., 1., 1., 0., 1.41, 1.41]
+        } else {
+            vec![1.; channels]
+        };
+        let rate = rate.max(1) as f64;
+        Ok(Self {
+            weights,
+            filters: vec![
+                [Biquad::shelf(rate), Biquad::high_pass(rate)];
+                channels
+            ],
+            peaks: vec![TruePeak::default(); channels],
+            coefs: TruePeak::coefs(),
+            segment_len: (rate / 10.).round().max(1.) as usize,
+            frames: 0,
+            sum: 0.,
+            segments: Vec::new(),
+        })
+    }
+
+    /// Processes interleaved samples
+    fn process(&mut self, samples: &[f32]) {
+        for frame in samples.chunks_exact(self.weights.len()) {
+            for (c, &sample) in frame.iter().enumerate() {
+                self.peaks[c].process(sample, &self.coefs);
+                let [shelf, pass] = &mut self.filters[c];
+                let y = pass.process(shelf.process(sample as f64));
+                self.sum += self.weights[c] * y * y;
+            }
+
+            self.frames += 1;
+            if self.frames == self.segment_len {
+                self.segments.push(self.sum / self.segment_len as f64);
+                self.frames = 0;
+                self.sum = 0.;
+            }
+        }
+    }
+
+    /// Gets loudness of the processed audio, error when it's silent
+    fn loudness(&self) -> Result<Loudness> {
+        // Momentary blocks are 400 ms long and overlap by 75 %
+        let momentary = self.blocks(4, 1);
+        let integrated = match gate(&momentary, 10.) {
+            Some(gated) => lufs(mean(&gated)),
+            None => return Err(Report::msg("Song is silent")),
+        };
+
+        // Short-term blocks are 3 s long and overlap by 2/3
+        let short = self.blocks(30, 10);
+        let range = match gate(&short, 20.) {
+            Some(gated) => {
+                let mut values: Vec<f64> =
+                    gated.iter().map(|&p| lufs(p)).collect();
+                values.sort_by(|a, b| a.total_cmp(b));
+                let percentile = |p: f64| {
+                    values[((values.len() - 1) as f64 * p).round() as usize]
+                };
+                percentile(0.95) - percentile(0.1)
+            }
+            None => 0.,
+        };
+
+        let peak = self.peaks.iter().map(|p| p.peak).fold(0., f32::max);
+        Ok(Loudness {
+            integrated: integrated as f32,
+            true_peak: 20. * peak.max(1e-10).log10(),
+            range: range as f32,
+        })
+    }
+
+    /// Gets mean power of blocks made of the given number of segments,
+    /// consecutive blocks start the given number of segments apart
+    fn blocks(&self, len: usize, step: usize) -> Vec<f64> {
+        self.segments.windows(len).step_by(step).map(mean).collect()
+    }
+}
+
+/// Gates out blocks quieter than silence and blocks quieter by more than
+/// the given number of LU than the loudness of the rest of the blocks.
+/// Returns None when all blocks are gated out.
+fn gate(blocks: &[f64], relative: f64) -> Option<Vec<f64>> {
+    let loud: Vec<f64> = blocks
+        .iter()
+        .copied()
+        .filter(|&p| lufs(p) > Loudness::SILENCE)
+        .collect();
+    if loud.is_empty() {
+        return None;
+    }
+    let threshold = lufs(mean(&loud)) - relative;
+    let gated: Vec<f64> =
+        loud.into_iter().filter(|&p| lufs(p) > threshold).collect();
+    (!gated.is_empty()).then_some(gated)
+}
+
+/// Converts mean power to loudness in LUFS
+fn lufs(power: f64) -> f64 {
+    -0.691 + 10. * power.max(1e-20).log10()
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len().max(1) as f64
+}
+
+/// Second order IIR filter
+#[derive(Debug, Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    z: [f64; 2],
+}
+
+impl Biquad {
+    /// Creates the first stage of K-weighting, high shelf modelling the
+    /// acoustic effect of the head
+    fn shelf(rate: f64) -> Self {
+        let f0 = 1681.974450955533;
+        let gain = 3.999843853973347;
+        let q = 0.7071752369554196;
+
+        let k = (PI * f0 / rate).tan();
+        let vh = 10_f64.powf(gain / 20.);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1. + k / q + k * k;
+        Self {
+            b: [
+                (vh + vb * k / q + k * k) / a0,
+                2. * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            a: [2. * (k * k - 1.) / a0, (1. - k / q + k * k) / a0],
+            z: [0.; 2],
+        }
+    }
+
+    /// Creates the second stage of K-weighting, high pass filter
+    fn high_pass(rate: f64) -> Self {
+        let f0 = 38.13547087602444;
+        let q = 0.5003270373238773;
+
+        let k = (PI * f0 / rate).tan();
+        let a0 = 1. + k / q + k * k;
+        Self {
+            b: [1., -2., 1.],
+            a: [2. * (k * k - 1.) / a0, (1. - k / q + k * k) / a0],
+            z: [0.; 2],
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.z[0];
+        self.z[0] = self.b[1] * x - self.a[0] * y + self.z[1];
+        self.z[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+/// True peak of a channel, measured by oversampling the audio
+#[derive(Debug, Clone, Default)]
+struct TruePeak {
+    /// Last samples of the channel
+    history: [f32; TruePeak::TAPS],
+    peak: f32,
+}
+
+impl TruePeak {
+    /// Number of samples interpolated values are computed from
+    const TAPS: usize = 12;
+    const OVERSAMPLING: usize = 4;
+
+    fn process(
+        &mut self,
+        sample: f32,
+        coefs: &[[f32; TruePeak::TAPS]; TruePeak::OVERSAMPLING - 1],
+    ) {
+        self.history.copy_within(1.., 0);
+        self.history[TruePeak::TAPS - 1] = sample;
+        self.peak = self.peak.max(sample.abs());
+        for coefs in coefs {
+            let value: f32 =
+                self.history.iter().zip(coefs).map(|(s, c)| s * c).sum();
+            self.peak = self.peak.max(value.abs());
+        }
+    }
+
+    /// Gets coefficients of windowed sinc interpolation between the middle
+    /// samples of the history
+    fn coefs() -> [[f32; TruePeak::TAPS]; TruePeak::OVERSAMPLING - 1] {
+        let half = (TruePeak::TAPS / 2) as f64;
+        let mut coefs = [[0.; TruePeak::TAPS]; TruePeak::OVERSAMPLING - 1];
+        for (p, coefs) in coefs.iter_mut().enumerate() {
+            let offset = (p + 1) as f64 / TruePeak::OVERSAMPLING as f64;
+            for (k, coef) in coefs.iter_mut().enumerate() {
+                let t = offset - (k as f64 - (half - 1.));
+                let sinc = (PI * t).sin() / (PI * t);
+                let window = 0.5 * (1. + (PI * t / half).cos());
+                *coef = (sinc * window) as f32;
+            }
+        }
+        coefs
+    }
+}
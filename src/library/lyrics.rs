@@ -0,0 +1,208 @@
+use std::{fs, path::Path, time::Duration};
+
+use id3::frame::TimestampFormat;
+use symphonia::{
+    core::{io::MediaSourceStream, meta::StandardTagKey, probe::Hint},
+    default::get_probe,
+};
+
+/// Lyrics of a song, synced lyrics have time of each line
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Lyrics {
+    /// Lines of the lyrics, synced lines are ordered by their time
+    lines: Vec<LyricsLine>,
+}
+
+/// Line of [`Lyrics`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct LyricsLine {
+    /// Time in the song when the line starts, None for lyrics that aren't
+    /// synced
+    time: Option<Duration>,
+    text: String,
+}
+
+impl Lyrics {
+    /// Loads lyrics of the song on the given path. Synced lyrics are
+    /// preferred, they are loaded from `.lrc` file next to the song,
+    /// embedded SYLT tag and embedded lyrics. Otherwise lyrics are loaded
+    /// from `.txt` file next to the song.
+    pub fn load(path: &Path) -> Option<Self> {
+        let file = |ext| {
+            fs::read_to_string(path.with_extension(ext))
+                .ok()
+                .map(|text| Lyrics::parse(&text))
+                .filter(|l| !l.lines.is_empty())
+        };
+
+        let lrc = file("lrc");
+        if lrc.as_ref().is_some_and(|l| l.is_synced()) {
+            return lrc;
+        }
+        if let Some(lyrics) = Lyrics::load_sylt(path) {
+            return Some(lyrics);
+        }
+        let embedded = Lyrics::load_embedded(path);
+        if embedded.as_ref().is_some_and(|l| l.is_synced()) {
+            return embedded;
+        }
+        lrc.or(embedded).or_else(|| file("txt"))
+    }
+
+    /// Parses lyrics in the LRC format, such as `[01:23.45] Line`. When
+    /// the text doesn't contain any time, it's parsed as plain lyrics.
+    pub fn parse(text: &str) -> Self {
+        let mut lines = Vec::new();
+        // Offset in milliseconds, positive offset shows lines sooner
+        let mut offset = 0;
+        for line in text.lines() {
+            let mut rest = line.trim();
+            let mut times = Vec::new();
+            while let Some((tag, after)) =
+                rest.strip_prefix('[').and_then(|r| r.split_once(']'))
+            {
+                if let Some(time) = parse_time(tag) {
+                    times.push(time);
+                } else if let Some(o) = tag.strip_prefix("offset:") {
+                    offset = o.trim().parse::<i64>().unwrap_or(0);
+                } else if !tag.contains(':') {
+                    break;
+                }
+                // Other tags such as `[ar:Artist]` are skipped
+                rest = after.trim_start();
+            }
+            for time in times {
+                lines.push(LyricsLine {
+                    time: Some(time),
+                    text: rest.trim().to_owned(),
+                });
+            }
+        }
+
+        if lines.is_empty() {
+            return Lyrics::plain(text);
+        }
+        for line in lines.iter_mut() {
+            if let Some(time) = line.time.as_mut() {
+                *time = if offset >= 0 {
+                    time.saturating_sub(Duration::from_millis(offset as u64))
+                } else {
+                    *time + Duration::from_millis(offset.unsigned_abs())
+                };
+            }
+        }
+        lines.sort_by_key(|l| l.time);
+        Self { lines }
+    }
+
+    /// Checks whether the lyrics have time of their lines
+    pub fn is_synced(&self) -> bool {
+        self.lines.iter().any(|l| l.time.is_some())
+    }
+
+    /// Gets lines of the lyrics
+    pub fn get_lines(&self) -> &[LyricsLine] {
+        &self.lines
+    }
+
+    /// Gets index of the line sung at the given time, None when the lyrics
+    /// aren't synced or the first line didn't start yet
+    pub fn current_line(&self, time: Duration) -> Option<usize> {
+        self.lines
+            .iter()
+            .rposition(|l| l.time.is_some_and(|t| t <= time))
+    }
+
+    /// Creates lyrics that aren't synced from the given text
+    fn plain(text: &str) -> Self {
+        let lines: Vec<LyricsLine> = text
+            .trim()
+            .lines()
+            .map(|l| LyricsLine {
+                time: None,
+                text: l.trim().to_owned(),
+            })
+            .collect();
+        Self { lines }
+    }
+
+    /// Loads synced lyrics from the ID3 SYLT tag
+    fn load_sylt(path: &Path) -> Option<Self> {
+        let tag = id3::Tag::read_from_path(path).ok()?;
+        let sylt = tag
+            .synchronised_lyrics()
+            .find(|l| l.timestamp_format == TimestampFormat::Ms)?;
+        let mut lines: Vec<LyricsLine> = sylt
+            .content
+            .iter()
+            .map(|(ms, text)| LyricsLine {
+                time: Some(Duration::from_millis(*ms as u64)),
+                text: text.trim().to_owned(),
+            })
+            .collect();
+        lines.sort_by_key(|l| l.time);
+        (!lines.is_empty()).then_some(Self { lines })
+    }
+
+    /// Loads lyrics embedded in the song tags, such as ID3 USLT tag.
+    /// Embedded lyrics can be in the LRC format.
+    fn load_embedded(path: &Path) -> Option<Self> {
+        let file = fs::File::open(path).ok()?;
+        let stream =
+            MediaSourceStream::new(Box::new(file), Default::default());
+        let mut probe = get_probe()
+            .format(
+                &Hint::new(),
+                stream,
+                &Default::default(),
+                &Default::default(),
+            )
+            .ok()?;
+
+        let mut text = None;
+        if let Some(mut meta) = probe.metadata.get() {
+            text = meta.skip_to_latest().and_then(|r| lyrics_tag(r.tags()));
+        }
+        if text.is_none() {
+            text = probe
+                .format
+                .metadata()
+                .skip_to_latest()
+                .and_then(|r| lyrics_tag(r.tags()));
+        }
+
+        let lyrics = Lyrics::parse(&text?);
+        (!lyrics.lines.is_empty()).then_some(lyrics)
+    }
+}
+
+impl LyricsLine {
+    /// Gets time in the song when the line starts, None when the lyrics
+    /// aren't synced
+    pub fn get_time(&self) -> Option<Duration> {
+        self.time
+    }
+
+    /// Gets text of the line
+    pub fn get_text(&self) -> &str {
+        &self.text
+    }
+}
+
+/// Gets value of the lyrics tag from the given tags
+fn lyrics_tag(tags: &[symphonia::core::meta::Tag]) -> Option<String> {
+    tags.iter()
+        .find(|t| t.std_key == Some(StandardTagKey::Lyrics))
+        .map(|t| t.value.to_string())
+        .filter(|t| !t.trim().is_empty())
+}
+
+/// Parses LRC time, such as `01:23.45`
+fn parse_time(time: &str) -> Option<Duration> {
+    let (mins, secs) = time.split_once(':')?;
+    let mins: u64 = mins.trim().parse().ok()?;
+    // Some files use ':' to separate hundredths of seconds
+    let secs: f64 = secs.trim().replacen(':', ".", 1).parse().ok()?;
+    let secs = Duration::try_from_secs_f64(secs).ok()?;
+    Duration::from_secs(mins.checked_mul(60)?).checked_add(secs)
+}
@@ -1,4 +1,26 @@
+mod analysis;
+mod art;
+mod catalog;
+mod duplicates;
+mod file_id;
+mod ignore_rules;
 mod library;
+mod loudness;
+mod lyrics;
+mod normalize;
+mod organizer;
+mod query;
+mod replay_gain;
+mod scan;
+mod search;
 mod song;
+mod sort;
+mod tag_edit;
+mod tag_infer;
+mod watcher;
 
-pub use self::{library::*, song::*};
+pub use self::{
+    art::*, duplicates::*, ignore_rules::*, library::*, loudness::*,
+    lyrics::*, normalize::*, organizer::*, query::*, replay_gain::*, scan::*,
+    song::*, sort::*, tag_edit::*, tag_infer::*, watcher::*,
+};
@@ -0,0 +1,313 @@
+use std::{
+    fs::File,
+    io::{BufReader, Read},
+    path::Path,
+};
+
+use encoding_rs::Encoding;
+use eyre::Result;
+use id3::TagLike;
+use log::warn;
+
+use crate::config::Config;
+
+use super::TagField;
+
+/// Normalizes tag values of loaded songs, repairs text of legacy tags
+/// decoded with wrong encoding, maps ID3 genre numbers to their names and
+/// trims whitespace
+#[derive(Debug, Clone, Copy)]
+pub struct TagNormalizer {
+    enabled: bool,
+    /// Encoding of text in legacy tags, such as `windows-1251`
+    encoding: Option<&'static Encoding>,
+}
+
+/// Text of the ID3 tags of a song file that is stored as Latin-1, such
+/// text can be in a legacy encoding
+#[derive(Debug, Default)]
+pub struct Latin1Tags {
+    /// Values of the ID3v2 frames with Latin-1 text
+    v2: Vec<(TagField, String)>,
+    /// Values of the ID3v1 tag, which is always Latin-1
+    v1: Vec<(TagField, String)>,
+}
+
+impl TagNormalizer {
+    /// Creates normalizer with settings from the config
+    pub fn new(config: &Config) -> Self {
+        let encoding =
+            config.get_legacy_encoding().as_ref().and_then(|label| {
+                let encoding = Encoding::for_label(label.trim().as_bytes());
+                if encoding.is_none() {
+                    warn!("Unknown legacy tag encoding '{label}'");
+                }
+                encoding
+            });
+        Self {
+            enabled: config.get_normalize_tags(),
+            encoding,
+        }
+    }
+
+    /// Checks whether the given label is a known encoding
+    pub fn is_encoding(label: &str) -> bool {
+        Encoding::for_label(label.trim().as_bytes()).is_some()
+    }
+
+    /// Reads text of the ID3 tags of the song file that is stored as
+    /// Latin-1, nothing is read when normalization is disabled
+    pub fn read_latin1(&self, path: &Path) -> Latin1Tags {
+        let mp3 = path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("mp3"));
+        if !self.enabled || !mp3 {
+            return Latin1Tags::default();
+        }
+        match Latin1Tags::read(path) {
+            Ok(tags) => tags,
+            Err(e) => {
+                warn!("Failed to read ID3 tags of {}: {e}", path.display());
+                Latin1Tags::default()
+            }
+        }
+    }
+
+    /// Gets normalized value of the field. Value stored as Latin-1 is
+    /// repaired when it's UTF-8 or decoded by the legacy encoding.
+    pub fn normalize(
+        &self,
+        field: TagField,
+        value: &str,
+        latin1: bool,
+    ) -> String {
+        if !self.enabled {
+            return value.to_owned();
+        }
+        let value = if latin1 {
+            self.decode(value)
+        } else {
+            value.to_owned()
+        };
+
+        match field {
+            // Comments can have more lines
+            TagField::Comment => value.trim().to_owned(),
+            TagField::Genre => TagNormalizer::genre(&collapse(&value)),
+            _ => collapse(&value),
+        }
+    }
+
+    /// Decodes text that was stored as Latin-1 again, as UTF-8 when it's
+    /// valid UTF-8, otherwise by the legacy encoding when it's set
+    fn decode(&self, value: &str) -> String {
+        let legacy = value.chars().all(|c| (c as u32) < 0x100)
+            && value.chars().any(|c| (c as u32) >= 0x80);
+        if !legacy {
+            return value.to_owned();
+        }
+
+        let bytes: Vec<u8> = value.chars().map(|c| c as u8).collect();
+        match (std::str::from_utf8(&bytes), self.encoding) {
+            (Ok(text), _) => text.to_owned(),
+            (_, Some(encoding)) => {
+                encoding.decode_without_bom_handling(&bytes).0.into_owned()
+            }
+            _ => value.to_owned(),
+        }
+    }
+
+    /// Maps ID3 genre numbers, such as `(17)` or `17`, to their names.
+    /// Genre name after the numbers replaces them, such as `(17)Rock`.
+    fn genre(value: &str) -> String {
+        let is_id = |id: &str| {
+            matches!(id, "RX" | "CR")
+                || (!id.is_empty() && id.bytes().all(|b| b.is_ascii_digit()))
+        };
+        let mut rest = value;
+        while let Some((id, genre)) =
+            rest.strip_prefix('(').and_then(|r| r.split_once(')'))
+        {
+            if !is_id(id) {
+                break;
+            }
+            rest = genre;
+        }
+        let rest = rest.trim();
+        if !rest.is_empty() && rest != value {
+            return rest.to_owned();
+        }
+
+        let tcon = if is_id(value) {
+            format!("({value})")
+        } else {
+            value.to_owned()
+        };
+        let mut tag = id3::Tag::new();
+        tag.set_genre(tcon);
+        match tag.genre_parsed() {
+            // Unknown genre numbers are kept as they are
+            Some(genre) if !genre.starts_with('(') => genre.into_owned(),
+            _ => value.to_owned(),
+        }
+    }
+}
+
+impl Latin1Tags {
+    /// Gets values of the ID3v1 tag
+    pub fn get_v1(&self) -> &[(TagField, String)] {
+        &self.v1
+    }
+
+    /// Checks whether the value of the field was read from ID3v2 frame
+    /// with Latin-1 text
+    pub fn is_latin1(&self, field: TagField, value: &str) -> bool {
+        self.v2
+            .iter()
+            .any(|(f, v)| *f == field && v.trim() == value.trim())
+    }
+
+    /// Reads Latin-1 text of the ID3 tags of the file on the given path
+    fn read(path: &Path) -> Result<Self> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+        let mut file = BufReader::new(file);
+        let v2 = Latin1Tags::read_v2(&mut file, len)?;
+        let v1 = match id3::v1::Tag::read_from(file.into_inner()) {
+            Ok(tag) => [
+                (TagField::Title, tag.title.clone()),
+                (TagField::Artist, tag.artist.clone()),
+                (TagField::Album, tag.album.clone()),
+                (TagField::Year, tag.year.clone()),
+                (TagField::Comment, tag.comment.clone()),
+                (
+                    TagField::Track,
+                    tag.track.map(|t| t.to_string()).unwrap_or_default(),
+                ),
+                (TagField::Genre, tag.genre().unwrap_or_default().to_owned()),
+            ]
+            .into_iter()
+            .filter(|(_, v)| !v.trim().is_empty())
+            .collect(),
+            Err(_) => Vec::new(),
+        };
+        Ok(Self { v2, v1 })
+    }
+
+    /// Reads values of the ID3v2 text frames whose encoding byte is 0, so
+    /// their text is Latin-1. Unsynchronised, compressed and encrypted
+    /// frames are skipped. Tag larger than the file of the given length
+    /// is corrupted and it's skipped too.
+    fn read_v2(
+        file: &mut impl Read,
+        len: u64,
+    ) -> Result<Vec<(TagField, String)>> {
+        let mut header = [0; 10];
+        if file.read_exact(&mut header).is_err() || &header[..3] != b"ID3" {
+            return Ok(Vec::new());
+        }
+        let version = header[3];
+        let flags = header[5];
+        if flags & 0x80 != 0 || !(2..=4).contains(&version) {
+            return Ok(Vec::new());
+        }
+        let size = syncsafe(&header[6..10]);
+        if size as u64 > len.saturating_sub(header.len() as u64) {
+            return Ok(Vec::new());
+        }
+        let mut data = vec![0; size];
+        file.read_exact(&mut data)?;
+
+        let mut pos = match (flags & 0x40 != 0, version) {
+            (true, 3) => data.get(..4).map_or(0, |s| 4 + big_endian(s)),
+            (true, 4) => data.get(..4).map_or(0, syncsafe),
+            _ => 0,
+        };
+        let (id_len, header_len) = if version == 2 { (3, 6) } else { (4, 10) };
+
+        let mut values = Vec::new();
+        while let Some(frame) = data.get(pos..pos + header_len) {
+            // Padding after the frames
+            if frame[0] == 0 {
+                break;
+            }
+            let size = match version {
+                2 => big_endian(&frame[3..6]),
+                3 => big_endian(&frame[4..8]),
+                _ => syncsafe(&frame[4..8]),
+            };
+            let start = pos + header_len;
+            pos = start + size;
+            let content = match data.get(start..pos) {
+                Some(content) => content,
+                None => break,
+            };
+
+            let formatted = match version {
+                3 => frame[9] & 0xc0 != 0,
+                4 => frame[9] & 0x0f != 0,
+                _ => false,
+            };
+            let field = match Latin1Tags::frame_field(&frame[..id_len]) {
+                Some(field) if !formatted => field,
+                _ => continue,
+            };
+            let text = match content.split_first() {
+                Some((0, text)) => text,
+                _ => continue,
+            };
+            // Comment starts with language and null terminated description
+            let text = if field == TagField::Comment {
+                match text.get(3..).and_then(|t| {
+                    t.iter().position(|&b| b == 0).map(|i| &t[i + 1..])
+                }) {
+                    Some(text) => text,
+                    None => continue,
+                }
+            } else {
+                text
+            };
+            values.push((
+                field,
+                text.iter()
+                    .take_while(|&&b| b != 0)
+                    .map(|&b| b as char)
+                    .collect(),
+            ));
+        }
+        Ok(values)
+    }
+
+    /// Gets field of the ID3v2 frame with the given ID
+    fn frame_field(id: &[u8]) -> Option<TagField> {
+        Some(match id {
+            b"TIT2" | b"TT2" => TagField::Title,
+            b"TPE1" | b"TP1" => TagField::Artist,
+            b"TALB" | b"TAL" => TagField::Album,
+            b"TPE2" | b"TP2" => TagField::AlbumArtist,
+            b"TCON" | b"TCO" => TagField::Genre,
+            b"TCOM" | b"TCM" => TagField::Composer,
+            b"COMM" | b"COM" => TagField::Comment,
+            _ => return None,
+        })
+    }
+}
+
+/// Reads big endian number
+fn big_endian(bytes: &[u8]) -> usize {
+    bytes.iter().fold(0, |n, &b| (n << 8) | b as usize)
+}
+
+/// Reads ID3v2 syncsafe number, which uses only 7 bits of each byte
+fn syncsafe(bytes: &[u8]) -> usize {
+    bytes.iter().fold(0, |n, &b| (n << 7) | (b & 0x7f) as usize)
+}
+
+/// Trims the text and replaces whitespace and null characters between
+/// words by single space
+fn collapse(text: &str) -> String {
+    text.split(|c: char| c.is_whitespace() || c == '\0')
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,609 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+use eyre::{Report, Result};
+use log::{error, info, warn};
+use serde_derive::{Deserialize, Serialize};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{
+    config::Config,
+    gui::app::{LibMsg, Msg},
+};
+
+use super::{FolderArt, Song, SongId};
+
+/// Template of song file paths relative to their search path, such as
+/// `{albumartist}/{year} - {album}/{disc}{track:02} {title}.{ext}`. Fields
+/// can be padded with zeros to the given width, such as `{track:02}`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathTemplate {
+    parts: Vec<TemplatePart>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TemplatePart {
+    Text(String),
+    Field(TemplateField, usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TemplateField {
+    Title,
+    Artist,
+    Album,
+    AlbumArtist,
+    Year,
+    Date,
+    Track,
+    TrackTotal,
+    Disc,
+    DiscTotal,
+    Genre,
+    Composer,
+    Ext,
+}
+
+/// Planned moves of the song files by the template, the moves are applied
+/// only when planning by the template gives the same moves again
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrganizePlan {
+    /// Template the moves were planned by
+    pub template: String,
+    pub moves: Vec<OrganizeMove>,
+}
+
+/// Planned move of a song file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrganizeMove {
+    /// ID of the moved song
+    pub id: SongId,
+    /// Current path of the song file
+    pub from: PathBuf,
+    /// Path the song file is moved to
+    pub to: PathBuf,
+    /// Why the song file can't be moved, None when it can
+    pub conflict: Option<OrganizeConflict>,
+}
+
+/// Reason why a song file can't be moved
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum OrganizeConflict {
+    /// Other file already exists on the new path
+    Exists,
+    /// More songs would be moved to the same path
+    SameTarget,
+    /// New path wouldn't be searched by the library update, because it's
+    /// too deep in the search path or it's ignored
+    OutOfScope,
+}
+
+/// Step of applying the planned moves
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OrganizeStep {
+    /// Moves song file of the move with the given index to its new path
+    Move(usize),
+    /// Sets song file of the move with the given index aside, so that
+    /// other file can be moved to its path
+    Aside(usize),
+}
+
+/// Handle to running organizing of the library, used to get its progress.
+/// Clones share the same organizing.
+#[derive(Clone)]
+pub struct Organizing {
+    state: Arc<OrganizingState>,
+}
+
+struct OrganizingState {
+    /// Number of planned moves
+    total: usize,
+    /// Number of moves that were already tried
+    done: AtomicUsize,
+    /// Number of moved songs
+    moved: AtomicUsize,
+}
+
+impl PathTemplate {
+    /// Maximal length of a file name in characters
+    const MAX_NAME: usize = 200;
+
+    /// Parses the template
+    pub fn parse(template: &str) -> Result<Self> {
+        let template = template.trim();
+        if template.starts_with('/') || template.starts_with('\\') {
+            return Err(Report::msg("Template must be a relative path"));
+        }
+
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                parts.push(TemplatePart::Text(rest[..start].to_owned()));
+            }
+            let end = match rest[start..].find('}') {
+                Some(end) => start + end,
+                None => {
+                    return Err(Report::msg("Template field isn't closed"))
+                }
+            };
+
+            let field = &rest[start + 1..end];
+            let (name, width) = match field.split_once(':') {
+                Some((name, width)) => match width.parse() {
+                    Ok(width) => (name, width),
+                    Err(_) => {
+                        return Err(Report::msg(format!(
+                            "Invalid width of template field '{field}'"
+                        )))
+                    }
+                },
+                None => (field, 0),
+            };
+            parts
+                .push(TemplatePart::Field(TemplateField::parse(name)?, width));
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(TemplatePart::Text(rest.to_owned()));
+        }
+
+        if !parts
+            .iter()
+            .any(|p| matches!(p, TemplatePart::Field(TemplateField::Ext, _)))
+        {
+            return Err(Report::msg("Template must contain {ext}"));
+        }
+        Ok(Self { parts })
+    }
+
+    /// Gets path of the song by the template, relative to its search path
+    pub fn render(&self, song: &Song) -> PathBuf {
+        let mut names = vec![String::new()];
+        for part in self.parts.iter() {
+            match part {
+                TemplatePart::Text(text) => {
+                    let mut dirs = text.split(['/', '\\']);
+                    if let (Some(name), Some(first)) =
+                        (names.last_mut(), dirs.next())
+                    {
+                        name.push_str(first);
+                    }
+                    names.extend(dirs.map(|d| d.to_owned()));
+                }
+                TemplatePart::Field(field, width) => {
+                    let value = field.get(song);
+                    let value = match value.parse::<u32>() {
+                        Ok(n) if *width > 0 => format!("{n:0width$}"),
+                        _ => value,
+                    };
+                    if let Some(name) = names.last_mut() {
+                        name.push_str(&value.replace(['/', '\\'], "_"));
+                    }
+                }
+            }
+        }
+
+        let file = names.pop().unwrap_or_default();
+        let mut path: PathBuf = names
+            .iter()
+            .map(|n| PathTemplate::sanitize(n, PathTemplate::MAX_NAME))
+            .filter(|n| !n.is_empty())
+            .collect();
+        path.push(PathTemplate::sanitize_file(&file));
+        path
+    }
+
+    /// Replaces characters that can't be in file names, trims spaces and
+    /// dots at the end and shortens the name to the given length
+    fn sanitize(name: &str, max: usize) -> String {
+        let name: String = name
+            .chars()
+            .map(|c| match c {
+                '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+                c if c.is_control() => '_',
+                c => c,
+            })
+            .take(max)
+            .collect();
+        match name.trim().trim_end_matches('.').trim_end() {
+            "." | ".." => "_".to_owned(),
+            name => name.to_owned(),
+        }
+    }
+
+    /// Sanitizes name of the song file, its extension is kept when the
+    /// name is shortened
+    fn sanitize_file(name: &str) -> String {
+        let (stem, ext) = match name.rsplit_once('.') {
+            Some((stem, ext)) => (stem, Some(ext)),
+            None => (name, None),
+        };
+        let ext = ext.map(|e| PathTemplate::sanitize(e, 16));
+        let max = PathTemplate::MAX_NAME - ext.as_ref().map_or(0, |e| e.len());
+        let stem = match PathTemplate::sanitize(stem, max) {
+            stem if stem.is_empty() => "_".to_owned(),
+            stem => stem,
+        };
+        match ext {
+            Some(ext) if !ext.is_empty() => format!("{stem}.{ext}"),
+            _ => stem,
+        }
+    }
+}
+
+impl TemplateField {
+    fn parse(name: &str) -> Result<Self> {
+        Ok(match name.trim().to_lowercase().as_str() {
+            "title" => TemplateField::Title,
+            "artist" => TemplateField::Artist,
+            "album" => TemplateField::Album,
+            "albumartist" => TemplateField::AlbumArtist,
+            "year" => TemplateField::Year,
+            "date" => TemplateField::Date,
+            "track" => TemplateField::Track,
+            "tracktotal" => TemplateField::TrackTotal,
+            "disc" => TemplateField::Disc,
+            "disctotal" => TemplateField::DiscTotal,
+            "genre" => TemplateField::Genre,
+            "composer" => TemplateField::Composer,
+            "ext" => TemplateField::Ext,
+            _ => {
+                return Err(Report::msg(format!(
+                    "Unknown template field '{name}'"
+                )))
+            }
+        })
+    }
+
+    /// Gets value of the field of the song, missing text is replaced by
+    /// `Unknown` and missing numbers are empty
+    fn get(&self, song: &Song) -> String {
+        let text = |t: &str| match t.trim() {
+            "" | "-" => "Unknown".to_owned(),
+            t => t.to_owned(),
+        };
+        let number = |n: Option<u16>| n.map(|n| n.to_string());
+        let path = song.get_path();
+        match self {
+            TemplateField::Title => match song.get_name().trim() {
+                // Untagged songs keep their file name
+                "" | "-" => path
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                name => name.to_owned(),
+            },
+            TemplateField::Artist => text(song.get_artist()),
+            TemplateField::Album => text(song.get_album()),
+            TemplateField::AlbumArtist => match song.get_album_artist() {
+                "" => text(song.get_artist()),
+                artist => text(artist),
+            },
+            TemplateField::Year => {
+                song.get_year().map(|y| y.to_string()).unwrap_or_default()
+            }
+            TemplateField::Date => {
+                song.get_date().unwrap_or_default().to_owned()
+            }
+            TemplateField::Track => {
+                number(song.get_track()).unwrap_or_default()
+            }
+            TemplateField::TrackTotal => {
+                number(song.get_track_total()).unwrap_or_default()
+            }
+            TemplateField::Disc => number(song.get_disc()).unwrap_or_default(),
+            TemplateField::DiscTotal => {
+                number(song.get_disc_total()).unwrap_or_default()
+            }
+            TemplateField::Genre => text(song.get_genre()),
+            TemplateField::Composer => text(song.get_composer()),
+            TemplateField::Ext => path
+                .extension()
+                .map(|e| e.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl OrganizeMove {
+    /// Finds conflicts of the planned moves, moves to existing files and
+    /// more moves to the same path. Moves that already have conflict keep
+    /// it. Files moved by other moves of the plan don't block their paths,
+    /// so that the songs can swap their paths. Paths are compared
+    /// case-insensitively, so that the moves work on all file systems.
+    pub fn find_conflicts(moves: &mut [OrganizeMove]) {
+        let mut targets: HashMap<String, usize> = HashMap::new();
+        for m in moves.iter().filter(|m| m.conflict.is_none()) {
+            *targets.entry(path_key(&m.to)).or_default() += 1;
+        }
+        for m in moves.iter_mut().filter(|m| m.conflict.is_none()) {
+            if targets.get(&path_key(&m.to)).is_some_and(|&c| c > 1) {
+                m.conflict = Some(OrganizeConflict::SameTarget);
+            }
+        }
+
+        // Blocked move also blocks the move waiting for its file
+        let sources = OrganizeMove::sources(moves);
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for i in 0..moves.len() {
+                if moves[i].conflict.is_some() || !moves[i].is_blocked() {
+                    continue;
+                }
+                let vacated = sources
+                    .get(&path_key(&moves[i].to))
+                    .is_some_and(|&j| j != i && moves[j].conflict.is_none());
+                if !vacated {
+                    moves[i].conflict = Some(OrganizeConflict::Exists);
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    /// Checks whether other file exists on the new path, the file itself
+    /// can exist there when only case of its name changes
+    pub fn is_blocked(&self) -> bool {
+        let same = path_key(&self.from) == path_key(&self.to);
+        !same && self.to.exists()
+    }
+
+    /// Orders the moves so that each file is moved only after the file on
+    /// its new path was moved away. When the moves wait for each other in
+    /// a cycle, one of the files is first set aside.
+    pub fn order(moves: &[OrganizeMove]) -> Vec<OrganizeStep> {
+        let sources = OrganizeMove::sources(moves);
+        let mut done = vec![false; moves.len()];
+        let mut chained = vec![false; moves.len()];
+        let mut steps = Vec::new();
+        for start in 0..moves.len() {
+            // Follows the moves vacating the new paths
+            let mut chain = Vec::new();
+            let mut i = start;
+            while !done[i] && !chained[i] {
+                chained[i] = true;
+                chain.push(i);
+                match sources.get(&path_key(&moves[i].to)) {
+                    Some(&j) if j != i => i = j,
+                    _ => break,
+                }
+            }
+            if !done[i] && chain.last() != Some(&i) {
+                steps.push(OrganizeStep::Aside(i));
+            }
+            for &i in chain.iter().rev() {
+                done[i] = true;
+                steps.push(OrganizeStep::Move(i));
+            }
+        }
+        steps
+    }
+
+    /// Moves the song file from the given path, which is its current path
+    /// or the path it was set aside to, to the new path
+    pub fn apply(&self, from: &Path) -> Result<()> {
+        if self.is_blocked() {
+            return Err(Report::msg("File already exists"));
+        }
+        if let Some(dir) = self.to.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        move_song(from, &self.to)
+    }
+
+    /// Gets path the song file is set aside to when it's in a cycle
+    fn aside(&self) -> PathBuf {
+        let name = self.from.file_name().unwrap_or_default();
+        self.from
+            .with_file_name(format!(".{}.organizing", name.to_string_lossy()))
+    }
+
+    /// Gets index of the move of each file by its path
+    fn sources(moves: &[OrganizeMove]) -> HashMap<String, usize> {
+        moves
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| m.conflict.is_none())
+            .map(|(i, m)| (path_key(&m.from), i))
+            .collect()
+    }
+
+    /// Gets description of the conflict of the move
+    pub fn conflict_str(&self) -> Option<&'static str> {
+        self.conflict.map(|c| match c {
+            OrganizeConflict::Exists => "file already exists",
+            OrganizeConflict::SameTarget => "more songs have the same path",
+            OrganizeConflict::OutOfScope => {
+                "new path wouldn't be searched by library update"
+            }
+        })
+    }
+}
+
+impl Organizing {
+    /// Creates handle to organizing with the given number of moves
+    pub fn new(total: usize) -> Self {
+        Self {
+            state: Arc::new(OrganizingState {
+                total,
+                done: AtomicUsize::new(0),
+                moved: AtomicUsize::new(0),
+            }),
+        }
+    }
+
+    /// Starts moving the song files in a background thread. Each moved
+    /// song is sent to the app as soon as it's moved, so that the library
+    /// follows its file.
+    pub fn start(
+        config: Config,
+        moves: Vec<OrganizeMove>,
+        sender: UnboundedSender<Msg>,
+    ) -> Self {
+        let organizing = Organizing::new(moves.len());
+        let handle = organizing.clone();
+        thread::spawn(move || {
+            handle.run(&config, &moves, |m| {
+                _ = sender.send(Msg::Lib(LibMsg::Moved(m.clone())));
+            });
+            _ = sender.send(Msg::Lib(LibMsg::OrganizeEnded));
+        });
+        organizing
+    }
+
+    /// Moves the song files as planned and calls `moved` for each moved
+    /// song. Art images of the directories are moved with the songs and
+    /// directories left empty are removed.
+    pub fn run(
+        &self,
+        config: &Config,
+        moves: &[OrganizeMove],
+        mut moved: impl FnMut(&OrganizeMove),
+    ) {
+        // Directories the songs of each directory were moved to
+        let mut dirs: HashMap<PathBuf, HashSet<PathBuf>> = HashMap::new();
+        let mut aside: HashMap<usize, PathBuf> = HashMap::new();
+        for step in OrganizeMove::order(moves) {
+            let i = match step {
+                OrganizeStep::Move(i) => i,
+                OrganizeStep::Aside(i) => {
+                    let m = &moves[i];
+                    match move_song(&m.from, &m.aside()) {
+                        Ok(_) => _ = aside.insert(i, m.aside()),
+                        Err(e) => {
+                            error!("Failed to move {}: {e}", m.from.display())
+                        }
+                    }
+                    continue;
+                }
+            };
+            let m = &moves[i];
+            let res = m.apply(aside.get(&i).unwrap_or(&m.from));
+            self.state.done.fetch_add(1, Ordering::Relaxed);
+            if let Err(e) = res {
+                error!("Failed to move {}: {e}", m.from.display());
+                // File set aside is returned back
+                if let Some(from) = aside.get(&i) {
+                    _ = move_song(from, &m.from);
+                }
+                continue;
+            }
+
+            self.state.moved.fetch_add(1, Ordering::Relaxed);
+            if let (Some(from), Some(to)) = (m.from.parent(), m.to.parent()) {
+                dirs.entry(from.to_owned())
+                    .or_default()
+                    .insert(to.to_owned());
+            }
+            moved(m);
+        }
+
+        for (dir, targets) in dirs.iter() {
+            move_art(dir, targets, config.get_art_files());
+            remove_empty(config, dir);
+        }
+        info!("Library organized: {} songs moved", self.get_moved());
+    }
+
+    /// Gets number of tried moves and number of all planned moves
+    pub fn get_progress(&self) -> (usize, usize) {
+        (self.state.done.load(Ordering::Relaxed), self.state.total)
+    }
+
+    /// Gets number of moved songs
+    pub fn get_moved(&self) -> usize {
+        self.state.moved.load(Ordering::Relaxed)
+    }
+}
+
+/// Moves art images of the directory to the directories its songs were
+/// moved to. Images are only copied when other files stay in the directory.
+fn move_art(dir: &Path, targets: &HashSet<PathBuf>, patterns: &[String]) {
+    let arts = FolderArt::find_files(dir, patterns);
+    if arts.is_empty() {
+        return;
+    }
+    let stays = match fs::read_dir(dir) {
+        Ok(entries) => entries.flatten().any(|e| !arts.contains(&e.path())),
+        Err(_) => true,
+    };
+
+    for art in arts.iter() {
+        let name = match art.file_name() {
+            Some(name) => name,
+            None => continue,
+        };
+        let mut copied = true;
+        for target in targets.iter().filter(|t| *t != dir) {
+            let to = target.join(name);
+            if to.exists() {
+                continue;
+            }
+            if let Err(e) = fs::copy(art, &to) {
+                warn!("Failed to copy art {}: {e}", art.display());
+                copied = false;
+            }
+        }
+        if copied && !stays {
+            _ = fs::remove_file(art);
+        }
+    }
+}
+
+/// Removes the directory and its parents in the search path while they
+/// are empty
+fn remove_empty(config: &Config, dir: &Path) {
+    let root = match config.get_search_path(dir) {
+        Some(root) => root.get_path(),
+        None => return,
+    };
+    let mut dir = dir;
+    while dir != root && dir.starts_with(root) {
+        if fs::remove_dir(dir).is_err() {
+            break;
+        }
+        dir = match dir.parent() {
+            Some(dir) => dir,
+            None => break,
+        };
+    }
+}
+
+/// Moves the song file with its lyrics files, lyrics files are found next
+/// to the song by its name
+fn move_song(from: &Path, to: &Path) -> Result<()> {
+    move_file(from, to)?;
+    for ext in ["lrc", "txt"] {
+        let from = from.with_extension(ext);
+        let to = to.with_extension(ext);
+        if from.is_file() && !to.exists() {
+            _ = move_file(&from, &to);
+        }
+    }
+    Ok(())
+}
+
+/// Gets key of the path used to compare paths case-insensitively
+fn path_key(path: &Path) -> String {
+    path.to_string_lossy().to_lowercase()
+}
+
+/// Moves file, when it can't be renamed, such as between drives, it's
+/// copied and removed
+fn move_file(from: &Path, to: &Path) -> Result<()> {
+    if fs::rename(from, to).is_err() {
+        fs::copy(from, to)?;
+        fs::remove_file(from)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,451 @@
+use std::{fmt::Display, iter::Peekable, str::CharIndices, time::Duration};
+
+use super::{search::normalize, Song};
+
+/// Query filtering songs, such as
+/// `artist:"Daft Punk" year:>=2000 genre:house length:<5m !is:deleted`.
+/// Words without known field are searched in all the text fields.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Query {
+    /// Words searched in all the text fields
+    text: Vec<String>,
+    /// Conditions the songs must satisfy
+    filters: Vec<Filter>,
+}
+
+/// Error of parsing [`Query`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryError {
+    /// Position of the error in the query, in characters
+    pub pos: usize,
+    /// Description of the error
+    pub msg: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Filter {
+    /// When true, songs must not satisfy the condition
+    negate: bool,
+    cond: Cond,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Cond {
+    /// Text field contains the value, or is equal to it
+    Text(TextField, bool, String),
+    Year(Cmp, i32),
+    Length(Cmp, Duration),
+    Flag(Flag),
+    /// Any text field contains the word
+    Word(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TextField {
+    Name,
+    Artist,
+    AlbumArtist,
+    Album,
+    Genre,
+    Composer,
+    Comment,
+    Path,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Cmp {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Flag {
+    Deleted,
+    Offline,
+    Duplicate,
+}
+
+impl Query {
+    /// Parses the given query
+    pub fn parse(query: &str) -> Result<Self, QueryError> {
+        Parser {
+            chars: query.char_indices().peekable(),
+            src: query,
+        }
+        .parse()
+    }
+
+    /// Gets words searched in all the text fields, None when there are no
+    /// such words
+    pub fn get_text(&self) -> Option<String> {
+        (!self.text.is_empty()).then(|| self.text.join(" "))
+    }
+
+    /// Checks whether the query filters by flags of songs hidden in the
+    /// library, deleted songs and duplicates
+    pub fn filters_hidden(&self) -> bool {
+        self.filters.iter().any(|f| {
+            matches!(f.cond, Cond::Flag(Flag::Deleted | Flag::Duplicate))
+        })
+    }
+
+    /// Checks whether song satisfies the query filters. Words searched in
+    /// all the fields aren't checked, they're matched by the search index.
+    pub fn matches(&self, song: &Song) -> bool {
+        self.filters
+            .iter()
+            .all(|f| f.cond.matches(song) != f.negate)
+    }
+}
+
+impl Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (at position {})", self.msg, self.pos + 1)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+//>=========================================================================<//
+//                               Query parsing                               //
+//>=========================================================================<//
+
+struct Parser<'a> {
+    chars: Peekable<CharIndices<'a>>,
+    src: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    /// Parses the whole query
+    fn parse(mut self) -> Result<Query, QueryError> {
+        let mut query = Query::default();
+        loop {
+            self.skip_whitespace();
+            let pos = match self.chars.peek() {
+                Some(&(pos, _)) => pos,
+                None => return Ok(query),
+            };
+
+            let negate = self.chars.next_if(|(_, c)| *c == '!').is_some();
+            let quoted = matches!(self.chars.peek(), Some((_, '"')));
+            let mut word = self.value(true)?;
+            let colon = !quoted && matches!(self.chars.peek(), Some((_, ':')));
+
+            let cond = if colon && Parser::is_field(&word) {
+                self.chars.next();
+                self.field(&word, pos)?
+            } else if colon {
+                // Colon after other word is part of it, such as `12:30`
+                word.push_str(&self.value(false)?);
+                if negate {
+                    Cond::Word(normalize(&word))
+                } else {
+                    query.text.push(word);
+                    continue;
+                }
+            } else if word.is_empty() {
+                return Err(self.error(pos, "Missing word after '!'"));
+            } else if negate {
+                Cond::Word(normalize(&word))
+            } else {
+                query.text.push(word);
+                continue;
+            };
+            query.filters.push(Filter { negate, cond });
+        }
+    }
+
+    /// Parses condition on the field with the given name
+    fn field(&mut self, name: &str, pos: usize) -> Result<Cond, QueryError> {
+        let cmp_pos = self.pos();
+        let cmp = self.cmp();
+        let val_pos = self.pos();
+        let value = self.value(false)?;
+        if value.is_empty() {
+            return Err(self.error(val_pos, "Missing value"));
+        }
+
+        if let Some(field) = TextField::parse(name) {
+            return match cmp {
+                None => Ok(Cond::Text(field, false, normalize(&value))),
+                Some(Cmp::Eq) => {
+                    Ok(Cond::Text(field, true, normalize(&value)))
+                }
+                Some(_) => Err(self.error(
+                    cmp_pos,
+                    &format!("Field '{name}' can only be compared with '='"),
+                )),
+            };
+        }
+
+        if name.eq_ignore_ascii_case("is") {
+            return match (cmp, Flag::parse(&value)) {
+                (None, Some(flag)) => Ok(Cond::Flag(flag)),
+                (Some(_), _) => Err(self.error(
+                    cmp_pos,
+                    "Flags can't be compared, expected for example is:deleted",
+                )),
+                (None, None) => {
+                    Err(self
+                        .error(val_pos, &format!("Unknown flag '{value}'")))
+                }
+            };
+        }
+
+        let cmp = cmp.unwrap_or(Cmp::Eq);
+        match name.to_lowercase().as_str() {
+            "year" => match value.parse() {
+                Ok(year) => Ok(Cond::Year(cmp, year)),
+                Err(_) => Err(self.error(val_pos, "Invalid year")),
+            },
+            "length" | "len" => match parse_length(&value) {
+                Some(len) => Ok(Cond::Length(cmp, len)),
+                None => Err(self.error(
+                    val_pos,
+                    "Invalid length, expected for example 90s, 5m or 3:30",
+                )),
+            },
+            _ => Err(self.error(pos, &format!("Unknown field '{name}'"))),
+        }
+    }
+
+    /// Checks whether the name is name of a field that can be used in
+    /// the query, such as `artist` or `is`
+    fn is_field(name: &str) -> bool {
+        TextField::parse(name).is_some()
+            || matches!(
+                name.to_lowercase().as_str(),
+                "year" | "length" | "len" | "is"
+            )
+    }
+
+    /// Parses comparison operator, None when there is no operator
+    fn cmp(&mut self) -> Option<Cmp> {
+        let (_, first) =
+            self.chars.next_if(|(_, c)| matches!(c, '<' | '>' | '='))?;
+        let eq =
+            first != '=' && self.chars.next_if(|(_, c)| *c == '=').is_some();
+        Some(match (first, eq) {
+            ('<', true) => Cmp::Le,
+            ('<', false) => Cmp::Lt,
+            ('>', true) => Cmp::Ge,
+            ('>', false) => Cmp::Gt,
+            _ => Cmp::Eq,
+        })
+    }
+
+    /// Parses quoted string or word ending with whitespace, when `key` is
+    /// true, the word also ends with ':'
+    fn value(&mut self, key: bool) -> Result<String, QueryError> {
+        let start = self.pos();
+        if self.chars.next_if(|(_, c)| *c == '"').is_none() {
+            let mut word = String::new();
+            while let Some((_, c)) = self
+                .chars
+                .next_if(|(_, c)| !c.is_whitespace() && (!key || *c != ':'))
+            {
+                word.push(c);
+            }
+            return Ok(word);
+        }
+
+        let mut value = String::new();
+        loop {
+            match self.chars.next() {
+                Some((_, '"')) => return Ok(value),
+                Some((_, '\\')) => match self.chars.next() {
+                    Some((_, c)) => value.push(c),
+                    None => break,
+                },
+                Some((_, c)) => value.push(c),
+                None => break,
+            }
+        }
+        Err(self.error(start, "Unterminated quote"))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+    }
+
+    /// Gets byte position of the next character
+    fn pos(&mut self) -> usize {
+        self.chars.peek().map_or(self.src.len(), |(p, _)| *p)
+    }
+
+    /// Creates error on the given byte position
+    fn error(&self, pos: usize, msg: &str) -> QueryError {
+        QueryError {
+            pos: self.src[..pos].chars().count(),
+            msg: msg.to_owned(),
+        }
+    }
+}
+
+/// Parses length such as `90s`, `5m`, `1h30m`, `3:30` or `1:02:03`,
+/// None when it's invalid or too long
+fn parse_length(value: &str) -> Option<Duration> {
+    if value.contains(':') {
+        let mut secs: u64 = 0;
+        for part in value.split(':') {
+            secs = secs.checked_mul(60)?.checked_add(part.parse().ok()?)?;
+        }
+        return Some(Duration::from_secs(secs));
+    }
+
+    let mut secs: u64 = 0;
+    let mut num = String::new();
+    for c in value.chars() {
+        if c.is_ascii_digit() {
+            num.push(c);
+            continue;
+        }
+        let unit = match c {
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return None,
+        };
+        let part = num.parse::<u64>().ok()?.checked_mul(unit)?;
+        secs = secs.checked_add(part)?;
+        num.clear();
+    }
+    // Number without unit is in seconds
+    if !num.is_empty() {
+        secs = secs.checked_add(num.parse().ok()?)?;
+    }
+    Some(Duration::from_secs(secs))
+}
+
+//>=========================================================================<//
+//                              Query matching                               //
+//>=========================================================================<//
+
+impl Cond {
+    /// Checks whether song satisfies the condition
+    fn matches(&self, song: &Song) -> bool {
+        match self {
+            Cond::Text(field, exact, value) => {
+                let text = normalize(&field.get(song));
+                if *exact {
+                    text == *value
+                } else {
+                    text.contains(value.as_str())
+                }
+            }
+            Cond::Year(cmp, year) => {
+                song.get_year().is_some_and(|y| cmp.matches(y, *year))
+            }
+            Cond::Length(cmp, len) => {
+                // Compared in seconds, lengths are shown in seconds
+                let secs = song.get_length().as_secs();
+                cmp.matches(secs, len.as_secs())
+            }
+            Cond::Flag(flag) => flag.matches(song),
+            Cond::Word(word) => [
+                TextField::Name,
+                TextField::Artist,
+                TextField::AlbumArtist,
+                TextField::Album,
+                TextField::Genre,
+                TextField::Composer,
+                TextField::Comment,
+                TextField::Path,
+            ]
+            .iter()
+            .any(|f| normalize(&f.get(song)).contains(word.as_str())),
+        }
+    }
+}
+
+impl TextField {
+    fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "title" | "name" => Some(TextField::Name),
+            "artist" => Some(TextField::Artist),
+            "albumartist" => Some(TextField::AlbumArtist),
+            "album" => Some(TextField::Album),
+            "genre" => Some(TextField::Genre),
+            "composer" => Some(TextField::Composer),
+            "comment" => Some(TextField::Comment),
+            "path" => Some(TextField::Path),
+            _ => None,
+        }
+    }
+
+    /// Gets value of the field of the given song
+    fn get(&self, song: &Song) -> String {
+        match self {
+            TextField::Name => song.get_name().to_owned(),
+            TextField::Artist => song.get_artist().to_owned(),
+            TextField::AlbumArtist => song.get_album_artist().to_owned(),
+            TextField::Album => song.get_album().to_owned(),
+            TextField::Genre => song.get_genre().to_owned(),
+            TextField::Composer => song.get_composer().to_owned(),
+            TextField::Comment => song.get_comment().to_owned(),
+            TextField::Path => song.get_path().to_string_lossy().into_owned(),
+        }
+    }
+}
+
+impl Cmp {
+    /// Compares the value with the value from the query
+    fn matches<T: PartialOrd>(&self, value: T, query: T) -> bool {
+        match self {
+            Cmp::Eq => value == query,
+            Cmp::Lt => value < query,
+            Cmp::Le => value <= query,
+            Cmp::Gt => value > query,
+            Cmp::Ge => value >= query,
+        }
+    }
+}
+
+impl Flag {
+    fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "deleted" => Some(Flag::Deleted),
+            "offline" => Some(Flag::Offline),
+            "duplicate" => Some(Flag::Duplicate),
+            _ => None,
+        }
+    }
+
+    fn matches(&self, song: &Song) -> bool {
+        match self {
+            Flag::Deleted => song.get_deleted(),
+            Flag::Offline => song.get_offline(),
+            Flag::Duplicate => song.get_duplicate_of().is_some(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overflowing_length() {
+        assert_eq!(parse_length("1h30m"), Some(Duration::from_secs(5400)));
+        assert_eq!(parse_length("99999999999999999h"), None);
+        assert_eq!(parse_length("99999999999999999:0:0"), None);
+        assert!(Query::parse("length:>99999999999999999h").is_err());
+    }
+
+    #[test]
+    fn flags_and_plain_words() {
+        let query = Query::parse("offline 12:30 !is:duplicate").unwrap();
+        assert_eq!(query.get_text().as_deref(), Some("offline 12:30"));
+        assert_eq!(
+            query.filters,
+            vec![Filter {
+                negate: true,
+                cond: Cond::Flag(Flag::Duplicate),
+            }]
+        );
+        assert!(Query::parse("is:nothing").is_err());
+    }
+}
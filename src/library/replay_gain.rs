@@ -0,0 +1,169 @@
+use std::path::Path;
+
+use eyre::{Report, Result};
+use id3::TagLike;
+use mp4ameta::{Data, FreeformIdent};
+use serde_derive::{Deserialize, Serialize};
+
+/// ReplayGain of a song read from its tags, gains are in dB
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReplayGain {
+    pub track_gain: Option<f32>,
+    pub track_peak: Option<f32>,
+    pub album_gain: Option<f32>,
+    pub album_peak: Option<f32>,
+}
+
+/// Which ReplayGain is applied when playing songs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum ReplayGainMode {
+    /// ReplayGain isn't applied
+    #[default]
+    Off,
+    /// Songs have the same loudness
+    Track,
+    /// Albums have the same loudness, songs of an album keep their
+    /// loudness relative to each other
+    Album,
+    /// Album gain is used when the songs around the playing song in the
+    /// playlist are from the same album, otherwise track gain is used
+    Auto,
+}
+
+impl ReplayGain {
+    /// Maximal preamp in dB, both positive and negative
+    pub const MAX_PREAMP: f32 = 15.;
+
+    /// Gets volume multiplier of the song in the given mode. When `album`
+    /// is true, the song is played as part of its album in auto mode.
+    /// Preamp in dB is added to the gain, when `prevent_clipping` is true
+    /// the gain is lowered so that the song peak doesn't clip.
+    pub fn get_volume(
+        &self,
+        mode: ReplayGainMode,
+        album: bool,
+        preamp: f32,
+        prevent_clipping: bool,
+    ) -> f32 {
+        let track = (self.track_gain, self.track_peak);
+        let (gain, peak) = match mode {
+            ReplayGainMode::Off => return 1.,
+            ReplayGainMode::Track => track,
+            ReplayGainMode::Album => self.album(),
+            ReplayGainMode::Auto if album => self.album(),
+            ReplayGainMode::Auto => track,
+        };
+        // Songs without ReplayGain are played as they are
+        let gain = match gain {
+            Some(gain) => gain,
+            None => return 1.,
+        };
+
+        let volume = 10_f32.powf((gain + preamp) / 20.);
+        match peak {
+            Some(peak) if prevent_clipping && peak > 0. => {
+                volume.min(1. / peak)
+            }
+            _ => volume,
+        }
+    }
+
+    /// Sets ReplayGain value from the tag with the given name, such as
+    /// `REPLAYGAIN_TRACK_GAIN` with value `-6.5 dB`. Returns false when
+    /// the tag isn't ReplayGain tag.
+    pub fn set_tag(&mut self, name: &str, value: &str) -> bool {
+        let name = name.to_lowercase();
+        let field = if name.ends_with("replaygain_track_gain") {
+            &mut self.track_gain
+        } else if name.ends_with("replaygain_track_peak") {
+            &mut self.track_peak
+        } else if name.ends_with("replaygain_album_gain") {
+            &mut self.album_gain
+        } else if name.ends_with("replaygain_album_peak") {
+            &mut self.album_peak
+        } else {
+            return false;
+        };
+
+        let value = value.trim();
+        let value = value
+            .strip_suffix("dB")
+            .or(value.strip_suffix("db"))
+            .unwrap_or(value);
+        *field = value.trim().parse().ok().filter(|v: &f32| v.is_finite());
+        true
+    }
+
+    /// Writes the ReplayGain to the tags of the song file on the given
+    /// path, values that aren't set aren't written. Only MP3, FLAC and MP4
+    /// files are supported.
+    pub fn write_tags(&self, path: &Path) -> Result<()> {
+        let gain = |v: Option<f32>| v.map(|v| format!("{v:.2} dB"));
+        let peak = |v: Option<f32>| v.map(|v| format!("{v:.6}"));
+        let tags: Vec<(&str, String)> = [
+            ("REPLAYGAIN_TRACK_GAIN", gain(self.track_gain)),
+            ("REPLAYGAIN_TRACK_PEAK", peak(self.track_peak)),
+            ("REPLAYGAIN_ALBUM_GAIN", gain(self.album_gain)),
+            ("REPLAYGAIN_ALBUM_PEAK", peak(self.album_peak)),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| value.map(|v| (name, v)))
+        .collect();
+
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase());
+        match ext.as_deref() {
+            Some("mp3") => {
+                let mut tag = match id3::Tag::read_from_path(path) {
+                    Ok(tag) => tag,
+                    Err(e) if matches!(e.kind, id3::ErrorKind::NoTag) => {
+                        id3::Tag::new()
+                    }
+                    Err(e) => return Err(e.into()),
+                };
+                for (name, value) in tags {
+                    tag.add_frame(id3::frame::ExtendedText {
+                        description: name.to_owned(),
+                        value,
+                    });
+                }
+                tag.write_to_path(path, id3::Version::Id3v24)?;
+            }
+            Some("flac") => {
+                let mut tag = metaflac::Tag::read_from_path(path)?;
+                for (name, value) in tags {
+                    tag.set_vorbis(name, vec![value]);
+                }
+                tag.write_to_path(path)?;
+            }
+            Some("m4a" | "m4b" | "mp4") => {
+                let mut tag = mp4ameta::Tag::read_from_path(path)?;
+                for (name, value) in tags {
+                    let name = name.to_lowercase();
+                    tag.set_data(
+                        FreeformIdent::new("com.apple.iTunes", &name),
+                        Data::Utf8(value),
+                    );
+                }
+                tag.write_to_path(path)?;
+            }
+            _ => {
+                return Err(Report::msg(
+                    "Writing tags isn't supported for this file type",
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    /// Gets album gain and peak, track gain and peak when the song doesn't
+    /// have album gain
+    fn album(&self) -> (Option<f32>, Option<f32>) {
+        match self.album_gain {
+            Some(gain) => (Some(gain), self.album_peak),
+            None => (self.track_gain, self.track_peak),
+        }
+    }
+}
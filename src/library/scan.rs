@@ -0,0 +1,190 @@
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use eyre::{Report, Result};
+use serde_derive::{Deserialize, Serialize};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::gui::app::{LibMsg, Msg};
+
+use super::{ArtCache, Song, TagNormalizer};
+
+/// Statistics of library scan
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ScanStats {
+    /// Number of newly found songs
+    pub added: usize,
+    /// Number of songs that were reloaded, because their file changed
+    pub updated: usize,
+    /// Number of songs that are no longer found
+    pub removed: usize,
+}
+
+/// Progress of running library scan
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ScanProgress {
+    /// Number of song files found in the search paths
+    pub discovered: usize,
+    /// Number of song files that were already processed
+    pub processed: usize,
+    /// Number of song files that failed to load
+    pub failed: usize,
+}
+
+/// Song file that failed to load
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScanError {
+    /// Path to the song file
+    pub path: PathBuf,
+    /// Why the song file failed to load
+    pub error: String,
+}
+
+impl ScanError {
+    /// Creates new [`ScanError`] of file on the given path
+    pub fn new(path: PathBuf, error: Report) -> Self {
+        Self {
+            path,
+            error: error.to_string(),
+        }
+    }
+}
+
+/// Handle to a running library scan, used to report its progress and to
+/// cancel it. Clones share the same scan.
+#[derive(Clone, Default)]
+pub struct Scan {
+    state: Arc<ScanState>,
+}
+
+#[derive(Default)]
+struct ScanState {
+    discovered: AtomicUsize,
+    processed: AtomicUsize,
+    failed: AtomicUsize,
+    cancelled: AtomicBool,
+    /// When the progress was last reported
+    reported: Mutex<Option<Instant>>,
+    /// Sender the progress is reported to, when None progress isn't reported
+    sender: Option<UnboundedSender<Msg>>,
+}
+
+impl Scan {
+    /// Minimal time between two progress reports
+    const REPORT_INTERVAL: Duration = Duration::from_millis(100);
+
+    /// Creates new [`Scan`] reporting its progress to the given sender
+    pub fn new(sender: UnboundedSender<Msg>) -> Self {
+        Self {
+            state: Arc::new(ScanState {
+                sender: Some(sender),
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Cancels the scan
+    pub fn cancel(&self) {
+        self.state.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Checks whether the scan was cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.state.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Gets current progress of the scan
+    pub fn get_progress(&self) -> ScanProgress {
+        ScanProgress {
+            discovered: self.state.discovered.load(Ordering::Relaxed),
+            processed: self.state.processed.load(Ordering::Relaxed),
+            failed: self.state.failed.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Adds given number of discovered song files
+    pub fn discovered(&self, count: usize) {
+        self.state.discovered.fetch_add(count, Ordering::Relaxed);
+        self.report(false);
+    }
+
+    /// Marks one song file as processed
+    pub fn processed(&self, failed: bool) {
+        if failed {
+            self.state.failed.fetch_add(1, Ordering::Relaxed);
+        }
+        self.state.processed.fetch_add(1, Ordering::Relaxed);
+        self.report(false);
+    }
+
+    /// Reports the final progress of the scan
+    pub fn finish(&self) {
+        self.report(true);
+    }
+
+    /// Loads songs on the given paths in parallel. When cancelled, not all
+    /// songs are returned. Art of the songs is saved to the given cache.
+    pub fn load_songs(
+        &self,
+        paths: &[PathBuf],
+        art: &ArtCache,
+        norm: &TagNormalizer,
+    ) -> Vec<(PathBuf, Result<Song>)> {
+        let next = AtomicUsize::new(0);
+        let workers = thread::available_parallelism()
+            .map_or(1, |n| n.get())
+            .min(paths.len());
+
+        thread::scope(|s| {
+            let handles: Vec<_> = (0..workers)
+                .map(|_| {
+                    s.spawn(|| {
+                        let mut songs = Vec::new();
+                        while !self.is_cancelled() {
+                            let path = match paths
+                                .get(next.fetch_add(1, Ordering::Relaxed))
+                            {
+                                Some(path) => path,
+                                None => break,
+                            };
+                            let song = Song::load(path, art, norm);
+                            self.processed(song.is_err());
+                            songs.push((path.to_owned(), song));
+                        }
+                        songs
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().unwrap_or_default())
+                .collect()
+        })
+    }
+
+    /// Sends the progress to the app, when not forced, progress is sent
+    /// only when enough time passed since the last report
+    fn report(&self, force: bool) {
+        let sender = match &self.state.sender {
+            Some(sender) => sender,
+            None => return,
+        };
+
+        if let Ok(mut reported) = self.state.reported.lock() {
+            let now = Instant::now();
+            match *reported {
+                Some(r) if !force && now - r < Scan::REPORT_INTERVAL => return,
+                _ => *reported = Some(now),
+            }
+        }
+        _ = sender.send(Msg::Lib(LibMsg::LoadProgress(self.get_progress())));
+    }
+}
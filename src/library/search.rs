@@ -0,0 +1,289 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    ops::Bound,
+    sync::Arc,
+};
+
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+use super::{Song, SongId};
+
+/// Field of the song where a term was found
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Name,
+    Artist,
+    AlbumArtist,
+    Album,
+    Composer,
+    Genre,
+    Comment,
+    Path,
+}
+
+impl Field {
+    /// Gets how much a match in the field is worth
+    fn weight(&self) -> f32 {
+        match self {
+            Field::Name => 4.,
+            Field::Artist | Field::AlbumArtist => 3.,
+            Field::Album => 2.,
+            Field::Composer | Field::Genre => 1.,
+            Field::Comment | Field::Path => 0.5,
+        }
+    }
+}
+
+/// In-memory full-text index of songs, matching is case and diacritics
+/// insensitive and tolerates typos
+#[derive(Debug, Clone, Default)]
+pub struct SearchIndex {
+    /// Songs containing the term, with the most important field the term
+    /// is in
+    terms: HashMap<Arc<str>, HashMap<SongId, Field>>,
+    /// Terms of each song, used to remove the song from the index
+    songs: HashMap<SongId, Vec<Arc<str>>>,
+    /// Sorted terms, used to find terms by their prefix
+    sorted: BTreeSet<Arc<str>>,
+    /// Terms containing the pair of characters, used to find terms with
+    /// typos
+    bigrams: HashMap<(char, char), HashSet<Arc<str>>>,
+}
+
+impl SearchIndex {
+    /// Creates [`SearchIndex`] of the given songs
+    pub fn new(songs: &[Song]) -> Self {
+        let mut index = SearchIndex::default();
+        for song in songs {
+            index.insert(song);
+        }
+        index
+    }
+
+    /// Adds song to the index, replacing its previous terms
+    pub fn insert(&mut self, song: &Song) {
+        let id = song.get_id();
+        self.remove(id);
+
+        let path = song.get_path().to_string_lossy();
+        let fields = [
+            (Field::Name, song.get_name()),
+            (Field::Artist, song.get_artist()),
+            (Field::AlbumArtist, song.get_album_artist()),
+            (Field::Album, song.get_album()),
+            (Field::Composer, song.get_composer()),
+            (Field::Genre, song.get_genre()),
+            (Field::Comment, song.get_comment()),
+            (Field::Path, path.as_ref()),
+        ];
+
+        let mut terms = Vec::new();
+        for (field, text) in fields {
+            for term in tokenize(text) {
+                let term = match self.terms.get_key_value(term.as_str()) {
+                    Some((term, _)) => term.clone(),
+                    None => self.add_term(&term),
+                };
+                let songs = self.terms.entry(term.clone()).or_default();
+                match songs.get(&id) {
+                    Some(f) if f.weight() >= field.weight() => {}
+                    Some(_) => _ = songs.insert(id, field),
+                    None => {
+                        songs.insert(id, field);
+                        terms.push(term);
+                    }
+                }
+            }
+        }
+        self.songs.insert(id, terms);
+    }
+
+    /// Removes song with the given ID from the index
+    pub fn remove(&mut self, id: SongId) {
+        for term in self.songs.remove(&id).unwrap_or_default() {
+            if let Some(songs) = self.terms.get_mut(&term) {
+                songs.remove(&id);
+                if songs.is_empty() {
+                    self.remove_term(&term);
+                }
+            }
+        }
+    }
+
+    /// Finds songs matching all the words in the query. Returns IDs of the
+    /// songs with their score, ordered from the best match.
+    pub fn search(&self, query: &str) -> Vec<(SongId, f32)> {
+        let mut tokens: Vec<String> = tokenize(query).collect();
+        tokens.sort();
+        tokens.dedup();
+
+        let mut scores: Option<HashMap<SongId, f32>> = None;
+        for token in tokens {
+            let chars: Vec<char> = token.chars().collect();
+            let mut matches: HashMap<SongId, f32> = HashMap::new();
+            for term in self.candidates(&token, &chars) {
+                let songs = match self.terms.get(term) {
+                    Some(songs) => songs,
+                    None => continue,
+                };
+                let score = match_score(&chars, term);
+                if score <= 0. {
+                    continue;
+                }
+                for (&id, field) in songs {
+                    let score = score * field.weight();
+                    let best = matches.entry(id).or_default();
+                    *best = best.max(score);
+                }
+            }
+
+            scores = Some(match scores {
+                None => matches,
+                Some(scores) => scores
+                    .into_iter()
+                    .filter_map(|(id, s)| {
+                        matches.get(&id).map(|m| (id, s + m))
+                    })
+                    .collect(),
+            });
+        }
+
+        let mut res: Vec<(SongId, f32)> =
+            scores.unwrap_or_default().into_iter().collect();
+        res.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        res
+    }
+
+    /// Finds terms that can match the token, so that not all the terms
+    /// are compared with it. Terms starting with the token and terms
+    /// sharing enough pairs of characters with it to be within the allowed
+    /// number of typos are found.
+    fn candidates<'a>(&'a self, token: &str, chars: &[char]) -> Vec<&'a str> {
+        let mut res: Vec<&str> = self
+            .sorted
+            .range::<str, _>((Bound::Included(token), Bound::Unbounded))
+            .take_while(|t| t.starts_with(token))
+            .map(|t| t.as_ref())
+            .collect();
+
+        let typos = max_typos(chars.len());
+        if typos == 0 {
+            return res;
+        }
+        let pairs: HashSet<(char, char)> =
+            chars.windows(2).map(|w| (w[0], w[1])).collect();
+        // Each typo breaks at most two pairs of characters
+        let min = pairs.len().saturating_sub(2 * typos).max(1);
+
+        let mut shared: HashMap<&str, usize> = HashMap::new();
+        for terms in pairs.iter().filter_map(|p| self.bigrams.get(p)) {
+            for term in terms {
+                *shared.entry(term.as_ref()).or_default() += 1;
+            }
+        }
+        res.extend(
+            shared
+                .into_iter()
+                .filter(|(t, n)| *n >= min && !t.starts_with(token))
+                .map(|(t, _)| t),
+        );
+        res
+    }
+
+    /// Adds new term to the indexes of terms
+    fn add_term(&mut self, term: &str) -> Arc<str> {
+        let term: Arc<str> = Arc::from(term);
+        self.sorted.insert(term.clone());
+        for pair in bigrams(&term) {
+            self.bigrams.entry(pair).or_default().insert(term.clone());
+        }
+        term
+    }
+
+    /// Removes term that no song contains anymore
+    fn remove_term(&mut self, term: &str) {
+        self.terms.remove(term);
+        self.sorted.remove(term);
+        for pair in bigrams(term) {
+            if let Some(terms) = self.bigrams.get_mut(&pair) {
+                terms.remove(term);
+                if terms.is_empty() {
+                    self.bigrams.remove(&pair);
+                }
+            }
+        }
+    }
+}
+
+/// Normalizes text for searching, diacritics are removed and text is
+/// converted to lowercase
+pub fn normalize(text: &str) -> String {
+    text.nfkd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Splits text to normalized words
+fn tokenize(text: &str) -> impl Iterator<Item = String> {
+    normalize(text)
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(|t| t.to_owned())
+        .collect::<Vec<_>>()
+        .into_iter()
+}
+
+/// Gets how well the query token matches the term, 0 when it doesn't
+/// match. Term can also be matched by its prefix, so that words can be
+/// searched before they're written whole.
+fn match_score(token: &[char], term: &str) -> f32 {
+    let term: Vec<char> = term.chars().collect();
+    if term == token {
+        return 1.;
+    }
+    if term.starts_with(token) {
+        return 0.8;
+    }
+
+    let max = max_typos(token.len());
+    if max == 0 {
+        return 0.;
+    }
+    let prefix = &term[..term.len().min(token.len())];
+    let dist = distance(token, &term).min(distance(token, prefix) + 1);
+    if dist > max {
+        return 0.;
+    }
+    0.6 - 0.2 * (dist - 1) as f32
+}
+
+/// Gets number of typos allowed in a token of the given length, longer
+/// words can contain more typos
+fn max_typos(len: usize) -> usize {
+    match len {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+/// Gets pairs of adjacent characters of the term
+fn bigrams(term: &str) -> impl Iterator<Item = (char, char)> + '_ {
+    term.chars().zip(term.chars().skip(1))
+}
+
+/// Gets Levenshtein distance between the given words
+fn distance(a: &[char], b: &[char]) -> usize {
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut cur = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        cur[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let sub = prev[j] + usize::from(ca != cb);
+            cur[j + 1] = sub.min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+    prev[b.len()]
+}
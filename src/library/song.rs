@@ -1,11 +1,31 @@
-use audiotags::Tag;
-use eyre::Result;
-use raplay::source::{Source, Symph};
+use eyre::{Report, Result};
+use log::warn;
 use serde_derive::{Deserialize, Serialize};
-use std::{fs::File, path::PathBuf, time::Duration};
+use std::{
+    collections::BTreeSet,
+    fs::{self, File, Metadata},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use symphonia::{
+    core::{
+        codecs::CODEC_TYPE_NULL,
+        io::MediaSourceStream,
+        meta::{self, StandardTagKey, StandardVisualKey},
+        probe::{Hint, ProbeResult},
+    },
+    default::get_probe,
+};
+
+use super::{
+    ArtCache, ArtId, Loudness, ReplayGain, TagField, TagNormalizer, TagPattern,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Song {
+    /// Unique ID of the song in the library
+    #[serde(default)]
+    id: SongId,
     /// Path to the song
     path: PathBuf,
     ///  Name of the song
@@ -15,43 +35,167 @@ pub struct Song {
     /// Song album
     album: String,
     /// Song release year
-    year: i32,
+    year: Option<i32>,
     /// Song length
     length: Duration,
     /// Song genre
     genre: String,
+    /// Artist of the album, empty when not set
+    #[serde(default)]
+    album_artist: String,
+    /// Number of the song on its disc
+    #[serde(default)]
+    track: Option<u16>,
+    /// Number of songs on the disc
+    #[serde(default)]
+    track_total: Option<u16>,
+    /// Number of the disc of the album the song is on
+    #[serde(default)]
+    disc: Option<u16>,
+    /// Number of discs of the album
+    #[serde(default)]
+    disc_total: Option<u16>,
+    /// Composer of the song, empty when not set
+    #[serde(default)]
+    composer: String,
+    /// Comment of the song, empty when not set
+    #[serde(default)]
+    comment: String,
+    /// Release date as saved in the tags, such as `2001-03-12`
+    #[serde(default)]
+    date: Option<String>,
+    /// Cover art embedded in the song file
+    #[serde(default)]
+    art: Option<ArtId>,
+    /// ReplayGain of the song
+    #[serde(default)]
+    replay_gain: ReplayGain,
+    /// Loudness measured by analysis of the song
+    #[serde(default)]
+    loudness: Option<Loudness>,
+    /// Art image from the directory of the song, set only when it's used
+    /// instead of the embedded art
+    #[serde(default)]
+    folder_art: Option<ArtId>,
     /// When true song is deleted
     deleted: bool,
+    /// Time since when the song is deleted
+    #[serde(default)]
+    deleted_since: Option<SystemTime>,
+    /// Fingerprint of the song file when it was loaded
+    #[serde(default)]
+    fingerprint: Fingerprint,
+    /// Time when the song was added to the library
+    #[serde(default)]
+    added: Option<SystemTime>,
+    /// When true song is in search path that can't be reached
+    #[serde(skip)]
+    offline: bool,
+    /// ID of the song this song is duplicate of, duplicates are hidden
+    #[serde(default)]
+    duplicate_of: Option<SongId>,
+    /// Fields that are missing in the tags and were inferred from the path
+    /// of the song
+    #[serde(default)]
+    inferred: BTreeSet<TagField>,
+    /// Fields whose values were normalized and differ from the tags
+    #[serde(default)]
+    normalized: BTreeSet<TagField>,
+}
+
+/// Unique ID of a song in the library, unlike the position of the song in
+/// the library it doesn't change when other songs are added or removed
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+)]
+#[serde(transparent)]
+pub struct SongId(u64);
+
+/// Fingerprint of a file, used to detect changes between library scans
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize,
+)]
+pub struct Fingerprint {
+    /// Last modification time of the file
+    modified: Option<SystemTime>,
+    /// Size of the file in bytes
+    size: u64,
 }
 
 impl Song {
-    pub fn load(path: &PathBuf) -> Result<Self> {
-        let tag = Tag::new().read_from_path(path)?;
+    /// Loads song from the file on the given path, its embedded cover art
+    /// is saved to the given art cache. Tag values are normalized by the
+    /// given normalizer. The file is probed once for its length, tags and
+    /// art.
+    pub fn load(
+        path: &Path,
+        art: &ArtCache,
+        norm: &TagNormalizer,
+    ) -> Result<Self> {
+        let mut probe = Song::probe_file(path)?;
+        if !Song::has_audio(&probe) {
+            return Err(Report::msg("File doesn't contain audio"));
+        }
 
+        let tags = Song::read_tags(&mut probe);
         let mut song = Self {
             path: path.to_path_buf(),
-            name: tag.title().unwrap_or("-").to_owned(),
-            artist: tag.artist().unwrap_or("-").to_owned(),
-            album: tag.album_title().unwrap_or("-").to_owned(),
-            year: tag.year().unwrap_or(i32::MAX),
-            length: Duration::from_secs_f64(tag.duration().unwrap_or(0.0)),
-            genre: tag.genre().unwrap_or("-").to_owned(),
+            name: "-".to_owned(),
+            artist: "-".to_owned(),
+            album: "-".to_owned(),
+            genre: "-".to_owned(),
             deleted: false,
+            fingerprint: Fingerprint::load(path).unwrap_or_default(),
+            ..Default::default()
         };
-        _ = song.set_length_symph();
+        song.set_tags(&tags);
+        song.set_length(&probe);
+        song.set_art(&mut probe, art);
+        song.normalize(norm);
 
         Ok(song)
     }
 
+    /// Checks whether the file on the given path contains audio, by probing
+    /// its content
+    pub fn probe(path: &Path) -> bool {
+        Song::probe_file(path).is_ok_and(|p| Song::has_audio(&p))
+    }
+
     //>=====================================================================<//
     //                           Getters & Setters                           //
     //>=====================================================================<//
 
+    /// Gets song ID
+    pub fn get_id(&self) -> SongId {
+        self.id
+    }
+
+    /// Sets song ID
+    pub fn set_id(&mut self, id: SongId) {
+        self.id = id;
+    }
+
     /// Gets song path
     pub fn get_path(&self) -> &PathBuf {
         &self.path
     }
 
+    /// Sets song path, used when the song file is moved
+    pub fn set_path(&mut self, path: PathBuf) {
+        self.path = path;
+    }
+
     /// Gets song name
     pub fn get_name(&self) -> &str {
         &self.name
@@ -68,19 +212,105 @@ impl Song {
     }
 
     /// Gets year the song was released in
-    pub fn get_year(&self) -> i32 {
+    pub fn get_year(&self) -> Option<i32> {
         self.year
     }
 
     /// Gets year string the song was released in, if no year returns '-'
     pub fn get_year_str(&self) -> String {
-        if self.get_year() == i32::MAX {
-            "-".to_owned()
-        } else {
-            self.get_year().to_string()
+        match self.year {
+            Some(year) => year.to_string(),
+            None => "-".to_owned(),
+        }
+    }
+
+    /// Gets release date as saved in the tags
+    pub fn get_date(&self) -> Option<&str> {
+        self.date.as_deref()
+    }
+
+    /// Gets artist of the album, empty when not set
+    pub fn get_album_artist(&self) -> &str {
+        &self.album_artist
+    }
+
+    /// Gets number of the song on its disc
+    pub fn get_track(&self) -> Option<u16> {
+        self.track
+    }
+
+    /// Gets number of songs on the disc
+    pub fn get_track_total(&self) -> Option<u16> {
+        self.track_total
+    }
+
+    /// Gets number of the disc the song is on
+    pub fn get_disc(&self) -> Option<u16> {
+        self.disc
+    }
+
+    /// Gets number of discs of the album
+    pub fn get_disc_total(&self) -> Option<u16> {
+        self.disc_total
+    }
+
+    /// Gets composer of the song, empty when not set
+    pub fn get_composer(&self) -> &str {
+        &self.composer
+    }
+
+    /// Gets comment of the song, empty when not set
+    pub fn get_comment(&self) -> &str {
+        &self.comment
+    }
+
+    /// Gets handle of the cover art of the song, it's either embedded in
+    /// the song or from the song directory
+    pub fn get_art(&self) -> Option<ArtId> {
+        self.folder_art.or(self.art)
+    }
+
+    /// Sets art from the directory of the song, it's used when the song
+    /// doesn't have embedded art or when `prefer` is true
+    pub fn set_folder_art(&mut self, art: Option<ArtId>, prefer: bool) {
+        self.folder_art = art.filter(|_| prefer || self.art.is_none());
+    }
+
+    /// Gets ReplayGain of the song, when the song doesn't have track gain
+    /// in its tags, track gain from its measured loudness is used
+    pub fn get_replay_gain(&self) -> ReplayGain {
+        match self.loudness {
+            Some(loudness) if self.replay_gain.track_gain.is_none() => {
+                let measured = loudness.get_replay_gain();
+                ReplayGain {
+                    track_gain: measured.track_gain,
+                    track_peak: measured.track_peak,
+                    ..self.replay_gain
+                }
+            }
+            _ => self.replay_gain,
         }
     }
 
+    /// Gets measured loudness of the song
+    pub fn get_loudness(&self) -> Option<Loudness> {
+        self.loudness
+    }
+
+    /// Sets measured loudness of the song
+    pub fn set_loudness(&mut self, loudness: Option<Loudness>) {
+        self.loudness = loudness;
+    }
+
+    /// Checks whether the song should have its loudness analyzed, it
+    /// doesn't have track gain or measured loudness and can be played
+    pub fn needs_analysis(&self) -> bool {
+        self.loudness.is_none()
+            && self.replay_gain.track_gain.is_none()
+            && !self.deleted
+            && !self.offline
+    }
+
     /// Gets song length
     pub fn get_length(&self) -> &Duration {
         &self.length
@@ -108,14 +338,213 @@ impl Song {
         }
     }
 
-    /// Sets song length using symph
-    fn set_length_symph(&mut self) -> Result<()> {
-        let file = File::open(&self.path)?;
-        let symph = Symph::try_new(file, &Default::default())?;
-        if let Some(t) = symph.get_time() {
-            self.length = t.total;
+    /// Reads tags of the probed file, metadata can be both before the
+    /// container and inside it
+    fn read_tags(probe: &mut ProbeResult) -> Vec<meta::Tag> {
+        let mut tags = Vec::new();
+        if let Some(mut meta) = probe.metadata.get() {
+            if let Some(rev) = meta.skip_to_latest() {
+                tags.extend_from_slice(rev.tags());
+            }
+        }
+        if let Some(rev) = probe.format.metadata().skip_to_latest() {
+            tags.extend_from_slice(rev.tags());
+        }
+        tags
+    }
+
+    /// Saves cover art of the probed file to the art cache, front cover is
+    /// preferred when the file contains more pictures
+    fn set_art(&mut self, probe: &mut ProbeResult, cache: &ArtCache) {
+        let mut visuals = Vec::new();
+        if let Some(meta) = probe.metadata.get() {
+            if let Some(rev) = meta.current() {
+                visuals.extend_from_slice(rev.visuals());
+            }
+        }
+        if let Some(rev) = probe.format.metadata().current() {
+            visuals.extend_from_slice(rev.visuals());
+        }
+
+        let visual = match visuals
+            .iter()
+            .find(|v| v.usage == Some(StandardVisualKey::FrontCover))
+            .or(visuals.first())
+        {
+            Some(visual) => visual,
+            None => return,
+        };
+        match cache.save(&visual.data) {
+            Ok(id) => self.art = Some(id),
+            Err(e) => {
+                warn!("Failed to save art of {}: {e}", self.path.display())
+            }
+        }
+    }
+
+    /// Sets song data from the given symphonia tags. Date, comment and
+    /// totals are taken from their first tag.
+    fn set_tags(&mut self, tags: &[meta::Tag]) {
+        for tag in tags {
+            let value = tag.value.to_string();
+            if self.replay_gain.set_tag(&tag.key, &value) {
+                continue;
+            }
+            match tag.std_key {
+                Some(StandardTagKey::TrackTitle) => self.name = value,
+                Some(StandardTagKey::Artist) => self.artist = value,
+                Some(StandardTagKey::Album) => self.album = value,
+                Some(StandardTagKey::Genre) => self.genre = value,
+                Some(StandardTagKey::AlbumArtist) => self.album_artist = value,
+                Some(StandardTagKey::Composer) => self.composer = value,
+                Some(StandardTagKey::TrackNumber) => {
+                    self.track = Song::parse_number(&value);
+                    if self.track_total.is_none() {
+                        self.track_total = Song::parse_total(&value);
+                    }
+                }
+                Some(StandardTagKey::DiscNumber) => {
+                    self.disc = Song::parse_number(&value);
+                    if self.disc_total.is_none() {
+                        self.disc_total = Song::parse_total(&value);
+                    }
+                }
+                Some(StandardTagKey::Date | StandardTagKey::ReleaseDate)
+                    if self.date.is_none() =>
+                {
+                    self.date = Some(value)
+                }
+                Some(StandardTagKey::Comment) if self.comment.is_empty() => {
+                    self.comment = value
+                }
+                Some(StandardTagKey::TrackTotal)
+                    if self.track_total.is_none() =>
+                {
+                    self.track_total = Song::parse_number(&value)
+                }
+                Some(StandardTagKey::DiscTotal)
+                    if self.disc_total.is_none() =>
+                {
+                    self.disc_total = Song::parse_number(&value)
+                }
+                _ => {}
+            }
+        }
+
+        // Year is the start of the date
+        if self.year.is_none() {
+            self.year = self
+                .date
+                .as_ref()
+                .and_then(|d| d.get(..4))
+                .and_then(|y| y.parse().ok());
+        }
+    }
+
+    /// Normalizes values of the text fields by the given normalizer. Only
+    /// text stored as Latin-1 is decoded again.
+    fn normalize(&mut self, norm: &TagNormalizer) {
+        let latin1 = norm.read_latin1(&self.path);
+        // Audiotags and symphonia don't read ID3v1 tags
+        let mut from_v1 = BTreeSet::new();
+        for (field, value) in latin1.get_v1() {
+            if field.get(self).is_empty() {
+                self.set_field(*field, value);
+                from_v1.insert(*field);
+            }
+        }
+
+        for field in [
+            TagField::Title,
+            TagField::Artist,
+            TagField::Album,
+            TagField::AlbumArtist,
+            TagField::Genre,
+            TagField::Composer,
+            TagField::Comment,
+        ] {
+            let value = field.get(self);
+            if value.is_empty() {
+                continue;
+            }
+            let stored =
+                from_v1.contains(&field) || latin1.is_latin1(field, &value);
+            let normalized = norm.normalize(field, &value, stored);
+            if normalized != value {
+                self.set_normalized(field, &normalized);
+            }
+        }
+    }
+
+    /// Sets value of the field, empty value sets it as missing in the tags
+    fn set_field(&mut self, field: TagField, value: &str) {
+        let text = |v: &str| match v {
+            "" => "-".to_owned(),
+            v => v.to_owned(),
+        };
+        match field {
+            TagField::Title => self.name = text(value),
+            TagField::Artist => self.artist = text(value),
+            TagField::Album => self.album = text(value),
+            TagField::Genre => self.genre = text(value),
+            TagField::AlbumArtist => self.album_artist = value.to_owned(),
+            TagField::Composer => self.composer = value.to_owned(),
+            TagField::Comment => self.comment = value.to_owned(),
+            TagField::Year => self.year = value.parse().ok(),
+            TagField::Date => {
+                self.date = Some(value.to_owned()).filter(|d| !d.is_empty())
+            }
+            TagField::Track => self.track = value.parse().ok(),
+            TagField::TrackTotal => self.track_total = value.parse().ok(),
+            TagField::Disc => self.disc = value.parse().ok(),
+            TagField::DiscTotal => self.disc_total = value.parse().ok(),
+        }
+    }
+
+    /// Parses track or disc number, the number can be saved with total
+    /// count, such as `3/12`
+    fn parse_number(value: &str) -> Option<u16> {
+        value.split('/').next()?.trim().parse().ok()
+    }
+
+    /// Parses total count saved with track or disc number, such as `3/12`
+    fn parse_total(value: &str) -> Option<u16> {
+        value.split_once('/')?.1.trim().parse().ok()
+    }
+
+    /// Probes format of the file on the given path
+    fn probe_file(path: &Path) -> Result<ProbeResult> {
+        let file = File::open(path)?;
+        let stream =
+            MediaSourceStream::new(Box::new(file), Default::default());
+        Ok(get_probe().format(
+            &Hint::new(),
+            stream,
+            &Default::default(),
+            &Default::default(),
+        )?)
+    }
+
+    /// Checks whether probed file contains audio track
+    fn has_audio(probe: &ProbeResult) -> bool {
+        probe
+            .format
+            .default_track()
+            .is_some_and(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+    }
+
+    /// Sets song length from the default track of the probed file
+    fn set_length(&mut self, probe: &ProbeResult) {
+        let params = match probe.format.default_track() {
+            Some(track) => &track.codec_params,
+            None => return,
+        };
+        if let (Some(base), Some(frames)) = (params.time_base, params.n_frames)
+        {
+            let time = base.calc_time(frames);
+            self.length = Duration::from_secs(time.seconds)
+                + Duration::from_secs_f64(time.frac);
         }
-        Ok(())
     }
 
     /// Gets genre
@@ -131,6 +560,123 @@ impl Song {
     /// Sets whether song is deleted
     pub fn set_deleted(&mut self, deleted: bool) {
         self.deleted = deleted;
+        if !deleted {
+            self.deleted_since = None;
+        } else if self.deleted_since.is_none() {
+            self.deleted_since = Some(SystemTime::now());
+        }
+    }
+
+    /// Gets time since when the song is deleted, None when not deleted
+    pub fn get_deleted_since(&self) -> Option<SystemTime> {
+        self.deleted_since
+    }
+
+    /// Gets fingerprint of the song file from the time it was loaded
+    pub fn get_fingerprint(&self) -> &Fingerprint {
+        &self.fingerprint
+    }
+
+    /// Gets time when the song was added to the library, None for songs
+    /// added by older versions
+    pub fn get_added(&self) -> Option<SystemTime> {
+        self.added
+    }
+
+    /// Sets time when the song was added to the library
+    pub fn set_added(&mut self, added: Option<SystemTime>) {
+        self.added = added;
+    }
+
+    /// Gets whether song is in search path that can't be reached
+    pub fn get_offline(&self) -> bool {
+        self.offline
+    }
+
+    /// Sets whether song is in search path that can't be reached
+    pub fn set_offline(&mut self, offline: bool) {
+        self.offline = offline;
+    }
+
+    /// Gets ID of the song this song is duplicate of
+    pub fn get_duplicate_of(&self) -> Option<SongId> {
+        self.duplicate_of
+    }
+
+    /// Sets ID of the song this song is duplicate of
+    pub fn set_duplicate_of(&mut self, id: Option<SongId>) {
+        self.duplicate_of = id;
+    }
+
+    /// Gets fields that were inferred from the path of the song
+    pub fn get_inferred(&self) -> &BTreeSet<TagField> {
+        &self.inferred
+    }
+
+    /// Checks whether the field was inferred from the path of the song
+    pub fn is_inferred(&self, field: TagField) -> bool {
+        self.inferred.contains(&field)
+    }
+
+    /// Infers missing tags from the song path relative to its search path,
+    /// by the first matching pattern. Previously inferred values are
+    /// replaced. Returns whether any value changed.
+    pub fn infer_tags(
+        &mut self,
+        path: &Path,
+        patterns: &[TagPattern],
+    ) -> bool {
+        let old: Vec<_> =
+            self.inferred.iter().map(|&f| (f, f.get(self))).collect();
+        for field in std::mem::take(&mut self.inferred) {
+            self.set_field(field, "");
+        }
+
+        let values = patterns.iter().find_map(|p| p.infer(path));
+        for (field, value) in values.unwrap_or_default() {
+            if field.get(self).is_empty() {
+                self.set_field(field, &value);
+                self.inferred.insert(field);
+            }
+        }
+
+        let new: Vec<_> =
+            self.inferred.iter().map(|&f| (f, f.get(self))).collect();
+        old != new
+    }
+
+    /// Gets fields whose values were normalized and differ from the tags
+    pub fn get_normalized(&self) -> &BTreeSet<TagField> {
+        &self.normalized
+    }
+
+    /// Sets normalized value of the field, the value is marked as
+    /// different from the tags
+    pub fn set_normalized(&mut self, field: TagField, value: &str) {
+        self.set_field(field, value);
+        self.normalized.insert(field);
+    }
+
+    /// Resets the fingerprint, so that the song is loaded again by the
+    /// next library update
+    pub fn reset_fingerprint(&mut self) {
+        self.fingerprint = Fingerprint::default();
+    }
+
+    /// Upgrades song loaded from library saved by the given older version
+    pub fn upgrade(&mut self, version: u32) {
+        if version < 1 {
+            // Songs without year had i32::MAX as year
+            if self.year == Some(i32::MAX) {
+                self.year = None;
+            }
+        }
+        if version < 4 {
+            // Song is loaded again by the next scan to read the new tags,
+            // its art and ReplayGain, the catalog and the default sort
+            // need its album artist, track and disc
+            self.reset_fingerprint();
+        }
     }
 }
 
@@ -138,6 +684,7 @@ impl Song {
 impl Default for Song {
     fn default() -> Self {
         Self {
+            id: Default::default(),
             path: Default::default(),
             name: "Not playing".to_owned(),
             artist: Default::default(),
@@ -145,7 +692,59 @@ impl Default for Song {
             year: Default::default(),
             length: Default::default(),
             genre: Default::default(),
+            album_artist: Default::default(),
+            track: None,
+            track_total: None,
+            disc: None,
+            disc_total: None,
+            composer: Default::default(),
+            comment: Default::default(),
+            date: None,
+            art: None,
+            folder_art: None,
+            replay_gain: ReplayGain::default(),
+            loudness: None,
             deleted: true,
+            deleted_since: None,
+            fingerprint: Default::default(),
+            added: None,
+            offline: false,
+            duplicate_of: None,
+            inferred: BTreeSet::new(),
+            normalized: BTreeSet::new(),
+        }
+    }
+}
+
+impl SongId {
+    /// Creates new [`SongId`] from the given number
+    pub fn new(id: u64) -> Self {
+        Self(id)
+    }
+
+    /// Checks whether the ID is assigned, songs that weren't added to the
+    /// library yet don't have their ID assigned
+    pub fn is_assigned(&self) -> bool {
+        self.0 != 0
+    }
+
+    /// Gets the ID as number
+    pub fn get(&self) -> u64 {
+        self.0
+    }
+}
+
+impl Fingerprint {
+    /// Gets fingerprint of the file on the given path
+    pub fn load(path: &Path) -> Result<Self> {
+        Ok(Fingerprint::from_metadata(&fs::metadata(path)?))
+    }
+
+    /// Gets fingerprint from the given file metadata
+    pub fn from_metadata(meta: &Metadata) -> Self {
+        Self {
+            modified: meta.modified().ok(),
+            size: meta.len(),
         }
     }
 }
@@ -0,0 +1,206 @@
+use std::{cmp::Ordering, iter::Peekable, str::Chars, time::UNIX_EPOCH};
+
+use serde_derive::{Deserialize, Serialize};
+
+use super::{search::normalize, Song, SongId};
+
+/// Field songs can be sorted by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SortField {
+    Title,
+    Artist,
+    /// Artist of the album, artist of the song when not set
+    AlbumArtist,
+    Album,
+    Disc,
+    Track,
+    Year,
+    Length,
+    Genre,
+    Path,
+    /// Time when the song was added to the library
+    Added,
+}
+
+/// Field songs are sorted by with the sort direction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SortKey {
+    pub field: SortField,
+    /// When true, songs are sorted in descending order
+    pub desc: bool,
+}
+
+/// Order of songs by more fields, songs equal in a field are ordered by
+/// the following fields. Songs missing the value of the field are always
+/// ordered last.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SortOrder(Vec<SortKey>);
+
+/// Value of the field used for sorting
+#[derive(Debug, PartialEq, Eq)]
+enum SortValue {
+    Text(String),
+    Num(u128),
+}
+
+impl SortOrder {
+    /// Creates new [`SortOrder`] by the given keys, the first key is the
+    /// most important
+    pub fn new(keys: Vec<SortKey>) -> Self {
+        Self(keys)
+    }
+
+    /// Gets the most important key of the order
+    pub fn get_primary(&self) -> Option<SortKey> {
+        self.0.first().copied()
+    }
+
+    /// Makes the field the most important. When it already is, its
+    /// direction is reversed. Other fields are kept as less important, so
+    /// each field is in the order at most once.
+    pub fn sort_by(&mut self, field: SortField) {
+        let desc = match self.get_primary() {
+            Some(key) if key.field == field => !key.desc,
+            _ => false,
+        };
+        self.0.retain(|k| k.field != field);
+        self.0.insert(0, SortKey { field, desc });
+    }
+
+    /// Sorts the songs, sorting is stable
+    pub fn sort(&self, songs: &[&Song]) -> Vec<SongId> {
+        let mut keyed: Vec<(Vec<Option<SortValue>>, SongId)> = songs
+            .iter()
+            .map(|s| {
+                let values = self.0.iter().map(|k| k.field.value(s));
+                (values.collect(), s.get_id())
+            })
+            .collect();
+
+        keyed.sort_by(|(a, _), (b, _)| {
+            self.0
+                .iter()
+                .zip(a.iter().zip(b))
+                .map(|(key, (a, b))| compare(a, b, key.desc))
+                .find(|o| o.is_ne())
+                .unwrap_or(Ordering::Equal)
+        });
+        keyed.into_iter().map(|(_, id)| id).collect()
+    }
+}
+
+impl Default for SortOrder {
+    fn default() -> Self {
+        let key = |field| SortKey { field, desc: false };
+        Self(vec![
+            key(SortField::AlbumArtist),
+            key(SortField::Album),
+            key(SortField::Disc),
+            key(SortField::Track),
+        ])
+    }
+}
+
+impl SortField {
+    /// Gets value of the field of the given song, None when the song
+    /// doesn't have the field set
+    fn value(&self, song: &Song) -> Option<SortValue> {
+        let text = |text: &str| {
+            // Songs without tags have '-' set
+            (!text.is_empty() && text != "-")
+                .then(|| SortValue::Text(normalize(text)))
+        };
+        match self {
+            SortField::Title => text(song.get_name()),
+            SortField::Artist => text(song.get_artist()),
+            SortField::AlbumArtist => text(song.get_album_artist())
+                .or_else(|| text(song.get_artist())),
+            SortField::Album => text(song.get_album()),
+            // Songs without disc number are usually on the only disc
+            SortField::Disc => {
+                Some(SortValue::Num(song.get_disc().unwrap_or(1).into()))
+            }
+            SortField::Track => {
+                song.get_track().map(|t| SortValue::Num(t.into()))
+            }
+            SortField::Genre => text(song.get_genre()),
+            SortField::Path => text(&song.get_path().to_string_lossy()),
+            SortField::Year => {
+                song.get_year().map(|y| SortValue::Num(y.max(0) as u128))
+            }
+            SortField::Length => {
+                Some(SortValue::Num(song.get_length().as_millis()))
+            }
+            SortField::Added => song
+                .get_added()
+                .and_then(|a| a.duration_since(UNIX_EPOCH).ok())
+                .map(|a| SortValue::Num(a.as_millis())),
+        }
+    }
+}
+
+/// Compares the values, missing values are last in both directions
+fn compare(
+    a: &Option<SortValue>,
+    b: &Option<SortValue>,
+    desc: bool,
+) -> Ordering {
+    let ord = match (a, b) {
+        (None, None) => return Ordering::Equal,
+        (None, Some(_)) => return Ordering::Greater,
+        (Some(_), None) => return Ordering::Less,
+        (Some(SortValue::Text(a)), Some(SortValue::Text(b))) => {
+            natural_cmp(a, b)
+        }
+        (Some(SortValue::Num(a)), Some(SortValue::Num(b))) => a.cmp(b),
+        _ => Ordering::Equal,
+    };
+    if desc {
+        ord.reverse()
+    } else {
+        ord
+    }
+}
+
+/// Compares the texts so that numbers in them are compared by their value,
+/// e.g. `Track 2` is before `Track 10`
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+    loop {
+        match (a.peek(), b.peek()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(ca), Some(cb))
+                if ca.is_ascii_digit() && cb.is_ascii_digit() =>
+            {
+                let ord = number(&mut a).cmp(&number(&mut b));
+                if ord.is_ne() {
+                    return ord;
+                }
+            }
+            (Some(ca), Some(cb)) => {
+                let ord = ca.cmp(cb);
+                if ord.is_ne() {
+                    return ord;
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+/// Reads number from the start of the text, returns its digits without
+/// leading zeros, so that comparing them compares the numbers
+fn number(text: &mut Peekable<Chars>) -> (usize, String) {
+    let mut digits = String::new();
+    while let Some(c) = text.next_if(|c| c.is_ascii_digit()) {
+        if c != '0' || !digits.is_empty() {
+            digits.push(c);
+        }
+    }
+    (digits.len(), digits)
+}
@@ -0,0 +1,333 @@
+use std::{collections::BTreeMap, fs, path::Path, path::PathBuf};
+
+use audiotags::{AudioTag, Id3v2Tag, MimeType, Picture, Tag};
+use eyre::{Report, Result};
+use id3::TagLike;
+use serde_derive::{Deserialize, Serialize};
+
+use super::Song;
+
+/// Tag field of a song that can be edited
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+)]
+pub enum TagField {
+    Title,
+    Artist,
+    Album,
+    AlbumArtist,
+    Year,
+    Date,
+    Track,
+    TrackTotal,
+    Disc,
+    DiscTotal,
+    Genre,
+    Composer,
+    Comment,
+}
+
+/// Change of the art embedded in songs
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum ArtEdit {
+    /// Art of the songs isn't changed
+    #[default]
+    Keep,
+    /// Art is replaced by the image on the given path
+    Set(PathBuf),
+    /// Art is removed from the songs
+    Remove,
+}
+
+/// Changes of the tags of songs, fields that aren't set keep their
+/// existing values. Fields set to empty string are removed from the tags.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TagEdit {
+    pub fields: BTreeMap<TagField, String>,
+    pub art: ArtEdit,
+}
+
+impl TagField {
+    /// All the fields in the order they're shown
+    pub const ALL: [TagField; 13] = [
+        TagField::Title,
+        TagField::Artist,
+        TagField::Album,
+        TagField::AlbumArtist,
+        TagField::Year,
+        TagField::Date,
+        TagField::Track,
+        TagField::TrackTotal,
+        TagField::Disc,
+        TagField::DiscTotal,
+        TagField::Genre,
+        TagField::Composer,
+        TagField::Comment,
+    ];
+
+    /// Gets name of the field shown to the user
+    pub fn name(&self) -> &'static str {
+        match self {
+            TagField::Title => "Title",
+            TagField::Artist => "Artist",
+            TagField::Album => "Album",
+            TagField::AlbumArtist => "Album artist",
+            TagField::Year => "Year",
+            TagField::Date => "Date",
+            TagField::Track => "Track",
+            TagField::TrackTotal => "Track total",
+            TagField::Disc => "Disc",
+            TagField::DiscTotal => "Disc total",
+            TagField::Genre => "Genre",
+            TagField::Composer => "Composer",
+            TagField::Comment => "Comment",
+        }
+    }
+
+    /// Gets value of the field of the given song, empty when not set
+    pub fn get(&self, song: &Song) -> String {
+        let number = |n: Option<u16>| n.map(|n| n.to_string());
+        match self {
+            TagField::Title => Some(song.get_name().to_owned()),
+            TagField::Artist => Some(song.get_artist().to_owned()),
+            TagField::Album => Some(song.get_album().to_owned()),
+            TagField::AlbumArtist => Some(song.get_album_artist().to_owned()),
+            TagField::Year => song.get_year().map(|y| y.to_string()),
+            TagField::Date => song.get_date().map(|d| d.to_owned()),
+            TagField::Track => number(song.get_track()),
+            TagField::TrackTotal => number(song.get_track_total()),
+            TagField::Disc => number(song.get_disc()),
+            TagField::DiscTotal => number(song.get_disc_total()),
+            TagField::Genre => Some(song.get_genre().to_owned()),
+            TagField::Composer => Some(song.get_composer().to_owned()),
+            TagField::Comment => Some(song.get_comment().to_owned()),
+        }
+        // Missing tags are loaded as '-'
+        .filter(|v| v != "-")
+        .unwrap_or_default()
+    }
+
+    /// Checks whether the value is valid for the field
+    pub(super) fn check(&self, value: &str) -> Result<()> {
+        let value = value.trim();
+        if value.is_empty() {
+            return Ok(());
+        }
+        let valid = match self {
+            TagField::Year => value.parse::<i32>().is_ok(),
+            TagField::Track
+            | TagField::TrackTotal
+            | TagField::Disc
+            | TagField::DiscTotal => value.parse::<u16>().is_ok(),
+            TagField::Date => value.parse::<id3::Timestamp>().is_ok(),
+            _ => true,
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(Report::msg(format!(
+                "Invalid {}: '{value}'",
+                self.name().to_lowercase()
+            )))
+        }
+    }
+
+    /// Sets the field in the tag, empty value removes it. Date and comment
+    /// can't be set by audiotags, so they're skipped.
+    fn write(&self, tag: &mut dyn AudioTag, value: &str) {
+        if value.is_empty() {
+            match self {
+                TagField::Title => tag.remove_title(),
+                TagField::Artist => tag.remove_artist(),
+                TagField::Album => tag.remove_album_title(),
+                TagField::AlbumArtist => tag.remove_album_artist(),
+                TagField::Year => tag.remove_year(),
+                TagField::Track => tag.remove_track_number(),
+                TagField::TrackTotal => tag.remove_total_tracks(),
+                TagField::Disc => tag.remove_disc_number(),
+                TagField::DiscTotal => tag.remove_total_discs(),
+                TagField::Genre => tag.remove_genre(),
+                TagField::Composer => tag.remove_composer(),
+                TagField::Date | TagField::Comment => {}
+            }
+            return;
+        }
+
+        // Numbers are checked before writing
+        let number = || value.parse().unwrap_or_default();
+        match self {
+            TagField::Title => tag.set_title(value),
+            TagField::Artist => tag.set_artist(value),
+            TagField::Album => tag.set_album_title(value),
+            TagField::AlbumArtist => tag.set_album_artist(value),
+            TagField::Year => tag.set_year(value.parse().unwrap_or_default()),
+            TagField::Track => tag.set_track_number(number()),
+            TagField::TrackTotal => tag.set_total_tracks(number()),
+            TagField::Disc => tag.set_disc_number(number()),
+            TagField::DiscTotal => tag.set_total_discs(number()),
+            TagField::Genre => tag.set_genre(value),
+            TagField::Composer => tag.set_composer(value.to_owned()),
+            TagField::Date | TagField::Comment => {}
+        }
+    }
+}
+
+impl TagEdit {
+    /// Checks whether the edit changes anything
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty() && self.art == ArtEdit::Keep
+    }
+
+    /// Checks whether the values of the fields are valid
+    pub fn check(&self) -> Result<()> {
+        for (field, value) in self.fields.iter() {
+            field.check(value)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the changes to the tags of the song file on the given path.
+    /// Only MP3, FLAC and MP4 files can be written.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        self.check()?;
+        let path_str =
+            path.to_str().ok_or(Report::msg("Path isn't valid UTF-8"))?;
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase())
+            .ok_or(Report::msg(
+                "Writing tags isn't supported for this file",
+            ))?;
+
+        // Nothing is written when any of the fields can't be written
+        let get = |field| self.fields.get(&field).map(|v| v.trim());
+        let date = get(TagField::Date);
+        let comment = get(TagField::Comment);
+        let extra = date.is_some() || comment.is_some();
+        if extra
+            && !matches!(ext.as_str(), "mp3" | "flac" | "m4a" | "m4b" | "mp4")
+        {
+            return Err(Report::msg(
+                "Writing date and comment isn't supported for this file",
+            ));
+        }
+
+        let mut tag = match Tag::new().read_from_path(path) {
+            Ok(tag) => tag,
+            // MP3 files don't have to have any tag yet
+            Err(_) if ext == "mp3" => Box::new(Id3v2Tag::new()),
+            Err(e) => return Err(e.into()),
+        };
+        for (field, value) in self.fields.iter() {
+            field.write(tag.as_mut(), value.trim());
+        }
+        let art = match &self.art {
+            ArtEdit::Set(art) => Some(fs::read(art)?),
+            _ => None,
+        };
+        match (&self.art, &art) {
+            (ArtEdit::Set(art_path), Some(data)) => {
+                tag.set_album_cover(Picture {
+                    data,
+                    mime_type: TagEdit::mime_type(art_path)?,
+                })
+            }
+            (ArtEdit::Remove, _) => tag.remove_album_cover(),
+            _ => {}
+        }
+        tag.write_to_path(path_str)?;
+
+        if extra {
+            TagEdit::write_extra(path, &ext, date, comment)?;
+        }
+        Ok(())
+    }
+
+    /// Writes date and comment that audiotags can't write, None values are
+    /// kept and empty values are removed
+    fn write_extra(
+        path: &Path,
+        ext: &str,
+        date: Option<&str>,
+        comment: Option<&str>,
+    ) -> Result<()> {
+        match ext {
+            "mp3" => {
+                let mut tag = id3::Tag::read_from_path(path)?;
+                match date {
+                    Some("") => tag.remove_date_recorded(),
+                    Some(date) => tag.set_date_recorded(date.parse()?),
+                    None => {}
+                }
+                if let Some(comment) = comment {
+                    tag.remove_comment(Some(""), None);
+                    if !comment.is_empty() {
+                        tag.add_frame(id3::frame::Comment {
+                            lang: "eng".to_owned(),
+                            description: String::new(),
+                            text: comment.to_owned(),
+                        });
+                    }
+                }
+                tag.write_to_path(path, id3::Version::Id3v24)?;
+            }
+            "flac" => {
+                let mut tag = metaflac::Tag::read_from_path(path)?;
+                for (key, value) in [("DATE", date), ("COMMENT", comment)] {
+                    match value {
+                        Some("") => tag.remove_vorbis(key),
+                        Some(value) => tag.set_vorbis(key, vec![value]),
+                        None => {}
+                    }
+                }
+                tag.write_to_path(path)?;
+            }
+            "m4a" | "m4b" | "mp4" => {
+                let mut tag = mp4ameta::Tag::read_from_path(path)?;
+                match date {
+                    Some("") => tag.remove_year(),
+                    Some(date) => tag.set_year(date),
+                    None => {}
+                }
+                match comment {
+                    Some("") => tag.remove_comments(),
+                    Some(comment) => tag.set_comment(comment),
+                    None => {}
+                }
+                tag.write_to_path(path)?;
+            }
+            _ => {
+                return Err(Report::msg(
+                    "Writing date and comment isn't supported for this file",
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    /// Gets mime type of the image on the given path by its extension
+    fn mime_type(path: &Path) -> Result<MimeType> {
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase());
+        match ext.as_deref() {
+            Some("jpg" | "jpeg") => Ok(MimeType::Jpeg),
+            Some("png") => Ok(MimeType::Png),
+            Some("bmp") => Ok(MimeType::Bmp),
+            Some("gif") => Ok(MimeType::Gif),
+            Some("tif" | "tiff") => Ok(MimeType::Tiff),
+            _ => Err(Report::msg("Unsupported art image type")),
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use std::path::Path;
+
+use eyre::{Report, Result};
+
+use super::TagField;
+
+/// Pattern of song paths used to infer missing tags, such as
+/// `{artist}/{album}/{track} - {title}`. The last part of the pattern is
+/// matched with the file name without extension and the other parts with
+/// the directories above it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagPattern {
+    components: Vec<Vec<PatternPart>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum PatternPart {
+    Text(String),
+    Field(TagField),
+}
+
+impl TagPattern {
+    /// Parses the pattern
+    pub fn parse(pattern: &str) -> Result<Self> {
+        let components = pattern
+            .trim()
+            .split(['/', '\\'])
+            .map(TagPattern::parse_component)
+            .collect::<Result<Vec<_>>>()?;
+
+        if components.iter().any(|c| c.is_empty()) {
+            return Err(Report::msg("Pattern can't have empty parts"));
+        }
+        if !components
+            .iter()
+            .flatten()
+            .any(|p| matches!(p, PatternPart::Field(_)))
+        {
+            return Err(Report::msg("Pattern must contain a field"));
+        }
+        Ok(Self { components })
+    }
+
+    /// Infers tags from the path of the song, relative to its search path.
+    /// Returns None when the path doesn't match the pattern.
+    pub fn infer(&self, path: &Path) -> Option<Vec<(TagField, String)>> {
+        let stem = path.file_stem()?.to_string_lossy();
+        let mut names = vec![stem.into_owned()];
+        names.extend(
+            path.parent()?
+                .iter()
+                .rev()
+                .map(|n| n.to_string_lossy().into_owned()),
+        );
+        if names.len() < self.components.len() {
+            return None;
+        }
+
+        let mut values = Vec::new();
+        for (parts, name) in self.components.iter().rev().zip(names.iter()) {
+            if !TagPattern::match_parts(parts, name, &mut values) {
+                return None;
+            }
+        }
+        Some(values)
+    }
+
+    /// Parses one part of the pattern between the path separators
+    fn parse_component(component: &str) -> Result<Vec<PatternPart>> {
+        let mut parts = Vec::new();
+        let mut rest = component;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                parts.push(PatternPart::Text(rest[..start].to_owned()));
+            }
+            let end = match rest[start..].find('}') {
+                Some(end) => start + end,
+                None => return Err(Report::msg("Pattern field isn't closed")),
+            };
+
+            let name = &rest[start + 1..end];
+            let field = TagField::ALL
+                .into_iter()
+                .find(|f| {
+                    f.name().replace(' ', "").eq_ignore_ascii_case(name.trim())
+                })
+                .ok_or(Report::msg(format!(
+                    "Unknown pattern field '{name}'"
+                )))?;
+            // Two fields next to each other can't be told apart
+            if matches!(parts.last(), Some(PatternPart::Field(_))) {
+                return Err(Report::msg(
+                    "Pattern fields must be separated by text",
+                ));
+            }
+            parts.push(PatternPart::Field(field));
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(PatternPart::Text(rest.to_owned()));
+        }
+        Ok(parts)
+    }
+
+    /// Matches the name with the parts of the pattern, values of the
+    /// fields are added to the given values. Text after a field is matched
+    /// with its first occurrence that gives a valid value.
+    fn match_parts(
+        parts: &[PatternPart],
+        name: &str,
+        values: &mut Vec<(TagField, String)>,
+    ) -> bool {
+        let (part, rest) = match parts.split_first() {
+            Some(split) => split,
+            None => return name.is_empty(),
+        };
+
+        match part {
+            PatternPart::Text(text) => {
+                match name.strip_prefix(text.as_str()) {
+                    Some(name) => TagPattern::match_parts(rest, name, values),
+                    None => false,
+                }
+            }
+            PatternPart::Field(field) => {
+                let ends: Vec<usize> = match rest.first() {
+                    Some(PatternPart::Text(text)) => name
+                        .match_indices(text.as_str())
+                        .map(|(i, _)| i)
+                        .collect(),
+                    _ => vec![name.len()],
+                };
+                for end in ends {
+                    let value = name[..end].trim();
+                    if value.is_empty() || field.check(value).is_err() {
+                        continue;
+                    }
+                    values.push((*field, value.to_owned()));
+                    if TagPattern::match_parts(rest, &name[end..], values) {
+                        return true;
+                    }
+                    values.pop();
+                }
+                false
+            }
+        }
+    }
+}
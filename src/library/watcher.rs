@@ -0,0 +1,62 @@
+use std::{collections::HashSet, path::PathBuf, time::Duration};
+
+use eyre::Result;
+use log::error;
+use notify_debouncer_mini::{
+    new_debouncer,
+    notify::{RecommendedWatcher, RecursiveMode},
+    DebounceEventResult, Debouncer,
+};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{
+    config::Config,
+    gui::app::{LibMsg, Msg},
+};
+
+/// Watches library search paths and sends changed paths to the app
+pub struct LibWatcher {
+    debouncer: Debouncer<RecommendedWatcher>,
+}
+
+impl LibWatcher {
+    /// Time for which changes are collected before they are sent
+    const DEBOUNCE: Duration = Duration::from_millis(500);
+
+    /// Creates new [`LibWatcher`] watching all the library search paths
+    pub fn new(conf: &Config, sender: UnboundedSender<Msg>) -> Result<Self> {
+        let debouncer = new_debouncer(
+            LibWatcher::DEBOUNCE,
+            move |res: DebounceEventResult| match res {
+                Ok(events) => {
+                    let paths: HashSet<PathBuf> =
+                        events.into_iter().map(|e| e.path).collect();
+                    _ = sender.send(Msg::Lib(LibMsg::Changed(
+                        paths.into_iter().collect(),
+                    )));
+                }
+                Err(e) => error!("Failed to watch library: {e}"),
+            },
+        )?;
+
+        let mut watcher = Self { debouncer };
+        watcher.watch(conf);
+        Ok(watcher)
+    }
+
+    /// Starts watching all the enabled library search paths
+    fn watch(&mut self, conf: &Config) {
+        for path in conf.get_paths().iter().filter(|p| p.get_enabled()) {
+            let mode = if path.get_recursive() {
+                RecursiveMode::Recursive
+            } else {
+                RecursiveMode::NonRecursive
+            };
+
+            let path = path.get_path();
+            if let Err(e) = self.debouncer.watcher().watch(path, mode) {
+                error!("Failed to watch {}: {e}", path.to_string_lossy());
+            }
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use anyhow::Result;
+use cpal::Sample;
+use raplay::{
+    sample_buffer::SampleBufferMut,
+    source::{DeviceConfig, Source, VolumeIterator},
+    Timestamp,
+};
+
+/// Gain of the playing song shared with its source, such as its
+/// ReplayGain. Clones share the same gain.
+#[derive(Debug, Clone)]
+pub struct Gain(Arc<AtomicU32>);
+
+/// Source that multiplies samples of the song by its gain. The gain is a
+/// separate factor from the volume of the sink, so fading of the volume
+/// doesn't change it.
+pub struct GainSource<S: Source> {
+    source: S,
+    gain: Gain,
+}
+
+impl Gain {
+    /// Creates new gain with the given value
+    pub fn new(gain: f32) -> Self {
+        Self(Arc::new(AtomicU32::new(gain.to_bits())))
+    }
+
+    /// Gets the gain
+    pub fn get(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    /// Sets the gain, it applies to the following samples
+    pub fn set(&self, gain: f32) {
+        self.0.store(gain.to_bits(), Ordering::Relaxed);
+    }
+}
+
+impl<S: Source> GainSource<S> {
+    /// Creates source applying the gain to the given source
+    pub fn new(source: S, gain: Gain) -> Self {
+        Self { source, gain }
+    }
+}
+
+impl<S: Source> Source for GainSource<S> {
+    fn init(&mut self, info: &DeviceConfig) -> Result<()> {
+        self.source.init(info)
+    }
+
+    fn read(&mut self, buffer: &mut SampleBufferMut) -> (usize, Result<()>) {
+        let (len, res) = self.source.read(buffer);
+        let gain = self.gain.get();
+        if gain != 1. {
+            match buffer {
+                SampleBufferMut::I8(b) => amplify(&mut b[..len], gain),
+                SampleBufferMut::I16(b) => amplify(&mut b[..len], gain),
+                SampleBufferMut::I32(b) => amplify(&mut b[..len], gain),
+                SampleBufferMut::I64(b) => amplify(&mut b[..len], gain),
+                SampleBufferMut::U8(b) => amplify(&mut b[..len], gain),
+                SampleBufferMut::U16(b) => amplify(&mut b[..len], gain),
+                SampleBufferMut::U32(b) => amplify(&mut b[..len], gain),
+                SampleBufferMut::U64(b) => amplify(&mut b[..len], gain),
+                SampleBufferMut::F32(b) => amplify(&mut b[..len], gain),
+                SampleBufferMut::F64(b) => amplify(&mut b[..len], gain),
+                _ => {}
+            }
+        }
+        (len, res)
+    }
+
+    fn preferred_config(&mut self) -> Option<DeviceConfig> {
+        self.source.preferred_config()
+    }
+
+    fn volume(&mut self, volume: VolumeIterator) -> bool {
+        self.source.volume(volume)
+    }
+
+    fn seek(&mut self, time: Duration) -> Result<Timestamp> {
+        self.source.seek(time)
+    }
+
+    fn get_time(&self) -> Option<Timestamp> {
+        self.source.get_time()
+    }
+}
+
+/// Multiplies the samples by the gain
+fn amplify<T: Sample>(samples: &mut [T], gain: f32)
+where
+    T::Float: From<f32>,
+{
+    for s in samples.iter_mut() {
+        *s = s.mul_amp(gain.into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Source of full samples that applies the volume, as the song
+    /// sources do
+    struct Full(VolumeIterator);
+
+    impl Source for Full {
+        fn init(&mut self, _: &DeviceConfig) -> Result<()> {
+            Ok(())
+        }
+
+        fn read(
+            &mut self,
+            buffer: &mut SampleBufferMut,
+        ) -> (usize, Result<()>) {
+            if let SampleBufferMut::F32(b) = buffer {
+                for s in b.iter_mut() {
+                    *s = self.0.next_vol();
+                }
+            }
+            (buffer.len(), Ok(()))
+        }
+
+        fn volume(&mut self, volume: VolumeIterator) -> bool {
+            self.0 = volume;
+            true
+        }
+    }
+
+    #[test]
+    fn gain_is_constant_across_fade() {
+        let mut src =
+            GainSource::new(Full(Default::default()), Gain::new(0.5));
+        let fade = VolumeIterator::linear(0., 1., 8, 1);
+        assert!(src.volume(fade));
+
+        let mut samples = [0.; 16];
+        let (len, res) = src.read(&mut SampleBufferMut::F32(&mut samples));
+        assert!(res.is_ok());
+        assert_eq!(len, samples.len());
+        let mut volume = fade;
+        for s in samples {
+            assert!((s - volume.next_vol() * 0.5).abs() < 1e-6);
+        }
+    }
+}
@@ -1,3 +1,4 @@
+mod gain;
 mod msg;
 mod player;
 mod sinker;
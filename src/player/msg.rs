@@ -2,12 +2,14 @@ use std::time::Duration;
 
 use serde_derive::{Deserialize, Serialize};
 
+use crate::library::{SongId, SortField};
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
 pub enum PlayerMsg {
     Play(Option<bool>),
     Next(Option<usize>),
     Prev(Option<usize>),
-    PlaySong(usize, bool),
+    PlaySong(SongId, bool),
     SeekTo(Duration),
     SongEnd,
     Volume(f32),
@@ -15,4 +17,5 @@ pub enum PlayerMsg {
     VolumeDown(Option<f32>),
     Mute(Option<bool>),
     Shuffle,
+    Sort(SortField),
 }
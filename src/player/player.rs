@@ -1,4 +1,5 @@
 use std::{
+    collections::HashSet,
     fs::{self, File},
     time::Duration,
 };
@@ -15,7 +16,7 @@ use crate::{
     config::Config,
     generate_struct,
     gui::app::{BumpApp, Msg},
-    library::{Library, Song},
+    library::{Library, ReplayGain, ReplayGainMode, Song, SongId, SortOrder},
 };
 
 use super::{sinker::Sinker, PlayerMsg};
@@ -30,7 +31,7 @@ pub enum PlayState {
 
 generate_struct! {
     pub Player {
-        playlist: Vec<usize>,
+        playlist: Vec<SongId>,
         ;
         current: Option<usize>,
         volume: f32,
@@ -40,6 +41,9 @@ generate_struct! {
         state: PlayState,
         shuffle_current: bool,
         volume_step: f32,
+        replay_gain: ReplayGainMode,
+        preamp: f32,
+        prevent_clipping: bool,
     }
 }
 
@@ -52,7 +56,7 @@ impl Player {
     ) -> Self {
         let mut plr = Player::load(config, library, sender);
         if plr.playlist.is_empty() && library.count() > 0 {
-            plr.playlist = (0..library.count()).collect();
+            plr.playlist = library.get_ids();
         }
         plr
     }
@@ -78,9 +82,12 @@ impl Player {
             current: data.current,
             volume: data.volume,
             mute: data.mute,
-            playlist: data.playlist,
+            playlist: data.get_playlist(lib),
             shuffle_current: config.get_shuffle_current(),
             volume_step: 0.1,
+            replay_gain: ReplayGainMode::Off,
+            preamp: 0.,
+            prevent_clipping: true,
             changed: true,
         };
         res.load_config(config);
@@ -93,6 +100,10 @@ impl Player {
     pub fn load_config(&mut self, config: &Config) {
         self.shuffle_current = config.get_shuffle_current();
         self.volume_step = config.get_volume_step();
+        self.replay_gain = config.get_replay_gain();
+        let max = ReplayGain::MAX_PREAMP;
+        self.preamp = config.get_replay_gain_preamp().clamp(-max, max);
+        self.prevent_clipping = config.get_prevent_clipping();
     }
 
     /// Saves player to the json
@@ -102,6 +113,7 @@ impl Player {
         }
 
         let data = PlayerSave {
+            version: PLAYER_VERSION,
             current: self.current,
             volume: self.volume,
             mute: self.mute,
@@ -149,27 +161,28 @@ impl Player {
         }
     }
 
-    /// Plays next song
+    /// Plays next song, offline songs are skipped
     pub fn next(&mut self, num: Option<usize>, lib: &Library) {
         let num = num.unwrap_or(1);
         if let Some(current) = self.get_current() {
-            self.play_at(
-                lib,
-                (current + num) % self.get_playlist().len(),
-                self.is_playing(),
-            );
+            let index = (current + num) % self.get_playlist().len();
+            match self.find_available(lib, index, true) {
+                Some(index) => self.play_at(lib, index, self.is_playing()),
+                None => self.stop(),
+            }
         }
     }
 
-    /// Plays previous song
+    /// Plays previous song, offline songs are skipped
     pub fn prev(&mut self, num: Option<usize>, lib: &Library) {
         let num = num.unwrap_or(1);
         if let Some(current) = self.get_current() {
-            self.play_at(
-                lib,
-                current.checked_sub(num).unwrap_or(self.playlist.len() - 1),
-                self.is_playing(),
-            );
+            let index =
+                current.checked_sub(num).unwrap_or(self.playlist.len() - 1);
+            match self.find_available(lib, index, false) {
+                Some(index) => self.play_at(lib, index, self.is_playing()),
+                None => self.stop(),
+            }
         }
     }
 
@@ -238,7 +251,7 @@ impl Player {
     }
 
     /// Gets current id as option, returns None when playback stopped
-    pub fn get_current_id(&self) -> Option<usize> {
+    pub fn get_current_id(&self) -> Option<SongId> {
         if self.get_current().is_none() || self.is_stopped() {
             return None;
         }
@@ -249,9 +262,7 @@ impl Player {
     pub fn get_current_song(&self, lib: &Library) -> Song {
         if let Some(current) = self.get_current() {
             match self.playlist.get(current) {
-                Some(index) if !self.is_stopped() => {
-                    lib.get_song(index.to_owned())
-                }
+                Some(&id) if !self.is_stopped() => lib.get_song(id),
                 _ => Song::default(),
             }
         } else {
@@ -301,12 +312,122 @@ impl Player {
         }
     }
 
-    /// Creates playlist from library
-    pub fn create_playlist(&mut self, library: &Library, id: usize) {
-        self.set_playlist((0..library.count()).collect());
+    /// Creates playlist from the given songs, with the given song current
+    pub fn create_playlist(&mut self, ids: Vec<SongId>, id: SongId) {
+        self.set_playlist(ids);
         self.find_current(id);
     }
 
+    /// Sorts the playlist, current song is kept
+    pub fn sort(&mut self, lib: &Library, order: &SortOrder) {
+        let current = self.get_current_id();
+        let songs: Vec<&Song> = self
+            .playlist
+            .iter()
+            .filter_map(|&id| lib.get_song_ref(id))
+            .collect();
+        self.set_playlist(order.sort(&songs));
+        if let Some(id) = current {
+            self.find_current(id);
+        }
+        // Songs around the current song decide its gain in auto mode
+        self.apply_gain(lib);
+    }
+
+    /// Replaces the playlist with the given songs and plays the first song
+    /// that isn't offline
+    pub fn play_songs(&mut self, lib: &Library, ids: Vec<SongId>) {
+        self.set_playlist(ids);
+        match self.find_available(lib, 0, true) {
+            Some(index) => self.play_at(lib, index, true),
+            None => self.stop(),
+        }
+    }
+
+    /// Removes songs with given IDs from the playlist, current song is kept
+    /// when it's not removed, otherwise playback is stopped
+    pub fn remove_songs(&mut self, ids: &[SongId]) {
+        let current = self.get_current().and_then(|c| self.playlist.get(c));
+        let current = current.copied();
+
+        let ids: HashSet<SongId> = ids.iter().copied().collect();
+        self.playlist.retain(|id| !ids.contains(id));
+        self.changed = true;
+
+        match current {
+            Some(id) if !ids.contains(&id) => self.find_current(id),
+            Some(_) => self.stop(),
+            None => {}
+        }
+    }
+
+    /// Replaces songs with given IDs in the playlist with the given song,
+    /// the song is kept in the playlist only once
+    pub fn replace_songs(&mut self, ids: &[SongId], with: SongId) {
+        let current = self.get_current().and_then(|c| self.playlist.get(c));
+        let current = current.copied();
+
+        let ids: HashSet<SongId> = ids.iter().copied().collect();
+        replace_ids(&mut self.playlist, &ids, with);
+        self.changed = true;
+
+        match current {
+            Some(id) if ids.contains(&id) => self.find_current(with),
+            Some(id) => self.find_current(id),
+            None => {}
+        }
+    }
+
+    /// Removes songs with given IDs from the saved player playlist, used
+    /// when the player isn't running. Library is used to read playlists
+    /// saved by older versions.
+    pub fn remove_saved_songs(
+        config: &Config,
+        lib: &Library,
+        ids: &[SongId],
+    ) -> Result<()> {
+        let ids: HashSet<SongId> = ids.iter().copied().collect();
+        Player::edit_saved(config, lib, |playlist, _| {
+            playlist.retain(|id| !ids.contains(id))
+        })
+    }
+
+    /// Replaces songs with given IDs in the saved player playlist with the
+    /// given song, used when the player isn't running
+    pub fn replace_saved_songs(
+        config: &Config,
+        lib: &Library,
+        ids: &[SongId],
+        with: SongId,
+    ) -> Result<()> {
+        let ids: HashSet<SongId> = ids.iter().copied().collect();
+        Player::edit_saved(config, lib, |playlist, current| {
+            replace_ids(playlist, &ids, with);
+            if current.is_some_and(|c| ids.contains(&c)) {
+                *current = Some(with);
+            }
+        })
+    }
+
+    /// Applies ReplayGain of the current song to the playback volume
+    pub fn apply_gain(&mut self, lib: &Library) {
+        let gain = match self.current.and_then(|c| {
+            let id = *self.playlist.get(c)?;
+            Some((lib.get_song_ref(id)?, self.in_album(lib, c)))
+        }) {
+            Some((song, album)) => song.get_replay_gain().get_volume(
+                self.replay_gain,
+                album,
+                self.preamp,
+                self.prevent_clipping,
+            ),
+            None => 1.,
+        };
+        if let Err(e) = self.sinker.set_gain(gain) {
+            error!("Failed to set ReplayGain: {e}");
+        }
+    }
+
     /// Sets fade duration
     pub fn fade(&mut self, fade: Duration) {
         if let Err(e) = self.sinker.set_fade(fade) {
@@ -339,7 +460,14 @@ impl BumpApp {
             }
             PlayerMsg::PlaySong(id, new) => {
                 if new {
-                    self.player.create_playlist(&self.library, id);
+                    // Playlist is created from the songs shown in library
+                    let ids = if self.shown.contains(&id) {
+                        self.shown.clone()
+                    } else {
+                        self.library.get_ids()
+                    };
+                    self.player.create_playlist(ids, id);
+                    self.gui.clear_playlist_sort();
                 } else {
                     self.player.find_current(id)
                 }
@@ -355,7 +483,15 @@ impl BumpApp {
             PlayerMsg::SongEnd => self.player.next(Some(1), &self.library),
             PlayerMsg::Volume(vol) => self.player.set_vol(vol),
             PlayerMsg::Mute(mute) => self.player.mute(mute),
-            PlayerMsg::Shuffle => self.player.shuffle(),
+            PlayerMsg::Shuffle => {
+                self.player.shuffle();
+                self.player.apply_gain(&self.library);
+                self.gui.clear_playlist_sort();
+            }
+            PlayerMsg::Sort(field) => {
+                let order = self.gui.sort_playlist(field);
+                self.player.sort(&self.library, order);
+            }
             PlayerMsg::VolumeUp(step) => self.player.volume_up(step),
             PlayerMsg::VolumeDown(step) => self.player.volume_down(step),
             _ => self.player.stop(),
@@ -369,6 +505,7 @@ impl BumpApp {
 impl Player {
     /// Loads song from the library
     fn load_song(&mut self, lib: &Library, id: usize, play: bool) {
+        self.apply_gain(lib);
         match self.sinker.load(lib, self.get_playlist()[id], play) {
             Ok(_) => self.set_state(play),
             Err(e) => error!("Failed to load the song: {e}"),
@@ -385,8 +522,79 @@ impl Player {
         }
     }
 
+    /// Edits the saved player playlist with the given function, which gets
+    /// the playlist and ID of the current song
+    fn edit_saved<F>(config: &Config, lib: &Library, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut Vec<SongId>, &mut Option<SongId>),
+    {
+        let path = config.get_player_path();
+        let data = match fs::read_to_string(path) {
+            Ok(data) => serde_json::from_str::<PlayerLoad>(&data)?,
+            Err(_) => return Ok(()),
+        };
+
+        let mut playlist = data.get_playlist(lib);
+        let mut current = data.current.and_then(|c| playlist.get(c)).copied();
+        f(&mut playlist, &mut current);
+
+        let save = PlayerSave {
+            version: PLAYER_VERSION,
+            current: current
+                .and_then(|id| playlist.iter().position(|&s| s == id)),
+            volume: data.volume,
+            mute: data.mute,
+            playlist: &playlist,
+        };
+        fs::write(path, serde_json::to_string::<PlayerSave>(&save)?)?;
+
+        Ok(())
+    }
+
+    /// Checks whether the song on the given index is played as part of its
+    /// album, which is when the previous or the next song in the playlist
+    /// is from the same album
+    fn in_album(&self, lib: &Library, index: usize) -> bool {
+        let album = |i: Option<usize>| {
+            let song = lib.get_song_ref(*self.playlist.get(i?)?)?;
+            let name = song.get_album().trim();
+            (!name.is_empty() && name != "-")
+                .then(|| (name.to_owned(), song.get_album_artist().to_owned()))
+        };
+        let current = match album(Some(index)) {
+            Some(current) => current,
+            None => return false,
+        };
+        [index.checked_sub(1), Some(index + 1)]
+            .into_iter()
+            .any(|i| album(i).as_ref() == Some(&current))
+    }
+
+    /// Finds index of the first song in the playlist, starting at the given
+    /// index, that isn't offline
+    fn find_available(
+        &self,
+        lib: &Library,
+        index: usize,
+        forward: bool,
+    ) -> Option<usize> {
+        let len = self.playlist.len();
+        (0..len)
+            .map(|i| {
+                if forward {
+                    (index + i) % len
+                } else {
+                    (index + len - i) % len
+                }
+            })
+            .find(|&i| {
+                lib.get_song_ref(self.playlist[i])
+                    .is_some_and(|s| !s.get_offline())
+            })
+    }
+
     /// Finds current
-    fn find_current(&mut self, id: usize) {
+    fn find_current(&mut self, id: SongId) {
         self.set_current(self.playlist.iter().position(|&x| x == id));
     }
 
@@ -433,6 +641,9 @@ impl Default for Player {
             playlist: Vec::new(),
             shuffle_current: true,
             volume_step: 0.1,
+            replay_gain: ReplayGainMode::Off,
+            preamp: 0.,
+            prevent_clipping: true,
             changed: true,
         }
     }
@@ -441,8 +652,16 @@ impl Default for Player {
 //>=========================================================================<//
 //                 Structs for saving and loading the player                 //
 //>=========================================================================<//
+
+/// Version of the saved player, before version 1 playlist contained indexes
+/// of the songs in the library instead of their IDs
+const PLAYER_VERSION: u32 = 1;
+
 #[derive(Deserialize)]
 struct PlayerLoad {
+    /// Version of the saved player
+    #[serde(default)]
+    version: u32,
     /// Index of the currently playing song
     current: Option<usize>,
     /// Volume of the playback
@@ -450,13 +669,29 @@ struct PlayerLoad {
     /// When true playback is muted
     mute: bool,
     /// Current playlist
-    playlist: Vec<usize>,
+    playlist: Vec<u64>,
+}
+
+impl PlayerLoad {
+    /// Gets the playlist, playlists of older versions are converted to IDs
+    fn get_playlist(&self, lib: &Library) -> Vec<SongId> {
+        if self.version >= 1 {
+            return self.playlist.iter().map(|&id| SongId::new(id)).collect();
+        }
+
+        self.playlist
+            .iter()
+            .filter_map(|&i| lib.get_songs().get(i as usize))
+            .map(|s| s.get_id())
+            .collect()
+    }
 }
 
 impl Default for PlayerLoad {
     /// Default values for PlayerLoad
     fn default() -> Self {
         Self {
+            version: PLAYER_VERSION,
             current: None,
             volume: 1.,
             mute: false,
@@ -467,6 +702,8 @@ impl Default for PlayerLoad {
 
 #[derive(Serialize)]
 struct PlayerSave<'a> {
+    /// Version of the saved player
+    version: u32,
     /// Index of the currently playing song
     current: Option<usize>,
     /// Volume of the playback
@@ -474,5 +711,21 @@ struct PlayerSave<'a> {
     /// When true playback is muted
     mute: bool,
     /// Current playlist
-    playlist: &'a Vec<usize>,
+    playlist: &'a Vec<SongId>,
+}
+
+/// Replaces songs with given IDs in the playlist with the given song, the
+/// song is kept only on its first position
+fn replace_ids(
+    playlist: &mut Vec<SongId>,
+    ids: &HashSet<SongId>,
+    with: SongId,
+) {
+    let mut kept = false;
+    playlist.retain_mut(|id| {
+        if ids.contains(id) {
+            *id = with;
+        }
+        *id != with || !std::mem::replace(&mut kept, true)
+    });
 }
@@ -6,12 +6,19 @@ use raplay::{
     CallbackInfo, Sink, Timestamp,
 };
 
-use crate::library::Library;
+use crate::library::{Library, SongId};
+
+use super::gain::{Gain, GainSource};
 
 /// Implements core player functions
 pub struct Sinker {
     sink: Sink,
     symph: SymphOptions,
+    /// Volume set by the user
+    volume: f32,
+    /// Volume multiplier of the playing song, such as its ReplayGain,
+    /// applied to the song samples separately from the volume
+    gain: Gain,
 }
 
 impl Sinker {
@@ -20,6 +27,8 @@ impl Sinker {
         Self {
             sink: Sink::default(),
             symph: SymphOptions::default(),
+            volume: 1.,
+            gain: Gain::new(1.),
         }
     }
 
@@ -27,16 +36,17 @@ impl Sinker {
     pub fn load(
         &mut self,
         library: &Library,
-        index: usize,
+        id: SongId,
         play: bool,
     ) -> Result<()> {
-        let song = library.get_songs().get(index);
-        if song.is_none() {
-            return Err(Report::msg("Song can't be accessed"));
-        }
-        let file = File::open(library.get_songs()[index].get_path())?;
+        let song = match library.get_song_ref(id) {
+            Some(song) => song,
+            None => return Err(Report::msg("Song can't be accessed")),
+        };
+        let file = File::open(song.get_path())?;
         let src = Symph::try_new(file, &self.symph)?;
-        self.sink.load(src, play)?;
+        self.sink
+            .load(GainSource::new(src, self.gain.clone()), play)?;
         Ok(())
     }
 
@@ -61,9 +71,16 @@ impl Sinker {
         Ok(())
     }
 
-    /// Sets the playback volume
+    /// Sets the playback volume, gain of the song is applied on top of it
     pub fn set_volume(&mut self, volume: f32) -> Result<()> {
         self.sink.volume(volume * volume)?;
+        self.volume = volume;
+        Ok(())
+    }
+
+    /// Sets volume multiplier of the playing song
+    pub fn set_gain(&mut self, gain: f32) -> Result<()> {
+        self.gain.set(gain);
         Ok(())
     }
 
@@ -1,22 +1,70 @@
 use std::fs;
 use std::io::{prelude::*, BufReader};
 use std::net::TcpStream;
+use std::sync::Mutex;
+
+use log::{error, warn};
+use serde_derive::Serialize;
 
 use crate::gui::app::Msg;
+use crate::library::{ArtCache, ArtSize, Song};
 use crate::player::PlayerMsg;
 
 pub struct Server;
 
+/// Song playing in the app, shown by the web remote
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct NowPlaying {
+    pub title: String,
+    pub artist: String,
+    /// URL of the song art, None when the song doesn't have art
+    pub art: Option<String>,
+}
+
+impl NowPlaying {
+    /// Creates [`NowPlaying`] of the given song
+    pub fn new(song: &Song) -> Self {
+        Self {
+            title: song.get_name().to_owned(),
+            artist: song.get_artist().to_owned(),
+            art: song.get_art().map(|id| {
+                format!("/art/{}", ArtCache::file_name(id, ArtSize::Small))
+            }),
+        }
+    }
+}
+
 impl Server {
-    pub fn handle_client(mut stream: &TcpStream) -> Option<Msg> {
+    pub fn handle_client(
+        mut stream: &TcpStream,
+        art: &ArtCache,
+        playing: &Mutex<NowPlaying>,
+    ) -> Option<Msg> {
         let buf_reader = BufReader::new(&mut stream);
-        let request = buf_reader.lines().next().unwrap().unwrap();
+        let request = match buf_reader.lines().next() {
+            Some(Ok(request)) => request,
+            _ => return None,
+        };
 
         let (status, filename) = match request.as_str() {
             "GET / HTTP/1.1" => ("HTTP/1.1 200 OK", "index.html"),
             "GET /instance/play HTTP/1.1" => {
                 return Some(Msg::Plr(PlayerMsg::Play(None)));
             }
+            "GET /instance/current HTTP/1.1" => {
+                let json = match playing.lock() {
+                    Ok(playing) => serde_json::to_string(&*playing),
+                    Err(_) => serde_json::to_string(&NowPlaying::default()),
+                }
+                .unwrap_or_default();
+                Server::send_response(
+                    stream,
+                    "HTTP/1.1 200 OK".to_owned(),
+                    "application/json".to_owned(),
+                    json.as_bytes(),
+                );
+                return None;
+            }
             s if s.starts_with("GET /icons") => {
                 let parts: Vec<_> = request.split_whitespace().collect();
                 let filename = parts[1];
@@ -26,7 +74,25 @@ impl Server {
                     stream,
                     "HTTP/1.1 200 OK".to_owned(),
                     "image/svg+xml".to_owned(),
-                    image,
+                    image.as_bytes(),
+                );
+                return None;
+            }
+            s if s.starts_with("GET /art/") => {
+                let parts: Vec<_> = request.split_whitespace().collect();
+                let name = parts[1].trim_start_matches("/art/");
+                let (status, image) = match art
+                    .get_file(name)
+                    .and_then(|path| fs::read(path).ok())
+                {
+                    Some(image) => ("HTTP/1.1 200 OK", image),
+                    None => ("HTTP/1.1 404 Not Found", Vec::new()),
+                };
+                Server::send_response(
+                    stream,
+                    status.to_owned(),
+                    "image/jpeg".to_owned(),
+                    &image,
                 );
                 return None;
             }
@@ -38,13 +104,20 @@ impl Server {
             }
         };
 
-        let content =
-            fs::read_to_string("src/server/".to_owned() + filename).unwrap();
-        let len = content.len();
-        let response =
-            format!("{status}\r\nContent-Length: {len}\r\n\r\n{content}");
-
-        stream.write_all(response.as_bytes()).unwrap();
+        let (status, content) =
+            match fs::read_to_string("src/server/".to_owned() + filename) {
+                Ok(content) => (status, content),
+                Err(e) => {
+                    error!("Failed to read {filename}: {e}");
+                    ("HTTP/1.1 404 Not Found", String::new())
+                }
+            };
+        Server::send_response(
+            stream,
+            status.to_owned(),
+            "text/html".to_owned(),
+            content.as_bytes(),
+        );
         None
     }
 
@@ -52,15 +125,21 @@ impl Server {
         mut stream: &TcpStream,
         status: String,
         content_type: String,
-        content: String,
+        content: &[u8],
     ) {
         let len = content.len();
-        let response = format!(
-            "{}\r\nContent-Type: {}\nContent-Length: {}\r\n\r\n{}",
-            status, content_type, len, content
+        let header = format!(
+            "{}\r\nContent-Type: {}\nContent-Length: {}\r\n\r\n",
+            status, content_type, len
         );
 
-        stream.write_all(response.as_bytes()).unwrap();
+        // Client can close the connection at any time
+        if let Err(e) = stream
+            .write_all(header.as_bytes())
+            .and_then(|_| stream.write_all(content))
+        {
+            warn!("Failed to send response: {e}");
+        }
     }
 
     pub fn send_cli_response(mut stream: &TcpStream, msg: &str) {